@@ -1,10 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use image::{Pixel, RgbImage};
+use image::Rgb;
 use show_image::create_window;
 use std::time::Instant;
 
-use moving_least_squares as mls;
+use moving_least_squares::DeformMethod;
 use moving_least_squares_image as mls_image;
 
 #[show_image::main]
@@ -38,23 +38,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     ];
 
     // Draw source control points.
-    let red: (u8, u8, u8) = (255, 0, 0);
+    let red = Rgb([255, 0, 0]);
     controls_src
         .iter()
-        .for_each(|&p| draw_point(p, 5.0, red, &mut img));
+        .for_each(|&p| mls_image::draw_point(&mut img, p, 5.0, red));
 
     // Create new warped image.
     let warped_img_affine =
-        mls_image::reverse_dense(&img, controls_src, controls_dst, mls::deform_affine);
+        mls_image::reverse_dense(&img, controls_src, controls_dst, DeformMethod::Affine);
     let warped_img_similarity =
-        mls_image::reverse_dense(&img, controls_src, controls_dst, mls::deform_similarity);
+        mls_image::reverse_dense(&img, controls_src, controls_dst, DeformMethod::Similarity);
     let now = Instant::now();
     let warped_img_rigid =
-        mls_image::reverse_dense(&img, controls_src, controls_dst, mls::deform_rigid);
+        mls_image::reverse_dense(&img, controls_src, controls_dst, DeformMethod::Rigid);
     println!("{} ms", now.elapsed().as_millis());
     let now = Instant::now();
     let warped_img_rigid_sparse =
-        mls_image::reverse_sparse(&img, controls_src, controls_dst, 4, mls::deform_rigid);
+        mls_image::reverse_sparse(&img, controls_src, controls_dst, 4, DeformMethod::Rigid);
     println!("{} ms", now.elapsed().as_millis());
 
     // Create a window with default options and display the image.
@@ -85,31 +85,3 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     warped_window_rigid_sparse.wait_until_destroyed()?;
     Ok(())
 }
-
-// Helpers #####################################################################
-
-fn draw_point((px, py): (f32, f32), radius: f32, (r, g, b): (u8, u8, u8), img: &mut RgbImage) {
-    for y in ((py - radius).floor() as u32)..((py + radius).ceil() as u32 + 1) {
-        for x in ((px - radius).floor() as u32)..((px + radius).ceil() as u32 + 1) {
-            // local coordinates (dx, dy) of the point inside the small window
-            let dx = x as f32 - px;
-            let dy = y as f32 - py;
-            let d = (dx * dx + dy * dy).sqrt();
-            // blend coefficient depending on the distance d and the point radius
-            // 1 inside, 0 outside.
-            let blend_coef = if d < radius {
-                1.0
-            } else {
-                (1.0 - d + radius).max(0.0)
-            };
-            // blend the pixel with the new color
-            let pix = img.get_pixel_mut(x, y);
-            pix.channels_mut()
-                .iter_mut()
-                .zip([r, g, b])
-                .for_each(|(p, c)| {
-                    *p = (blend_coef * c as f32 + (1.0 - blend_coef) * *p as f32) as u8
-                });
-        }
-    }
-}
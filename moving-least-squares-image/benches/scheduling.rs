@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compare the scheduling of parallel tasks per pixel and per row,
+//! when filling a 4K image, with and without the `rayon` feature.
+//!
+//! Run with `cargo bench --bench scheduling --features rayon`.
+//! Criterion compares the results with the previous run,
+//! or with a baseline saved with `-- --save-baseline <name>`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use image::{Rgb, RgbImage};
+use moving_least_squares_image::{
+    interpolation, reverse_dense, reverse_sparse, Alpha, DeformMethod,
+};
+use std::time::Duration;
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+#[cfg(feature = "rayon")]
+use rayon::slice::ParallelSliceMut;
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+
+criterion_group! {
+    name = benches;
+    // a 4K warp takes from tens to hundreds of milliseconds
+    config = Criterion::default().sample_size(10).measurement_time(Duration::from_secs(10));
+    targets = scheduling
+}
+criterion_main!(benches);
+
+fn scheduling(c: &mut Criterion) {
+    let img = RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+    });
+    let mut group = c.benchmark_group("scheduling");
+    let mut warped = RgbImage::new(WIDTH, HEIGHT);
+    group.bench_function("remap, one task per pixel", |b| {
+        b.iter(|| fill_per_pixel(&img, &mut warped))
+    });
+    group.bench_function("remap, one task per row", |b| {
+        b.iter(|| fill_per_row(&img, &mut warped))
+    });
+
+    let controls_src = [(400.0, 300.0), (3400.0, 400.0), (1900.0, 1800.0)];
+    let controls_dst = [(450.0, 250.0), (3300.0, 450.0), (1950.0, 1700.0)];
+    group.bench_function("reverse_dense", |b| {
+        b.iter(|| reverse_dense(&img, &controls_src, &controls_dst, DeformMethod::Rigid))
+    });
+    group.bench_function("reverse_sparse, subresolution factor 4", |b| {
+        b.iter(|| reverse_sparse(&img, &controls_src, &controls_dst, 4, DeformMethod::Rigid))
+    });
+    group.finish();
+}
+
+/// Location in the source image of the pixel of the warped image, a small rotation.
+fn source(x: u32, y: u32) -> (f32, f32) {
+    let (cx, cy) = (WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0);
+    let (cos, sin) = (0.05_f32.cos(), 0.05_f32.sin());
+    let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+    (cx + cos * dx - sin * dy, cy + sin * dx + cos * dy)
+}
+
+/// Sample the pixel of the source image, black outside of it.
+fn sample(img: &RgbImage, x: u32, y: u32) -> Rgb<u8> {
+    let (x2, y2) = source(x, y);
+    interpolation::bilinear::<_, _, _, Rgb<u8>>(img, x2, y2, Alpha::Premultiplied)
+        .unwrap_or(Rgb([0, 0, 0]))
+}
+
+/// Fill the warped image with one task per pixel.
+fn fill_per_pixel(img: &RgbImage, warped: &mut RgbImage) {
+    let width = WIDTH as usize;
+    chunks(warped, 3).enumerate().for_each(|(index, pixel)| {
+        let (x, y) = ((index % width) as u32, (index / width) as u32);
+        pixel.copy_from_slice(&sample(img, x, y).0);
+    });
+}
+
+/// Fill the warped image with one task per row.
+fn fill_per_row(img: &RgbImage, warped: &mut RgbImage) {
+    chunks(warped, 3 * WIDTH as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                pixel.copy_from_slice(&sample(img, x as u32, y as u32).0);
+            }
+        });
+}
+
+/// Chunks of the slice, in parallel if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+fn chunks(data: &mut [u8], size: usize) -> impl IndexedParallelIterator<Item = &mut [u8]> {
+    data.par_chunks_exact_mut(size)
+}
+
+/// Chunks of the slice, in parallel if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+fn chunks(data: &mut [u8], size: usize) -> std::slice::ChunksExactMut<'_, u8> {
+    data.chunks_exact_mut(size)
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compare filling the sparse warp pixel by pixel, retrieving the corners
+//! of the bloc of every pixel, and bloc by bloc along each row,
+//! retrieving the corners once for all the pixels of a bloc in the row.
+//!
+//! Run with `cargo bench --bench sparse_fill`, without the `rayon` feature
+//! to compare both on a single thread.
+//! Criterion compares the results with the previous run,
+//! or with a baseline saved with `-- --save-baseline <name>`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{Rgb, RgbImage};
+use moving_least_squares_image::{DeformMethod, Sampler, SparseWarpGrid};
+
+const WIDTH: u32 = 3000;
+const HEIGHT: u32 = 2000;
+const SUBRESOLUTION_FACTORS: [u32; 2] = [4, 16];
+
+criterion_group! {
+    name = benches;
+    // filling the image takes hundreds of milliseconds
+    config = Criterion::default().sample_size(10);
+    targets = sparse_fill
+}
+criterion_main!(benches);
+
+fn sparse_fill(c: &mut Criterion) {
+    let img = RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+    });
+    let controls_src = [(300.0, 200.0), (2700.0, 300.0), (1500.0, 1800.0)];
+    let controls_dst = [(350.0, 150.0), (2600.0, 350.0), (1550.0, 1700.0)];
+    let sampler = Sampler::default();
+    let mut group = c.benchmark_group("sparse_fill");
+    for &factor in &SUBRESOLUTION_FACTORS {
+        let grid = SparseWarpGrid::new(
+            WIDTH,
+            HEIGHT,
+            &controls_src,
+            &controls_dst,
+            factor,
+            DeformMethod::Rigid,
+        );
+        group.bench_with_input(BenchmarkId::new("per pixel", factor), &grid, |b, grid| {
+            b.iter(|| {
+                RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+                    let (x2, y2) = grid.source(x, y);
+                    sampler.sample(&img, x2, y2)
+                })
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("per bloc", factor), &grid, |b, grid| {
+            b.iter(|| grid.warp(&img, &sampler))
+        });
+    }
+    group.finish();
+}
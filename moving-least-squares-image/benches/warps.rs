@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Time the dense and sparse warps, for each MLS method,
+//! with 5 to 5000 control points.
+//!
+//! Run with `cargo bench --bench warps`, and with `--features rayon`
+//! to also compare the parallel warps with the warps on a single thread.
+//! A filter selects the benches by name, as in `cargo bench --bench warps -- rigid`,
+//! and criterion compares the results with the previous run,
+//! or with a baseline saved with `-- --save-baseline <name>`.
+
+use criterion::measurement::WallTime;
+use criterion::{
+    criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput,
+};
+use image::{Rgb, RgbImage};
+use moving_least_squares_image::{reverse_dense, reverse_sparse, DeformMethod};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const WIDTH: u32 = 512;
+const HEIGHT: u32 = 384;
+const NB_CONTROLS: [usize; 4] = [5, 50, 500, 5000];
+const SUBRESOLUTION_FACTORS: [u32; 2] = [4, 16];
+const METHODS: [(DeformMethod, &str); 3] = [
+    (DeformMethod::Affine, "affine"),
+    (DeformMethod::Similarity, "similarity"),
+    (DeformMethod::Rigid, "rigid"),
+];
+
+criterion_group! {
+    name = benches;
+    // the dense warp is long with many control points
+    config = Criterion::default().sample_size(10);
+    targets = dense, sparse
+}
+criterion_main!(benches);
+
+/// Points in the image.
+type Points = Vec<(f32, f32)>;
+
+fn dense(c: &mut Criterion) {
+    let img = image();
+    for &(method, method_name) in &METHODS {
+        let mut group = c.benchmark_group(format!("dense/{}", method_name));
+        bench_controls(&mut group, |controls_src, controls_dst| {
+            reverse_dense(&img, controls_src, controls_dst, method);
+        });
+        group.finish();
+    }
+}
+
+fn sparse(c: &mut Criterion) {
+    let img = image();
+    for &factor in &SUBRESOLUTION_FACTORS {
+        for &(method, method_name) in &METHODS {
+            let mut group = c.benchmark_group(format!("sparse {}/{}", factor, method_name));
+            bench_controls(&mut group, |controls_src, controls_dst| {
+                reverse_sparse(&img, controls_src, controls_dst, factor, method);
+            });
+            group.finish();
+        }
+    }
+}
+
+/// Time the warp for every number of control points,
+/// and also on a single thread with the `rayon` feature.
+fn bench_controls<F>(group: &mut BenchmarkGroup<'_, WallTime>, warp: F)
+where
+    F: Fn(&[(f32, f32)], &[(f32, f32)]) + Sync,
+{
+    group.throughput(Throughput::Elements(u64::from(WIDTH * HEIGHT)));
+    #[cfg(feature = "rayon")]
+    let single_thread = moving_least_squares_image::rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    for &nb_controls in &NB_CONTROLS {
+        let (controls_src, controls_dst) = random_controls(nb_controls);
+        let threads = if cfg!(feature = "rayon") {
+            "rayon"
+        } else {
+            "serial"
+        };
+        let id = BenchmarkId::new(threads, nb_controls);
+        group.bench_with_input(id, &nb_controls, |b, _| {
+            b.iter(|| warp(&controls_src, &controls_dst))
+        });
+        #[cfg(feature = "rayon")]
+        {
+            let id = BenchmarkId::new("1 thread", nb_controls);
+            group.bench_with_input(id, &nb_controls, |b, _| {
+                b.iter(|| single_thread.install(|| warp(&controls_src, &controls_dst)))
+            });
+        }
+    }
+}
+
+/// Source image of the warps.
+fn image() -> RgbImage {
+    RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+    })
+}
+
+/// Control points spread over the image, randomly displaced by a few pixels,
+/// always the same for a given number of control points.
+fn random_controls(nb_controls: usize) -> (Points, Points) {
+    let mut rng = StdRng::seed_from_u64(nb_controls as u64);
+    let controls_src: Points = (0..nb_controls)
+        .map(|_| {
+            let x = rng.gen_range(0.0..WIDTH as f32);
+            (x, rng.gen_range(0.0..HEIGHT as f32))
+        })
+        .collect();
+    let controls_dst = controls_src
+        .iter()
+        .map(|&(x, y)| (x + rng.gen_range(-5.0..5.0), y + rng.gen_range(-5.0..5.0)))
+        .collect();
+    (controls_src, controls_dst)
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Sparse warp on a grid adaptively refined where the bilinear interpolation
+//! of the mapping between the grid points is not precise enough.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::mapping::{grid_positions, map_points, Mapping};
+use crate::{bilinear_warp, image_from_fn, reverse_mapping, Canvas, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// Compute the warped image with an MLS algorithm,
+/// as [`reverse_sparse`](crate::reverse_sparse), but with grid blocs recursively split in 4
+/// while the bilinear interpolation of their corners, measured at their center,
+/// is more than `tolerance` pixels away from the exact MLS mapping.
+///
+/// Blocs start with a size of `subresolution_factor` pixels,
+/// so flat regions are as fast as the sparse warp,
+/// and regions near the control points get as precise as needed.
+pub fn reverse_sparse_adaptive<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    tolerance: f32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    let sampler = Sampler::default();
+    warp_adaptive(img_src, &mapping, subresolution_factor, tolerance, &sampler)
+}
+
+/// Compute the image warped by the given mapping,
+/// on a grid adaptively refined as in [`reverse_sparse_adaptive`].
+pub fn warp_adaptive<P, C, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    subresolution_factor: u32,
+    tolerance: f32,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    assert!(
+        subresolution_factor > 0,
+        "The subresolution factor must be strictly positive"
+    );
+    let canvas = Canvas::of(img_src);
+    let (width, height) = (canvas.width, canvas.height);
+    let step = subresolution_factor;
+
+    // the coarse blocs, with the mapping of the regular grid at their corners
+    let anchors = map_points(&grid_positions(&canvas, step), mapping);
+    let nb_columns = ((width.max(1) - 1) / step + 2) as usize;
+    let nb_rows = anchors.len() / nb_columns;
+    let blocs: Vec<Bloc> = (0..nb_rows - 1)
+        .flat_map(|row| (0..nb_columns - 1).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let top = row * nb_columns + column;
+            let bot = top + nb_columns;
+            let (left, top_y) = (column as u32 * step, row as u32 * step);
+            Bloc {
+                top_left: (left, top_y),
+                bot_right: (left + step, top_y + step),
+                corners: [
+                    anchors[top],
+                    anchors[top + 1],
+                    anchors[bot],
+                    anchors[bot + 1],
+                ],
+            }
+        })
+        .collect();
+
+    // the source location of every pixel, interpolated in the refined blocs
+    let mut coordinates = vec![(0.0, 0.0); (width * height) as usize];
+    for bloc in refine_all(&blocs, mapping, tolerance) {
+        let (left, top) = bloc.top_left;
+        let (right, bottom) = bloc.bot_right;
+        for y in top..bottom.min(height) {
+            for x in left..right.min(width) {
+                coordinates[(y * width + x) as usize] =
+                    bilinear_warp(bloc.top_left, bloc.bot_right, bloc.corners, (x, y));
+            }
+        }
+    }
+    image_from_fn(width, height, |x, y| {
+        let (x2, y2) = coordinates[(y * width + x) as usize];
+        sampler.sample(img_src, x2, y2)
+    })
+}
+
+/// Bloc of pixels, with the mapping of its corners.
+/// The pixels on its right and bottom edges belong to the neighbor blocs.
+#[derive(Clone, Copy)]
+struct Bloc {
+    top_left: (u32, u32),
+    bot_right: (u32, u32),
+    /// Mapping of the top left, top right, bottom left and bottom right corners.
+    corners: [(f32, f32); 4],
+}
+
+/// Refine all the blocs, in parallel if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+fn refine_all<M: Mapping + ?Sized>(blocs: &[Bloc], mapping: &M, tolerance: f32) -> Vec<Bloc> {
+    let mut leaves = Vec::with_capacity(blocs.len());
+    for &bloc in blocs {
+        refine(bloc, mapping, tolerance, &mut leaves);
+    }
+    leaves
+}
+
+/// Refine all the blocs, in parallel if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+fn refine_all<M: Mapping + ?Sized>(blocs: &[Bloc], mapping: &M, tolerance: f32) -> Vec<Bloc> {
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+    let refined: Vec<Vec<Bloc>> = blocs
+        .par_iter()
+        .map(|&bloc| {
+            let mut leaves = Vec::new();
+            refine(bloc, mapping, tolerance, &mut leaves);
+            leaves
+        })
+        .collect();
+    refined.concat()
+}
+
+/// Recursively split the bloc in 4 (or 2 if it is only 1 pixel wide or high),
+/// until the bilinear interpolation of its corners is precise enough at its center.
+fn refine<M: Mapping + ?Sized>(bloc: Bloc, mapping: &M, tolerance: f32, leaves: &mut Vec<Bloc>) {
+    let (left, top) = bloc.top_left;
+    let (right, bottom) = bloc.bot_right;
+    let (width, height) = (right - left, bottom - top);
+    if width < 2 && height < 2 {
+        leaves.push(bloc);
+        return;
+    }
+    let center = ((left + right) as f32 / 2.0, (top + bottom) as f32 / 2.0);
+    let exact = mapping.source(center);
+    let [tl, tr, bl, br] = bloc.corners;
+    let approx = (
+        (tl.0 + tr.0 + bl.0 + br.0) / 4.0,
+        (tl.1 + tr.1 + bl.1 + br.1) / 4.0,
+    );
+    let error = ((exact.0 - approx.0).powi(2) + (exact.1 - approx.1).powi(2)).sqrt();
+    // Non finite errors are not refined since the refinement would not converge.
+    if error <= tolerance || !error.is_finite() {
+        leaves.push(bloc);
+        return;
+    }
+
+    // coordinates of the split lines
+    let xs: Vec<u32> = if width >= 2 {
+        vec![left, left + width / 2, right]
+    } else {
+        vec![left, right]
+    };
+    let ys: Vec<u32> = if height >= 2 {
+        vec![top, top + height / 2, bottom]
+    } else {
+        vec![top, bottom]
+    };
+    // mapping of the points of the split lines, reusing the known ones
+    let at = |x: u32, y: u32| match (x, y) {
+        _ if (x, y) == (left, top) => tl,
+        _ if (x, y) == (right, top) => tr,
+        _ if (x, y) == (left, bottom) => bl,
+        _ if (x, y) == (right, bottom) => br,
+        _ if (x as f32, y as f32) == center => exact,
+        _ => mapping.source((x as f32, y as f32)),
+    };
+    let values: Vec<Vec<(f32, f32)>> = ys
+        .iter()
+        .map(|&y| xs.iter().map(|&x| at(x, y)).collect())
+        .collect();
+    for j in 0..ys.len() - 1 {
+        for i in 0..xs.len() - 1 {
+            let child = Bloc {
+                top_left: (xs[i], ys[j]),
+                bot_right: (xs[i + 1], ys[j + 1]),
+                corners: [
+                    values[j][i],
+                    values[j][i + 1],
+                    values[j + 1][i],
+                    values[j + 1][i + 1],
+                ],
+            };
+            refine(child, mapping, tolerance, leaves);
+        }
+    }
+}
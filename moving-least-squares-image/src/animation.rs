@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Animated warps, with frames interpolated between keyframes of the control points.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{frames_from_fn, reverse_dense, DeformMethod, Image};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Easing of the animation time, to start or end the motion smoothly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Starting slowly and accelerating.
+    EaseIn,
+    /// Starting fast and decelerating.
+    EaseOut,
+    /// Starting and ending slowly (smoothstep).
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased time of the linear time `t` in [0, 1], also in [0, 1].
+    pub fn ease(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Control points at time `t` in [0, 1] of keyframes evenly spaced in time,
+/// linearly interpolated between the two surrounding keyframes.
+///
+/// Panics if there is no keyframe.
+pub fn keyframe_controls<K: AsRef<[(f32, f32)]>>(keyframes: &[K], t: f32) -> Vec<(f32, f32)> {
+    assert!(!keyframes.is_empty(), "no keyframe");
+    if keyframes.len() == 1 {
+        return keyframes[0].as_ref().to_vec();
+    }
+    let position = t.clamp(0.0, 1.0) * (keyframes.len() - 1) as f32;
+    let index = (position.floor() as usize).min(keyframes.len() - 2);
+    let (start, end) = (keyframes[index].as_ref(), keyframes[index + 1].as_ref());
+    mls::blend_controls(start, end, position - index as f32)
+}
+
+/// Compute the frames of the image animated through the keyframes of its control points,
+/// the first keyframe being the control points of the source image.
+///
+/// The frames are evenly spaced in time before easing, from the first keyframe
+/// to the last one, included, and computed in parallel if the `rayon` feature is enabled.
+/// Each frame is warped from the source image, so errors do not accumulate.
+pub fn animate<P, C, V, K>(
+    img_src: &ImageBuffer<P, C>,
+    keyframes: &[K],
+    nb_frames: usize,
+    easing: Easing,
+    method: DeformMethod,
+) -> Vec<Image<P>>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    K: AsRef<[(f32, f32)]> + Sync,
+{
+    let controls_src = keyframes.first().expect("no keyframe").as_ref();
+    frames_from_fn(nb_frames, |index| {
+        let t = if nb_frames > 1 {
+            index as f32 / (nb_frames - 1) as f32
+        } else {
+            0.0
+        };
+        let controls_dst = keyframe_controls(keyframes, easing.ease(t));
+        reverse_dense(img_src, controls_src, &controls_dst, method)
+    })
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warp of annotations, such as points, polygons and bounding boxes,
+//! to keep them aligned with the images warped by [`reverse_dense`](crate::reverse_dense).
+
+use crate::DeformMethod;
+use moving_least_squares as mls;
+
+/// Direction of the warp of annotations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the source image to the warped image,
+    /// for example to move the labels of an image with it.
+    SourceToWarped,
+    /// From the warped image to the source image,
+    /// which is the back projection of the pixels done by [`reverse_dense`](crate::reverse_dense).
+    WarpedToSource,
+}
+
+/// Move the points between the source image and the warped image,
+/// for the warp of [`reverse_dense`](crate::reverse_dense) with the same control points.
+///
+/// Since the warped image is computed by back projecting its pixels,
+/// points are moved to the warped image by inverting that back projection
+/// with [`inverse_deform`](mls::inverse_deform),
+/// so that they land exactly where their pixels are displayed.
+/// Points where the inversion does not converge keep the forward MLS deformation.
+pub fn warp_points(
+    points: &[(f32, f32)],
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    direction: Direction,
+) -> Vec<(f32, f32)> {
+    match direction {
+        Direction::WarpedToSource => mls::deform_all(controls_dst, controls_src, points, method),
+        Direction::SourceToWarped => points
+            .iter()
+            .map(|&point| {
+                mls::inverse_deform(controls_dst, controls_src, point, method)
+                    .unwrap_or_else(|| mls::deform(controls_src, controls_dst, point, method))
+            })
+            .collect(),
+    }
+}
+
+/// Move the polygon between the source image and the warped image, as [`warp_points`].
+///
+/// Since straight edges are curved by the warp,
+/// they are first subdivided in segments no longer than `segment_length` pixels.
+pub fn warp_polygon(
+    polygon: &[(f32, f32)],
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    direction: Direction,
+    segment_length: f32,
+) -> Vec<(f32, f32)> {
+    assert!(
+        segment_length > 0.0,
+        "The segment length must be strictly positive"
+    );
+    let nb_vertices = polygon.len();
+    let subdivided: Vec<(f32, f32)> = (0..nb_vertices)
+        .flat_map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % nb_vertices]);
+            subdivide(a, b, segment_length)
+        })
+        .collect();
+    warp_points(&subdivided, controls_src, controls_dst, method, direction)
+}
+
+/// Move the bounding box, given by its top left and bottom right corners,
+/// between the source image and the warped image, as [`warp_points`].
+///
+/// The result is the bounding box of its warped border,
+/// sampled every `segment_length` pixels.
+pub fn warp_bbox(
+    (top_left, bottom_right): ((f32, f32), (f32, f32)),
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    direction: Direction,
+    segment_length: f32,
+) -> ((f32, f32), (f32, f32)) {
+    let polygon = [
+        top_left,
+        (bottom_right.0, top_left.1),
+        bottom_right,
+        (top_left.0, bottom_right.1),
+    ];
+    let border = warp_polygon(
+        &polygon,
+        controls_src,
+        controls_dst,
+        method,
+        direction,
+        segment_length,
+    );
+    let (mut x_min, mut y_min) = (f32::INFINITY, f32::INFINITY);
+    let (mut x_max, mut y_max) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for (x, y) in border {
+        x_min = x_min.min(x);
+        y_min = y_min.min(y);
+        x_max = x_max.max(x);
+        y_max = y_max.max(y);
+    }
+    ((x_min, y_min), (x_max, y_max))
+}
+
+/// Points of the segment from `a` (included) to `b` (excluded),
+/// spaced by at most `segment_length`.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn subdivide(a: (f32, f32), b: (f32, f32), segment_length: f32) -> Vec<(f32, f32)> {
+    let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+    let nb_segments = (length / segment_length).ceil().max(1.0) as usize;
+    (0..nb_segments)
+        .map(|i| {
+            let t = i as f32 / nb_segments as f32;
+            (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1))
+        })
+        .collect()
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Prefilter of images for an interpolating cubic B-spline sampling.
+//!
+//! The cubic B-spline kernel is not interpolating: sampling the pixels directly
+//! blurs the image. Sampling instead the B-spline coefficients of the image,
+//! computed by a recursive filter, gives a smooth interpolation of its pixels.
+
+use crate::Image;
+use image::{ImageBuffer, Pixel};
+use std::ops::Deref;
+
+/// Pole of the recursive filter of the cubic B-spline.
+const POLE: f64 = -0.267_949_192_431_122_7; // sqrt(3) - 2
+
+/// Cubic B-spline coefficients of the image, with mirror boundary conditions.
+///
+/// Sampling them with [`Interpolation::BSpline`](crate::Interpolation::BSpline)
+/// and the [`Border::Reflect`](crate::Border::Reflect) border interpolates the image.
+/// The coefficients overshoot the range of the pixels near edges,
+/// so the image has `f32` channels, which are not clamped.
+pub fn bspline_prefilter<P, C>(img: &ImageBuffer<P, C>) -> Image<P>
+where
+    P: Pixel<Subpixel = f32> + 'static,
+    C: Deref<Target = [f32]>,
+{
+    let (width, height) = img.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let channels = P::CHANNEL_COUNT as usize;
+    let mut coefs: Vec<f32> = img.as_raw().to_vec();
+    let mut line = Vec::with_capacity(width.max(height));
+    for c in 0..channels {
+        // Filter the rows.
+        for y in 0..height {
+            let index = |x| (y * width + x) * channels + c;
+            line.clear();
+            line.extend((0..width).map(|x| f64::from(coefs[index(x)])));
+            filter(&mut line);
+            (0..width).for_each(|x| coefs[index(x)] = line[x] as f32);
+        }
+        // Filter the columns.
+        for x in 0..width {
+            let index = |y| (y * width + x) * channels + c;
+            line.clear();
+            line.extend((0..height).map(|y| f64::from(coefs[index(y)])));
+            filter(&mut line);
+            (0..height).for_each(|y| coefs[index(y)] = line[y] as f32);
+        }
+    }
+    ImageBuffer::from_raw(img.width(), img.height(), coefs).expect("same size as the image")
+}
+
+/// In place recursive filter of a line of samples into its cubic B-spline coefficients,
+/// with a causal and an anti-causal pass (Unser, 1999).
+pub(crate) fn filter(line: &mut [f64]) {
+    let n = line.len();
+    if n < 2 {
+        return;
+    }
+    let z = POLE;
+    // Gain of the filter, (1 - z) * (1 - 1/z).
+    line.iter_mut().for_each(|v| *v *= 6.0);
+    line[0] = causal_init(line);
+    for k in 1..n {
+        line[k] += z * line[k - 1];
+    }
+    line[n - 1] = (z / (z * z - 1.0)) * (line[n - 1] + z * line[n - 2]);
+    for k in (0..n - 1).rev() {
+        line[k] = z * (line[k + 1] - line[k]);
+    }
+}
+
+/// Initial causal coefficient of the line mirrored at its borders.
+fn causal_init(line: &[f64]) -> f64 {
+    let n = line.len();
+    let z = POLE;
+    // Number of samples after which the powers of the pole are negligible.
+    let horizon = 30;
+    if n > horizon {
+        let mut zk = 1.0;
+        line[..horizon]
+            .iter()
+            .map(|v| {
+                let term = zk * v;
+                zk *= z;
+                term
+            })
+            .sum()
+    } else {
+        // Exact sum over the whole mirrored line.
+        let zn = z.powi(n as i32 - 1);
+        let mut z1 = z;
+        let mut z2 = zn * zn / z;
+        let mut sum = line[0] + zn * line[n - 1];
+        for v in &line[1..n - 1] {
+            sum += (z1 + z2) * v;
+            z1 *= z;
+            z2 /= z;
+        }
+        sum / (1.0 - zn * zn)
+    }
+}
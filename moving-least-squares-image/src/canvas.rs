@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Frame of the warped image, independent of the frame of the source image.
+
+use crate::DeformMethod;
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::Deref;
+
+/// Size of the warped image, and position of its top left pixel
+/// in the coordinates of the deformation.
+///
+/// With a canvas bigger than the source image, or with an offset,
+/// the content moved outside of the source frame by the warp is not cropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Canvas {
+    /// Width of the warped image.
+    pub width: u32,
+    /// Height of the warped image.
+    pub height: u32,
+    /// Coordinates (x, y) of the top left pixel of the warped image.
+    pub offset: (f32, f32),
+}
+
+impl Canvas {
+    /// Canvas of the given size, without offset.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            offset: (0.0, 0.0),
+        }
+    }
+
+    /// Canvas with the same frame than the given image.
+    pub fn of<P, C>(img: &ImageBuffer<P, C>) -> Self
+    where
+        P: Pixel + 'static,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        let (width, height) = img.dimensions();
+        Self::new(width, height)
+    }
+
+    /// Set the coordinates of the top left pixel of the warped image.
+    pub fn offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    /// Coordinates in the deformation of the given pixel of the warped image.
+    pub fn position(&self, x: u32, y: u32) -> (f32, f32) {
+        (x as f32 + self.offset.0, y as f32 + self.offset.1)
+    }
+
+    /// Smallest canvas containing the whole warped image,
+    /// for a source image of the given size.
+    ///
+    /// The border of the source image is forward mapped through the deformation,
+    /// and the bounding box of the result is rounded to whole pixels.
+    /// It is the frame of the source image if no point is mapped to finite coordinates.
+    pub fn fit(
+        width: u32,
+        height: u32,
+        controls_src: &[(f32, f32)],
+        controls_dst: &[(f32, f32)],
+        method: DeformMethod,
+    ) -> Self {
+        let border = border_points(width, height);
+        let warped = mls::deform_all(controls_src, controls_dst, &border, method);
+        let finite = warped
+            .iter()
+            .filter(|(x, y)| x.is_finite() && y.is_finite());
+        let (mut x_min, mut y_min) = (f32::INFINITY, f32::INFINITY);
+        let (mut x_max, mut y_max) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for &(x, y) in finite {
+            x_min = x_min.min(x);
+            y_min = y_min.min(y);
+            x_max = x_max.max(x);
+            y_max = y_max.max(y);
+        }
+        if x_min > x_max {
+            return Self::new(width, height);
+        }
+        let (left, top) = (x_min.floor(), y_min.floor());
+        let (right, bottom) = (x_max.ceil(), y_max.ceil());
+        Self::new((right - left) as u32 + 1, (bottom - top) as u32 + 1).offset(left, top)
+    }
+}
+
+/// Pixels on the border of an image of the given size.
+fn border_points(width: u32, height: u32) -> Vec<(f32, f32)> {
+    let (right, bottom) = (width.max(1) - 1, height.max(1) - 1);
+    let horizontal = (0..=right).flat_map(|x| [(x, 0), (x, bottom)]);
+    let vertical = (0..=bottom).flat_map(|y| [(0, y), (right, y)]);
+    horizontal
+        .chain(vertical)
+        .map(|(x, y)| (x as f32, y as f32))
+        .collect()
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Anti-aliased drawing of control points and of their displacements,
+//! to show the handles of a deformation on the source or warped image.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use image::{ImageBuffer, Pixel, Rgb};
+use std::ops::{Add, DerefMut, Mul};
+
+/// Colors and sizes of the drawn control points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlsStyle<P> {
+    /// Color of the source control points.
+    pub src_color: P,
+    /// Color of the destination control points.
+    pub dst_color: P,
+    /// Color of the arrows from the source to the destination control points.
+    pub arrow_color: P,
+    /// Radius of the control points, in pixels.
+    pub radius: f32,
+    /// Width of the arrows, in pixels.
+    pub arrow_width: f32,
+}
+
+impl Default for ControlsStyle<Rgb<u8>> {
+    /// Red source points, blue destination points and yellow arrows.
+    fn default() -> Self {
+        Self {
+            src_color: Rgb([255, 0, 0]),
+            dst_color: Rgb([0, 0, 255]),
+            arrow_color: Rgb([255, 200, 0]),
+            radius: 5.0,
+            arrow_width: 2.0,
+        }
+    }
+}
+
+/// Draw the source and destination control points,
+/// with an arrow from each source point to its destination.
+pub fn draw_controls<P, C, V>(
+    img: &mut ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    style: &ControlsStyle<P>,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    for (&p, &q) in controls_src.iter().zip(controls_dst) {
+        draw_arrow(img, p, q, style.arrow_width, style.arrow_color);
+    }
+    for &p in controls_src {
+        draw_point(img, p, style.radius, style.src_color);
+    }
+    for &q in controls_dst {
+        draw_point(img, q, style.radius, style.dst_color);
+    }
+}
+
+/// Draw a disc of the given radius, clipped to the image.
+pub fn draw_point<P, C, V>(img: &mut ImageBuffer<P, C>, center: (f32, f32), radius: f32, color: P)
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (px, py) = center;
+    let top_left = (px - radius, py - radius);
+    let bot_right = (px + radius, py + radius);
+    draw_shape(img, top_left, bot_right, color, |x, y| {
+        coverage(distance((x, y), center), radius)
+    })
+}
+
+/// Draw a segment of the given width with rounded ends, clipped to the image.
+pub fn draw_segment<P, C, V>(
+    img: &mut ImageBuffer<P, C>,
+    from: (f32, f32),
+    to: (f32, f32),
+    width: f32,
+    color: P,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let half = width / 2.0;
+    let top_left = (from.0.min(to.0) - half, from.1.min(to.1) - half);
+    let bot_right = (from.0.max(to.0) + half, from.1.max(to.1) + half);
+    draw_shape(img, top_left, bot_right, color, |x, y| {
+        coverage(distance_to_segment((x, y), from, to), half)
+    })
+}
+
+/// Draw an arrow of the given width from a point to another, clipped to the image.
+pub fn draw_arrow<P, C, V>(
+    img: &mut ImageBuffer<P, C>,
+    from: (f32, f32),
+    to: (f32, f32),
+    width: f32,
+    color: P,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    draw_segment(img, from, to, width, color);
+    for &side in &arrow_head(from, to, width) {
+        draw_segment(img, to, side, width, color);
+    }
+}
+
+/// Ends of the two segments of the arrow head, at 30 degrees from the arrow,
+/// and shorter than half of it.
+fn arrow_head(from: (f32, f32), to: (f32, f32), width: f32) -> Vec<(f32, f32)> {
+    let length = distance(from, to);
+    if length == 0.0 {
+        return Vec::new();
+    }
+    let head = (3.0 * width + 4.0).min(length / 2.0);
+    let (ux, uy) = (
+        head * (from.0 - to.0) / length,
+        head * (from.1 - to.1) / length,
+    );
+    let (sin, cos) = std::f32::consts::FRAC_PI_6.sin_cos();
+    [sin, -sin]
+        .iter()
+        .map(|&sin| (to.0 + cos * ux - sin * uy, to.1 + sin * ux + cos * uy))
+        .collect()
+}
+
+/// Blend the color over the pixels of the bounding box inside of the image,
+/// with the opacity given by the coverage of the shape at each pixel.
+#[allow(clippy::cast_possible_truncation)]
+fn draw_shape<P, C, V, F>(
+    img: &mut ImageBuffer<P, C>,
+    top_left: (f32, f32),
+    bot_right: (f32, f32),
+    color: P,
+    coverage: F,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    F: Fn(f32, f32) -> f32,
+{
+    let (width, height) = img.dimensions();
+    // Pixel range of the bounding box with a margin for anti-aliasing, clipped to the image.
+    let range = |min: f32, max: f32, size: u32| {
+        let first = ((min - 1.0).floor() as i64).max(0);
+        let last = ((max + 1.0).ceil() as i64).min(i64::from(size) - 1);
+        first..=last
+    };
+    for y in range(top_left.1, bot_right.1, height) {
+        for x in range(top_left.0, bot_right.0, width) {
+            let opacity = coverage(x as f32, y as f32);
+            if opacity > 0.0 {
+                let pixel = img.get_pixel_mut(x as u32, y as u32);
+                interpolation::blend(pixel, color, opacity);
+            }
+        }
+    }
+}
+
+/// Opacity of a pixel at the distance d from a shape of the given radius,
+/// 1 inside and fading over a pixel outside.
+fn coverage(d: f32, radius: f32) -> f32 {
+    (1.0 - d + radius).clamp(0.0, 1.0)
+}
+
+/// Distance between two points.
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt()
+}
+
+/// Distance of the point to the segment.
+fn distance_to_segment((x, y): (f32, f32), from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let squared_length = dx * dx + dy * dy;
+    let t = if squared_length > 0.0 {
+        (((x - from.0) * dx + (y - from.1) * dy) / squared_length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    distance((x, y), (from.0 + t * dx, from.1 + t * dy))
+}
@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warp of a `DynamicImage`, whatever its color type.
+
+use crate::{reverse_dense, DeformMethod};
+use image::DynamicImage;
+
+/// Same as [`reverse_dense`], for a `DynamicImage`,
+/// keeping the color type of the source image.
+///
+/// Color types in the BGR order are warped as their RGB or RGBA equivalent,
+/// and converted back.
+pub fn warp_dynamic(
+    img_src: &DynamicImage,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> DynamicImage {
+    use DynamicImage::*;
+    match img_src {
+        ImageLuma8(img) => ImageLuma8(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageRgb8(img) => ImageRgb8(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageRgba8(img) => ImageRgba8(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageLuma16(img) => ImageLuma16(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageRgb16(img) => ImageRgb16(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageRgba16(img) => ImageRgba16(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageLumaA8(img) => ImageLumaA8(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageLumaA16(img) => ImageLumaA16(reverse_dense(img, controls_src, controls_dst, method)),
+        ImageBgra8(_) => {
+            let img = img_src.to_rgba8();
+            let warped = ImageRgba8(reverse_dense(&img, controls_src, controls_dst, method));
+            ImageBgra8(warped.into_bgra8())
+        }
+        ImageBgr8(_) => {
+            let img = img_src.to_rgb8();
+            let warped = ImageRgb8(reverse_dense(&img, controls_src, controls_dst, method));
+            ImageBgr8(warped.into_bgr8())
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Estimation of the error of the sparse warp, compared to the dense warp,
+//! to choose its subresolution factor.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{anchors_warp, reverse_sparse, DeformMethod, Image};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Error of the sparse warp, in pixels of the source image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SparseError {
+    /// Maximum distance between the sparse and exact locations of the sampled pixels.
+    pub max: f32,
+    /// Mean distance between the sparse and exact locations of the sampled pixels.
+    pub mean: f32,
+}
+
+/// Estimate the error of [`reverse_sparse`](crate::reverse_sparse) for a warped image
+/// of the given size, compared to the exact MLS deformation of [`reverse_dense`](crate::reverse_dense).
+///
+/// The error is sampled at the center and at the middle of the top and left edges
+/// of each bloc of the sparse grid, where the bilinear interpolation is the least precise.
+/// Pixels where the deformation is not finite, such as the control points
+/// with the affine method, are ignored.
+pub fn sparse_error(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+) -> SparseError {
+    assert!(
+        subresolution_factor > 0,
+        "The subresolution factor must be strictly positive"
+    );
+    let step = subresolution_factor;
+    let half = step / 2;
+    let anchors = mls::deform_grid(controls_dst, controls_src, width, height, step, method);
+    let sub_width = ((width.max(1) - 1) / step + 2) as usize;
+
+    // pixels sampled in each bloc
+    let samples: Vec<(u32, u32)> = (0..height)
+        .step_by(step as usize)
+        .flat_map(|top| {
+            (0..width)
+                .step_by(step as usize)
+                .map(move |left| (left, top))
+        })
+        .flat_map(|(left, top)| {
+            [
+                (left + half, top + half),
+                (left + half, top),
+                (left, top + half),
+            ]
+        })
+        .filter(|&(x, y)| x < width && y < height)
+        .collect();
+    let positions: Vec<(f32, f32)> = samples.iter().map(|&(x, y)| (x as f32, y as f32)).collect();
+    let exact = mls::deform_all(controls_dst, controls_src, &positions, method);
+
+    let mut max: f32 = 0.0;
+    let mut sum = 0.0;
+    let mut count = 0;
+    for (&pixel, e) in samples.iter().zip(exact) {
+        let s = anchors_warp(&anchors, sub_width, step, pixel);
+        let error = ((s.0 - e.0).powi(2) + (s.1 - e.1).powi(2)).sqrt();
+        if error.is_finite() {
+            max = max.max(error);
+            sum += error;
+            count += 1;
+        }
+    }
+    SparseError {
+        max,
+        mean: if count > 0 { sum / count as f32 } else { 0.0 },
+    }
+}
+
+/// Coarsest subresolution factor, among the powers of 2,
+/// for which the maximum error estimated by [`sparse_error`] is within `max_error` pixels.
+///
+/// Returns 1, the dense warp, if no coarser factor meets that budget.
+pub fn auto_subresolution_factor(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    max_error: f32,
+    method: DeformMethod,
+) -> u32 {
+    let mut factor = 1;
+    while factor < width.max(height) {
+        let coarser = 2 * factor;
+        let error = sparse_error(width, height, controls_src, controls_dst, coarser, method);
+        if error.max > max_error {
+            break;
+        }
+        factor = coarser;
+    }
+    factor
+}
+
+/// Compute the warped image with [`reverse_sparse`],
+/// with the subresolution factor chosen by [`auto_subresolution_factor`]
+/// for an error within `max_error` pixels.
+pub fn reverse_sparse_auto<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    max_error: f32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = img_src.dimensions();
+    let factor =
+        auto_subresolution_factor(width, height, controls_src, controls_dst, max_error, method);
+    reverse_sparse(img_src, controls_src, controls_dst, factor, method)
+}
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Anti-aliased warp with elliptical weighted average (EWA) filtering,
+//! driven by the Jacobian of the MLS deformation.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use crate::{image_from_fn, reverse_mapping, Border, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Maximum half size in pixels of the footprint of a warped pixel in the source image,
+/// to bound the cost of extreme shrinks.
+const MAX_RADIUS: f32 = 32.0;
+
+/// Minimum local shrink factor of the warp for the source image to be filtered,
+/// so that the rounding errors of the Jacobian do not blur unscaled regions.
+const MIN_SHRINK: f32 = 1.01;
+
+/// Same as [`reverse_dense_with`](crate::reverse_dense_with), but the source image is filtered
+/// where the warp locally shrinks it, to avoid aliasing.
+///
+/// Each warped pixel covers an ellipse of the source image,
+/// given by the Jacobian of the back projection at that pixel.
+/// Where that ellipse is larger than a pixel, its source pixels are averaged
+/// with Gaussian weights (elliptical weighted average).
+/// Elsewhere, the source image is sampled with the given sampler.
+pub fn reverse_dense_ewa<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = img_src.dimensions();
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    image_from_fn(width, height, |x, y| {
+        let point = (x as f32, y as f32);
+        let center = mapping(point);
+        let jacobian = mls::jacobian(controls_dst, controls_src, point, method);
+        match Ellipse::of(jacobian) {
+            Some(ellipse) => ellipse.average(img_src, center, sampler),
+            None => sampler.sample(img_src, center.0, center.1),
+        }
+    })
+}
+
+/// Footprint of a warped pixel in the source image,
+/// the points d such that d^T Q d < 1.
+struct Ellipse {
+    /// Conic matrix Q, symmetric, as (a, b, c) for [[a, b], [b, c]].
+    conic: (f32, f32, f32),
+    /// Half size of the bounding box of the ellipse.
+    radius: (f32, f32),
+}
+
+impl Ellipse {
+    /// Ellipse of the pixel footprint given by the Jacobian of the back projection,
+    /// convolved with the reconstruction filter of the source pixels,
+    /// or `None` if the warp does not shrink the image there.
+    fn of([[j00, j01], [j10, j11]]: [[f32; 2]; 2]) -> Option<Self> {
+        // Covariance J J^T of the footprint.
+        let a = j00 * j00 + j01 * j01;
+        let b = j00 * j10 + j01 * j11;
+        let c = j10 * j10 + j11 * j11;
+        // Largest eigen value, the squared largest stretch of a pixel.
+        let half_trace = (a + c) / 2.0;
+        let largest = half_trace + (half_trace * half_trace - (a * c - b * b)).max(0.0).sqrt();
+        if largest <= MIN_SHRINK * MIN_SHRINK || !largest.is_finite() {
+            return None;
+        }
+        // Convolution with a pixel wide reconstruction filter.
+        let (a, c) = (a + 1.0, c + 1.0);
+        let det = a * c - b * b;
+        Some(Self {
+            conic: (c / det, -b / det, a / det),
+            radius: (a.sqrt().min(MAX_RADIUS), c.sqrt().min(MAX_RADIUS)),
+        })
+    }
+
+    /// Gaussian weight of the pixel at the given offset from the center,
+    /// or `None` if it is outside of the ellipse.
+    fn weight(&self, dx: f32, dy: f32) -> Option<f32> {
+        let (a, b, c) = self.conic;
+        let q = a * dx * dx + 2.0 * b * dx * dy + c * dy * dy;
+        if q < 1.0 {
+            Some((-2.0 * q).exp())
+        } else {
+            None
+        }
+    }
+
+    /// Gaussian weighted average of the source pixels inside of the ellipse at the given center.
+    #[allow(clippy::cast_possible_truncation)]
+    fn average<P, C, V>(
+        &self,
+        img: &ImageBuffer<P, C>,
+        center: (f32, f32),
+        sampler: &Sampler<P>,
+    ) -> P
+    where
+        P: Pixel + CanLinearInterpolate<V, P> + 'static,
+        C: Deref<Target = [P::Subpixel]>,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        let (width, height) = img.dimensions();
+        let x_range = (center.0 - self.radius.0).ceil() as i64..=(center.0 + self.radius.0) as i64;
+        let y_range = (center.1 - self.radius.1).ceil() as i64..=(center.1 + self.radius.1) as i64;
+        let mut sum = None;
+        let mut total_weight: f32 = 0.0;
+        for j in y_range {
+            let dy = j as f32 - center.1;
+            for i in x_range.clone() {
+                let dx = i as f32 - center.0;
+                let weight = match self.weight(dx, dy) {
+                    Some(weight) => weight,
+                    None => continue,
+                };
+                let pixel = match (
+                    sampler.border.resolve(i, width),
+                    sampler.border.resolve(j, height),
+                ) {
+                    (Some(i), Some(j)) => *img.get_pixel(i, j),
+                    _ => match sampler.border {
+                        Border::Constant(color) => color,
+                        _ => unreachable!("only the constant border has no pixel to resolve"),
+                    },
+                };
+                let vector: V = interpolation::to_vector(pixel, sampler.alpha);
+                let term = weight * vector;
+                total_weight += weight;
+                sum = Some(match sum {
+                    None => term,
+                    Some(acc) => acc + term,
+                });
+            }
+        }
+        match sum {
+            Some(sum) => {
+                let normalized = (1.0 / total_weight) * sum;
+                interpolation::from_vector::<V, P, P>(normalized, sampler.alpha)
+            }
+            // Not even a pixel inside of the ellipse, which is at least a pixel wide.
+            None => sampler.sample(img, center.0, center.1),
+        }
+    }
+}
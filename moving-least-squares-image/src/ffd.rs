@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Free-form deformation (FFD) by cubic B-splines of a regular lattice of control nodes,
+//! as used in medical image registration (Rueckert et al., 1999).
+//!
+//! The displacement of every pixel is a cubic B-spline of the displacements
+//! of the 4x4 closest nodes, so the warp is twice continuously differentiable,
+//! and only depends on the nodes around it.
+
+use crate::bspline::filter;
+use crate::interpolation::CanLinearInterpolate;
+use crate::{
+    image_from_fn, warp_dense, warp_dense_into, DeformMethod, Field, Image, Mapping, Sampler,
+};
+use image::{ImageBuffer, LumaA, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, DerefMut, Mul};
+
+/// Cubic B-spline free-form deformation, mapping the pixels of the warped image
+/// to their location in the source image.
+///
+/// The nodes of the lattice are spaced by `spacing` pixels, starting one node
+/// before the top left pixel, up to two nodes after the bottom right pixel.
+/// The node at the column i and row j of the lattice is at
+/// `((i - 1) * spacing, (j - 1) * spacing)` in the warped image.
+///
+/// The warp is one to one if the displacements of the nodes
+/// are smaller than about 0.4 times the spacing (Choi and Lee, 2000).
+///
+/// With the `serde` feature, deserializing fails
+/// if there is not one displacement per lattice node.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawBSplineFfd"))]
+pub struct BSplineFfd {
+    width: u32,
+    height: u32,
+    spacing: u32,
+    /// Displacement of the lattice nodes, row by row.
+    displacements: Vec<(f32, f32)>,
+}
+
+/// Deserialized lattice, before checking its number of displacements.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawBSplineFfd {
+    width: u32,
+    height: u32,
+    spacing: u32,
+    displacements: Vec<(f32, f32)>,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<RawBSplineFfd> for BSplineFfd {
+    type Error = &'static str;
+
+    fn try_from(raw: RawBSplineFfd) -> Result<Self, Self::Error> {
+        if raw.spacing == 0 {
+            return Err("the lattice spacing must be strictly positive");
+        }
+        let (columns, rows) = lattice_size(raw.width, raw.height, raw.spacing);
+        if raw.displacements.len() != columns as usize * rows as usize {
+            return Err("there must be one displacement per node of the lattice");
+        }
+        Ok(Self {
+            width: raw.width,
+            height: raw.height,
+            spacing: raw.spacing,
+            displacements: raw.displacements,
+        })
+    }
+}
+
+impl BSplineFfd {
+    /// Fit the lattice of the warp of images of the given size,
+    /// to the MLS deformation of the control points, as in [`reverse_dense`](crate::reverse_dense).
+    ///
+    /// The B-spline interpolates the MLS deformation at the nodes of the lattice.
+    pub fn new(
+        width: u32,
+        height: u32,
+        controls_src: &[(f32, f32)],
+        controls_dst: &[(f32, f32)],
+        spacing: u32,
+        method: DeformMethod,
+    ) -> Self {
+        assert!(spacing > 0, "The lattice spacing must be strictly positive");
+        let (columns, rows) = lattice_size(width, height, spacing);
+        let s = spacing as f32;
+        let nodes: Vec<(f32, f32)> = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| ((i as f32 - 1.0) * s, (j as f32 - 1.0) * s))
+            .collect();
+        let sources = mls::deform_all(controls_dst, controls_src, &nodes, method);
+        let samples: Vec<(f32, f32)> = sources
+            .iter()
+            .zip(&nodes)
+            .map(|(src, node)| (src.0 - node.0, src.1 - node.1))
+            .collect();
+        let displacements = prefilter(&samples, columns as usize, rows as usize);
+        Self {
+            width,
+            height,
+            spacing,
+            displacements,
+        }
+    }
+
+    /// Lattice without any displacement, for the identity warp.
+    pub fn identity(width: u32, height: u32, spacing: u32) -> Self {
+        assert!(spacing > 0, "The lattice spacing must be strictly positive");
+        let (columns, rows) = lattice_size(width, height, spacing);
+        let displacements = vec![(0.0, 0.0); (columns * rows) as usize];
+        Self::from_displacements(width, height, spacing, displacements)
+    }
+
+    /// Lattice with the given displacements of its nodes, row by row,
+    /// such as the ones optimized by a registration.
+    ///
+    /// Panics if there are not as many displacements as lattice nodes.
+    pub fn from_displacements(
+        width: u32,
+        height: u32,
+        spacing: u32,
+        displacements: Vec<(f32, f32)>,
+    ) -> Self {
+        assert!(spacing > 0, "The lattice spacing must be strictly positive");
+        let (columns, rows) = lattice_size(width, height, spacing);
+        assert_eq!(
+            displacements.len(),
+            (columns * rows) as usize,
+            "There must be one displacement per node of the lattice"
+        );
+        Self {
+            width,
+            height,
+            spacing,
+            displacements,
+        }
+    }
+
+    /// Size of the images this lattice applies to.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Spacing of the lattice nodes, in pixels.
+    pub fn spacing(&self) -> u32 {
+        self.spacing
+    }
+
+    /// Number of columns and rows of the lattice nodes.
+    pub fn lattice_dimensions(&self) -> (u32, u32) {
+        lattice_size(self.width, self.height, self.spacing)
+    }
+
+    /// Displacement of the lattice nodes, row by row.
+    pub fn displacements(&self) -> &[(f32, f32)] {
+        &self.displacements
+    }
+
+    /// Mutable displacement of the lattice nodes, row by row, to edit them directly.
+    pub fn displacements_mut(&mut self) -> &mut [(f32, f32)] {
+        &mut self.displacements
+    }
+
+    /// Location in the source image of the given point of the warped image.
+    ///
+    /// Points outside of the image extend the B-spline of its closest nodes.
+    pub fn source(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (columns, rows) = self.lattice_dimensions();
+        let s = self.spacing as f32;
+        let (left, tx) = span(x / s, columns);
+        let (top, ty) = span(y / s, rows);
+        let (wx, wy) = (basis(tx), basis(ty));
+        let mut dx = 0.0;
+        let mut dy = 0.0;
+        for (j, wj) in wy.iter().enumerate() {
+            let start = (top + j) * columns as usize + left;
+            let nodes = &self.displacements[start..start + 4];
+            for (node, wi) in nodes.iter().zip(&wx) {
+                dx += wi * wj * node.0;
+                dy += wi * wj * node.1;
+            }
+        }
+        (x + dx, y + dy)
+    }
+
+    /// Coordinates field of the warp, to [`remap`](crate::remap) images.
+    pub fn field(&self) -> Field {
+        image_from_fn(self.width, self.height, |x, y| {
+            let (x2, y2) = self.source((x as f32, y as f32));
+            LumaA([x2, y2])
+        })
+    }
+
+    /// Compute the warped image.
+    ///
+    /// The source image may have any size, the warped image has the dimensions of the lattice.
+    pub fn warp<P, C, V>(&self, img_src: &ImageBuffer<P, C>, sampler: &Sampler<P>) -> Image<P>
+    where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        let mut warped = ImageBuffer::new(self.width, self.height);
+        self.warp_into(img_src, sampler, &mut warped);
+        warped
+    }
+
+    /// Same as [`warp`](BSplineFfd::warp), but writes the warped image into the given buffer,
+    /// to reuse its allocation.
+    ///
+    /// Panics if the buffer does not have the dimensions of the lattice.
+    pub fn warp_into<P, C, O, V>(
+        &self,
+        img_src: &ImageBuffer<P, C>,
+        sampler: &Sampler<P>,
+        warped: &mut ImageBuffer<P, O>,
+    ) where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        O: DerefMut<Target = [P::Subpixel]>,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        assert_eq!(
+            warped.dimensions(),
+            self.dimensions(),
+            "The warped image must have the dimensions of the lattice"
+        );
+        warp_dense_into(img_src, self, sampler, warped);
+    }
+}
+
+impl Mapping for BSplineFfd {
+    fn source(&self, point: (f32, f32)) -> (f32, f32) {
+        BSplineFfd::source(self, point)
+    }
+}
+
+/// Compute the image warped by a B-spline free-form deformation
+/// fit to the MLS deformation of the control points, with nodes spaced by `spacing` pixels.
+///
+/// Same as [`reverse_dense`](crate::reverse_dense), but smoother, see [`BSplineFfd`].
+pub fn reverse_ffd<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    spacing: u32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = img_src.dimensions();
+    let ffd = BSplineFfd::new(width, height, controls_src, controls_dst, spacing, method);
+    warp_dense(img_src, &ffd, &Sampler::default())
+}
+
+/// Number of columns and rows of the lattice nodes.
+fn lattice_size(width: u32, height: u32, spacing: u32) -> (u32, u32) {
+    let columns = (width.max(1) - 1) / spacing + 4;
+    let rows = (height.max(1) - 1) / spacing + 4;
+    (columns, rows)
+}
+
+/// Index of the first of the 4 nodes around a coordinate in spacing units,
+/// and the position of the coordinate after the second node.
+fn span(u: f32, nodes: u32) -> (usize, f32) {
+    let first = (u.floor() as i64).clamp(0, nodes as i64 - 4);
+    (first as usize, u - first as f32)
+}
+
+/// Weights of the cubic B-spline of the 4 nodes around a position t in [0, 1].
+fn basis(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let u = 1.0 - t;
+    [
+        u * u * u / 6.0,
+        (3.0 * t3 - 6.0 * t2 + 4.0) / 6.0,
+        (-3.0 * t3 + 3.0 * t2 + 3.0 * t + 1.0) / 6.0,
+        t3 / 6.0,
+    ]
+}
+
+/// Cubic B-spline coefficients interpolating the samples of a lattice, row by row.
+fn prefilter(samples: &[(f32, f32)], columns: usize, rows: usize) -> Vec<(f32, f32)> {
+    let mut xs: Vec<f64> = samples.iter().map(|s| f64::from(s.0)).collect();
+    let mut ys: Vec<f64> = samples.iter().map(|s| f64::from(s.1)).collect();
+    let mut line = Vec::with_capacity(columns.max(rows));
+    for coefs in [&mut xs, &mut ys] {
+        for row in coefs.chunks_exact_mut(columns) {
+            filter(row);
+        }
+        for i in 0..columns {
+            line.clear();
+            line.extend((0..rows).map(|j| coefs[j * columns + i]));
+            filter(&mut line);
+            (0..rows).for_each(|j| coefs[j * columns + i] = line[j]);
+        }
+    }
+    xs.iter()
+        .zip(&ys)
+        .map(|(&x, &y)| (x as f32, y as f32))
+        .collect()
+}
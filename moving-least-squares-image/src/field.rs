@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warps computed as coordinates fields instead of images,
+//! to apply the same warp to several aligned layers (color, depth, masks, ...).
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{anchors_warp, image_from_fn, reverse_mapping, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Luma, LumaA, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Coordinates (x, y) in the source image of every pixel of the warped image,
+/// in the two channels of each pixel.
+///
+/// The displacement of a pixel is its coordinates minus its own position.
+pub type Field = ImageBuffer<LumaA<f32>, Vec<f32>>;
+
+/// Compute the coordinates field of the warp of [`reverse_dense`](crate::reverse_dense),
+/// for images of the given size.
+pub fn reverse_dense_field(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> Field {
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    image_from_fn(width, height, |x, y| {
+        let (x2, y2) = mapping((x as f32, y as f32));
+        LumaA([x2, y2])
+    })
+}
+
+/// Compute the coordinates field of the warp of [`reverse_sparse`](crate::reverse_sparse),
+/// for images of the given size.
+pub fn reverse_sparse_field(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+) -> Field {
+    // the anchors are the MLS reprojection of the subresolution matrix of points
+    let anchors: Vec<(f32, f32)> = mls::deform_grid(
+        controls_dst,
+        controls_src,
+        width,
+        height,
+        subresolution_factor,
+        method,
+    );
+    let sub_width = ((width.max(1) - 1) / subresolution_factor + 2) as usize;
+    image_from_fn(width, height, |x, y| {
+        let (x2, y2) = anchors_warp(&anchors, sub_width, subresolution_factor, (x, y));
+        LumaA([x2, y2])
+    })
+}
+
+/// Sample the image at the coordinates of the field,
+/// like the `remap` function of OpenCV.
+///
+/// The warped image has the dimensions of the field.
+/// The interpolation and the border are the ones of the sampler.
+pub fn remap<P, C, V>(img_src: &ImageBuffer<P, C>, field: &Field, sampler: &Sampler<P>) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = field.dimensions();
+    image_from_fn(width, height, |x, y| {
+        let [x2, y2] = field.get_pixel(x, y).0;
+        sampler.sample(img_src, x2, y2)
+    })
+}
+
+// OpenCV maps #################################################################
+
+/// Number of bits of the fractional part of the fixed-point OpenCV maps (`INTER_BITS`).
+const INTER_BITS: u32 = 5;
+
+/// Number of subpixel positions of the fixed-point OpenCV maps (`INTER_TAB_SIZE`).
+const INTER_TAB_SIZE: i32 = 1 << INTER_BITS;
+
+/// Split the field into the `map_x` and `map_y` maps of `cv::remap`, with the `CV_32FC1` type.
+///
+/// Their raw buffers are row major, as the data of a `cv::Mat` of the same size.
+#[allow(clippy::type_complexity)]
+pub fn opencv_maps(
+    field: &Field,
+) -> (
+    ImageBuffer<Luma<f32>, Vec<f32>>,
+    ImageBuffer<Luma<f32>, Vec<f32>>,
+) {
+    let (width, height) = field.dimensions();
+    let map_x = ImageBuffer::from_fn(width, height, |x, y| Luma([field.get_pixel(x, y)[0]]));
+    let map_y = ImageBuffer::from_fn(width, height, |x, y| Luma([field.get_pixel(x, y)[1]]));
+    (map_x, map_y)
+}
+
+/// Encode the field as the fixed-point maps of `cv::remap`,
+/// as returned by `cv::convertMaps` with the `CV_16SC2` type.
+///
+/// The first map has the integer part of the coordinates (x, y) with the `CV_16SC2` type,
+/// and the second one the index of the subpixel position in the interpolation tables
+/// of OpenCV, with the `CV_16UC1` type.
+#[allow(clippy::type_complexity)]
+#[allow(clippy::cast_possible_truncation)]
+pub fn opencv_fixed_point_maps(
+    field: &Field,
+) -> (
+    ImageBuffer<LumaA<i16>, Vec<i16>>,
+    ImageBuffer<Luma<u16>, Vec<u16>>,
+) {
+    let (width, height) = field.dimensions();
+    // Rounded coordinates in 1 / INTER_TAB_SIZE pixel units,
+    // saturated like the conversions of OpenCV.
+    let fixed = |x, y| {
+        let [x2, y2] = field.get_pixel(x, y).0;
+        let scale = INTER_TAB_SIZE as f32;
+        ((x2 * scale).round() as i32, (y2 * scale).round() as i32)
+    };
+    let integer_part = |i: i32| (i >> INTER_BITS).clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+    let map_xy = ImageBuffer::from_fn(width, height, |x, y| {
+        let (ix, iy) = fixed(x, y);
+        LumaA([integer_part(ix), integer_part(iy)])
+    });
+    let map_tab = ImageBuffer::from_fn(width, height, |x, y| {
+        let (ix, iy) = fixed(x, y);
+        let mask = INTER_TAB_SIZE - 1;
+        Luma([((iy & mask) * INTER_TAB_SIZE + (ix & mask)) as u16])
+    });
+    (map_xy, map_tab)
+}
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Fast path of the dense warp for 8 bits images, such as `RgbImage`,
+//! sampling the source image with fixed point arithmetic.
+
+use crate::interpolation::bilinear_fixed;
+use crate::{image_from_fn, reverse_mapping, zero_pixel, DeformMethod, Image, Mapping};
+use image::{ImageBuffer, Pixel};
+use std::ops::Deref;
+
+/// Same as [`reverse_dense`](crate::reverse_dense), for 8 bits images,
+/// with the source image sampled by [`bilinear_fixed`] instead of floating point arithmetic.
+///
+/// The result differs by at most 1 from the floating point warp,
+/// except for images with an alpha channel, interpolated straight instead of premultiplied.
+pub fn reverse_dense_fixed<P, C>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel<Subpixel = u8> + Send + Sync + 'static,
+    C: Deref<Target = [u8]> + Sync,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_dense_fixed(img_src, &mapping)
+}
+
+/// Same as [`warp_dense`](crate::warp_dense) with the default sampler, for 8 bits images,
+/// with the source image sampled by [`bilinear_fixed`] instead of floating point arithmetic.
+pub fn warp_dense_fixed<P, C, M>(img_src: &ImageBuffer<P, C>, mapping: &M) -> Image<P>
+where
+    P: Pixel<Subpixel = u8> + Send + Sync + 'static,
+    C: Deref<Target = [u8]> + Sync,
+    M: Mapping + ?Sized,
+{
+    let (width, height) = img_src.dimensions();
+    image_from_fn(width, height, |x, y| {
+        let (x2, y2) = mapping.source((x as f32, y as f32));
+        bilinear_fixed(img_src, x2, y2).unwrap_or_else(zero_pixel)
+    })
+}
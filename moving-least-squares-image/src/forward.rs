@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Forward warp, pushing the source pixels to their deformed positions,
+//! instead of back projecting the pixels of the warped image.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{zero_pixel, DeformMethod, Image};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Compute the warped image by forward mapping the source pixels with an MLS algorithm.
+/// The last argument is the MLS method you choose.
+///
+/// Contrary to [`reverse_dense`](crate::reverse_dense), the deformation is not reversed:
+/// each source pixel is moved to its deformed position,
+/// and splatted on the 4 closest pixels with bilinear weights.
+/// The holes left inside the warped image, where it is stretched,
+/// are then filled from their neighbors.
+/// Pixels outside of the warped image are zero.
+pub fn forward_dense<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = img_src.dimensions();
+    let pixels: Vec<(f32, f32)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x as f32, y as f32)))
+        .collect();
+    let warped = mls::deform_all(controls_src, controls_dst, &pixels, method);
+
+    let mut splats = Splats::new(width, height);
+    for (&(x, y), &position) in pixels.iter().zip(&warped) {
+        let color = img_src.get_pixel(x as u32, y as u32).into_vector();
+        splats.add(position, color);
+    }
+    let covered = coverage(width, height, &warped);
+    let colors = fill_holes(width, height, splats.normalized(), &covered);
+
+    let outside = zero_pixel();
+    ImageBuffer::from_fn(width, height, |x, y| {
+        match colors[(y * width + x) as usize] {
+            Some(color) => P::from_vector(color),
+            None => outside,
+        }
+    })
+}
+
+/// Weighted sums of the colors splatted on each pixel.
+struct Splats<V> {
+    width: u32,
+    height: u32,
+    colors: Vec<Option<V>>,
+    weights: Vec<f32>,
+}
+
+impl<V> Splats<V>
+where
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            colors: vec![None; size],
+            weights: vec![0.0; size],
+        }
+    }
+
+    /// Splat the color at the given position on its 4 closest pixels.
+    #[allow(clippy::cast_possible_truncation)]
+    fn add(&mut self, (x, y): (f32, f32), color: V) {
+        if !(x.is_finite() && y.is_finite()) {
+            return;
+        }
+        let (u, v) = (x.floor(), y.floor());
+        let (a, b) = (x - u, y - v);
+        let (u, v) = (u as i64, v as i64);
+        let neighbors = [
+            (u, v, Mul::<f32>::mul(1.0 - a, 1.0 - b)),
+            (u + 1, v, Mul::<f32>::mul(a, 1.0 - b)),
+            (u, v + 1, Mul::<f32>::mul(1.0 - a, b)),
+            (u + 1, v + 1, Mul::<f32>::mul(a, b)),
+        ];
+        for &(i, j, weight) in &neighbors {
+            let inside =
+                (0..i64::from(self.width)).contains(&i) && (0..i64::from(self.height)).contains(&j);
+            if inside && weight > 0.0 {
+                let index = (j * i64::from(self.width) + i) as usize;
+                let term = weight * color;
+                self.colors[index] = Some(match self.colors[index] {
+                    None => term,
+                    Some(acc) => acc + term,
+                });
+                self.weights[index] += weight;
+            }
+        }
+    }
+
+    /// Colors of the pixels, normalized by the sum of their weights,
+    /// or `None` for pixels without any splat.
+    fn normalized(self) -> Vec<Option<V>> {
+        let weights = self.weights;
+        self.colors
+            .into_iter()
+            .zip(weights)
+            .map(|(color, weight)| color.map(|c| (1.0 / weight) * c))
+            .collect()
+    }
+}
+
+/// Pixels inside of the warped image, covered by the warped cells of 2x2 source pixels.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn coverage(width: u32, height: u32, warped: &[(f32, f32)]) -> Vec<bool> {
+    let mut covered = vec![false; (width * height) as usize];
+    let at = |x: u32, y: u32| warped[(y * width + x) as usize];
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            let [tl, tr, bl, br] = [at(x, y), at(x + 1, y), at(x, y + 1), at(x + 1, y + 1)];
+            for triangle in &[[tl, tr, br], [tl, br, bl]] {
+                let xs = triangle.iter().map(|p| p.0);
+                let ys = triangle.iter().map(|p| p.1);
+                let x_min = xs.clone().fold(f32::INFINITY, f32::min).ceil().max(0.0);
+                let x_max = xs.fold(f32::NEG_INFINITY, f32::max).floor();
+                let y_min = ys.clone().fold(f32::INFINITY, f32::min).ceil().max(0.0);
+                let y_max = ys.fold(f32::NEG_INFINITY, f32::max).floor();
+                let x_max = x_max.min((width - 1) as f32);
+                let y_max = y_max.min((height - 1) as f32);
+                // Also skips non finite triangles.
+                if !(x_min <= x_max && y_min <= y_max) {
+                    continue;
+                }
+                for j in y_min as u32..=y_max as u32 {
+                    for i in x_min as u32..=x_max as u32 {
+                        if in_triangle((i as f32, j as f32), triangle) {
+                            covered[(j * width + i) as usize] = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    covered
+}
+
+/// Check if the point is inside of the triangle, or on its edges, whatever its orientation.
+fn in_triangle(point: (f32, f32), [a, b, c]: &[(f32, f32); 3]) -> bool {
+    let cross = |o: (f32, f32), p: (f32, f32)| {
+        (p.0 - o.0) * (point.1 - o.1) - (p.1 - o.1) * (point.0 - o.0)
+    };
+    let (d1, d2, d3) = (cross(*a, *b), cross(*b, *c), cross(*c, *a));
+    let has_negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_negative && has_positive)
+}
+
+/// Fill the covered pixels without color with the mean of their neighbors,
+/// iteratively until no hole can be filled anymore.
+fn fill_holes<V>(
+    width: u32,
+    height: u32,
+    mut colors: Vec<Option<V>>,
+    covered: &[bool],
+) -> Vec<Option<V>>
+where
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    let mut holes: Vec<(u32, u32)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let index = (y * width + x) as usize;
+            covered[index] && colors[index].is_none()
+        })
+        .collect();
+    while !holes.is_empty() {
+        let filled: Vec<((u32, u32), Option<V>)> = holes
+            .iter()
+            .map(|&(x, y)| ((x, y), neighbors_mean(width, height, &colors, (x, y))))
+            .collect();
+        let nb_holes = holes.len();
+        holes.clear();
+        for ((x, y), color) in filled {
+            match color {
+                Some(_) => colors[(y * width + x) as usize] = color,
+                None => holes.push((x, y)),
+            }
+        }
+        if holes.len() == nb_holes {
+            break;
+        }
+    }
+    colors
+}
+
+/// Mean color of the 8 neighbors of the pixel with a color, if any.
+#[allow(clippy::cast_precision_loss)]
+fn neighbors_mean<V>(width: u32, height: u32, colors: &[Option<V>], (x, y): (u32, u32)) -> Option<V>
+where
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    let mut sum = None;
+    let mut count = 0;
+    for j in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+        for i in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+            if let Some(color) = colors[(j * width + i) as usize] {
+                sum = Some(match sum {
+                    None => color,
+                    Some(acc) => acc + color,
+                });
+                count += 1;
+            }
+        }
+    }
+    sum.map(|s| (1.0 / count as f32) * s)
+}
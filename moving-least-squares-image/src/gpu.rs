@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Building blocks of warps on the GPU, for real-time previews.
+//!
+//! The WGSL shader evaluates the MLS deformation per fragment,
+//! and samples the source texture with the hardware bilinear filtering.
+//! The helpers here pack the buffers it expects, in the layout of its bindings,
+//! and the `wgpu` feature provides a [`GpuWarper`] running it with wgpu.
+//!
+//! Alternatively, [`textured_mesh`] only computes the deformed geometry,
+//! to be drawn with any graphics API sampling the source texture.
+
+use crate::DeformMethod;
+use moving_least_squares as mls;
+
+/// WGSL shader of the dense warp, with the `vs_main` vertex entry point
+/// drawing a full screen triangle (3 vertices, no vertex buffer),
+/// and the `fs_main` fragment entry point.
+///
+/// Its bindings, all in group 0, are:
+///  - 0: uniform buffer of the parameters, see [`gpu_params`],
+///  - 1: read-only storage buffer of the control points, see [`gpu_controls`],
+///  - 2: source texture, `texture_2d<f32>`,
+///  - 3: filtering sampler of the source texture, clamping to its edges.
+pub const MLS_WGSL: &str = include_str!("mls.wgsl");
+
+/// Content of the uniform buffer of the parameters of the shader,
+/// as bytes in native endianness (16 bytes).
+pub fn gpu_params(nb_controls: usize, method: DeformMethod, src_size: (u32, u32)) -> Vec<u8> {
+    let method_code: u32 = match method {
+        DeformMethod::Affine => 0,
+        DeformMethod::Similarity => 1,
+        DeformMethod::Rigid => 2,
+    };
+    let mut bytes = Vec::with_capacity(16);
+    bytes.extend_from_slice(&(nb_controls as u32).to_ne_bytes());
+    bytes.extend_from_slice(&method_code.to_ne_bytes());
+    bytes.extend_from_slice(&(src_size.0 as f32).to_ne_bytes());
+    bytes.extend_from_slice(&(src_size.1 as f32).to_ne_bytes());
+    bytes
+}
+
+/// Content of the storage buffer of the control points of the shader,
+/// as bytes in native endianness (16 bytes per control point).
+///
+/// The control points are given as for [`reverse_dense`](crate::reverse_dense),
+/// the shader back projects the pixels of the warped image.
+pub fn gpu_controls(controls_src: &[(f32, f32)], controls_dst: &[(f32, f32)]) -> Vec<u8> {
+    controls_dst
+        .iter()
+        .zip(controls_src)
+        .flat_map(|(&(px, py), &(qx, qy))| [px, py, qx, qy])
+        .flat_map(f32::to_ne_bytes)
+        .collect()
+}
+
+// Textured mesh ###############################################################
+
+/// Triangle mesh of a warped image, to draw the source texture deformed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TexturedMesh {
+    /// Positions of the vertices in the warped image, in pixels.
+    pub positions: Vec<[f32; 2]>,
+    /// Texture coordinates of the vertices in the source image, between 0 and 1.
+    pub uvs: Vec<[f32; 2]>,
+    /// Indices of the vertices of the triangles, 3 per triangle.
+    pub indices: Vec<u32>,
+}
+
+/// Compute the mesh of the image forward warped with an MLS algorithm,
+/// from a grid with a vertex every `step` pixels of the source image.
+///
+/// The grid covers the whole source image, from the outer edge of its border pixels,
+/// with the texture coordinates of the undeformed grid,
+/// and each grid cell is split in two triangles.
+/// Like the rest of the crate, pixel centers are at integer positions,
+/// so the edges of the image are at -0.5 and at its size minus 0.5.
+pub fn textured_mesh(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    step: u32,
+    method: DeformMethod,
+) -> TexturedMesh {
+    assert!(step > 0, "The grid step must be strictly positive");
+    // coordinates of the grid lines, with the last one on the edge of the image
+    let lines = |size: u32| -> Vec<f32> {
+        let mut lines: Vec<f32> = (0..size)
+            .step_by(step as usize)
+            .map(|l| l as f32 - 0.5)
+            .collect();
+        lines.push(size as f32 - 0.5);
+        lines
+    };
+    let (xs, ys) = (lines(width), lines(height));
+    let grid: Vec<(f32, f32)> = ys
+        .iter()
+        .flat_map(|&y| xs.iter().map(move |&x| (x, y)))
+        .collect();
+    let deformed = mls::deform_all(controls_src, controls_dst, &grid, method);
+
+    let (w, h) = (width as f32, height as f32);
+    let nb_columns = xs.len() as u32;
+    let indices = (0..ys.len() as u32 - 1)
+        .flat_map(|row| (0..nb_columns - 1).map(move |column| row * nb_columns + column))
+        .flat_map(|tl| {
+            let (tr, bl) = (tl + 1, tl + nb_columns);
+            [tl, bl, tr, tr, bl, bl + 1]
+        })
+        .collect();
+    TexturedMesh {
+        positions: deformed.iter().map(|&(x, y)| [x, y]).collect(),
+        uvs: grid
+            .iter()
+            .map(|&(x, y)| [(x + 0.5) / w, (y + 0.5) / h])
+            .collect(),
+        indices,
+    }
+}
+
+// wgpu pipeline ###############################################################
+
+/// Render pipeline of the dense warp on the GPU, with wgpu.
+///
+/// It renders into the texture views of the caller,
+/// so that previews can be displayed without any copy back to the CPU.
+#[cfg(feature = "wgpu")]
+pub struct GpuWarper {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+#[cfg(feature = "wgpu")]
+impl GpuWarper {
+    /// Create the render pipeline, for render targets of the given format.
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> Self {
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("mls warp"),
+            source: wgpu::ShaderSource::Wgsl(MLS_WGSL.into()),
+            flags: wgpu::ShaderFlags::all(),
+        });
+        let fragment = wgpu::ShaderStage::FRAGMENT;
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: fragment,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("mls warp"),
+            entries: &[
+                buffer_entry(0, wgpu::BufferBindingType::Uniform),
+                buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: fragment,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: fragment,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mls warp"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mls warp"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mls warp"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// Record the rendering of the warped image into the target view,
+    /// with the same arguments than [`reverse_dense`](crate::reverse_dense).
+    ///
+    /// The source view must be a filterable 2D texture of the given size.
+    /// The warped image has the size of the target.
+    ///
+    /// Panics if there is no control point.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        src_view: &wgpu::TextureView,
+        src_size: (u32, u32),
+        controls_src: &[(f32, f32)],
+        controls_dst: &[(f32, f32)],
+        method: DeformMethod,
+        target: &wgpu::TextureView,
+    ) {
+        use wgpu::util::DeviceExt;
+
+        assert!(!controls_src.is_empty(), "There must be control points");
+        let nb_controls = controls_src.len().min(controls_dst.len());
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mls warp params"),
+            contents: &gpu_params(nb_controls, method, src_size),
+            usage: wgpu::BufferUsage::UNIFORM,
+        });
+        let controls = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mls warp controls"),
+            contents: &gpu_controls(controls_src, controls_dst),
+            usage: wgpu::BufferUsage::STORAGE,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mls warp"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: controls.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(src_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("mls warp"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Convenience functions for grayscale images, such as `GrayImage`
+//! or `ImageBuffer<Luma<u16>, Vec<u16>>`, common in medical and document imaging.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{reverse_dense, reverse_sparse, DeformMethod, Image};
+use image::{ImageBuffer, Luma, Primitive};
+use std::ops::Deref;
+
+/// Same as [`reverse_dense`], for grayscale images.
+pub fn reverse_dense_gray<T, C>(
+    img_src: &ImageBuffer<Luma<T>, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> Image<Luma<T>>
+where
+    T: Primitive + CanLinearInterpolate<f32, T> + Send + Sync + 'static,
+    C: Deref<Target = [T]> + Sync,
+{
+    reverse_dense(img_src, controls_src, controls_dst, method)
+}
+
+/// Same as [`reverse_sparse`], for grayscale images.
+pub fn reverse_sparse_gray<T, C>(
+    img_src: &ImageBuffer<Luma<T>, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+) -> Image<Luma<T>>
+where
+    T: Primitive + CanLinearInterpolate<f32, T> + Send + Sync + 'static,
+    C: Deref<Target = [T]> + Sync,
+{
+    reverse_sparse(
+        img_src,
+        controls_src,
+        controls_dst,
+        subresolution_factor,
+        method,
+    )
+}
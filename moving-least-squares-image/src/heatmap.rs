@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Jacobian determinant of the deformation at every pixel of the warped image,
+//! and its heat map, to spot compressions, stretches and fold-overs.
+
+use crate::{image_from_fn, DeformMethod, Image};
+use image::{Luma, Rgb};
+use moving_least_squares as mls;
+
+/// Area ratio at which the heat map colors are saturated.
+const SATURATION: f32 = 4.0;
+
+/// Color of the pixels where the deformation folds over itself.
+const FOLD_COLOR: Rgb<u8> = Rgb([0, 200, 0]);
+
+/// Jacobian determinant of the deformation of [`reverse_dense`](crate::reverse_dense),
+/// at every pixel of the warped image.
+///
+/// This is the local area ratio of the warped image to the source image:
+/// above 1 where the image is stretched, below 1 where it is compressed,
+/// and negative where the deformation folds over itself.
+pub fn jacobian_determinant(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> Image<Luma<f32>> {
+    image_from_fn(width, height, |x, y| {
+        let point = (x as f32, y as f32);
+        // The back projection has the inverse determinant, with the same sign.
+        let [[a, b], [c, d]] = mls::jacobian(controls_dst, controls_src, point, method);
+        Luma([1.0 / (a * d - b * c)])
+    })
+}
+
+/// Color map of the determinants given by [`jacobian_determinant`],
+/// white where the area is preserved, shading to blue where the image is compressed
+/// and to red where it is stretched, saturated at a 4x area ratio,
+/// and green where the deformation folds over itself.
+pub fn determinant_heatmap(determinant: &Image<Luma<f32>>) -> Image<Rgb<u8>> {
+    let (width, height) = determinant.dimensions();
+    image_from_fn(width, height, |x, y| {
+        heat_color(determinant.get_pixel(x, y).0[0])
+    })
+}
+
+/// Color of the determinant in the heat map.
+fn heat_color(det: f32) -> Rgb<u8> {
+    if det <= 0.0 || det.is_nan() {
+        return FOLD_COLOR;
+    }
+    // Log scale in [-1, 1], symmetric for compressions and stretches.
+    let t = (det.log2() / SATURATION.log2()).clamp(-1.0, 1.0);
+    let fade = (255.0 * (1.0 - t.abs())).round() as u8;
+    if t < 0.0 {
+        Rgb([fade, fade, 255])
+    } else {
+        Rgb([255, fade, fade])
+    }
+}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Incremental update of a warped image when a single control point is dragged,
+//! only re-warping the region affected by its move.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{reverse_mapping, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Step of the grid where the change of the deformation is measured.
+const DIRTY_STEP: u32 = 8;
+
+/// Rectangular region of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Column of the left pixels of the region.
+    pub x: u32,
+    /// Row of the top pixels of the region.
+    pub y: u32,
+    /// Number of columns of the region.
+    pub width: u32,
+    /// Number of rows of the region.
+    pub height: u32,
+}
+
+/// Estimate the region of the warped image changed by more than `tolerance` pixels
+/// when the destination control point at index `handle` moved from `previous`
+/// to its current position in `controls_dst`.
+///
+/// The deformation is compared before and after the move on a sparse grid,
+/// so a change smaller than the grid step between two grid points may be missed.
+/// Returns `None` if nothing changed more than the tolerance.
+#[allow(clippy::too_many_arguments)]
+pub fn dirty_region(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    handle: usize,
+    previous: (f32, f32),
+    method: DeformMethod,
+    tolerance: f32,
+) -> Option<Region> {
+    let mut controls_previous = controls_dst.to_vec();
+    controls_previous[handle] = previous;
+    let nb_columns = (width.max(1) - 1) / DIRTY_STEP + 2;
+    let nb_rows = (height.max(1) - 1) / DIRTY_STEP + 2;
+    let grid: Vec<(u32, u32)> = (0..nb_rows)
+        .flat_map(|row| (0..nb_columns).map(move |column| (column, row)))
+        .collect();
+    let positions: Vec<(f32, f32)> = grid
+        .iter()
+        .map(|&(column, row)| ((column * DIRTY_STEP) as f32, (row * DIRTY_STEP) as f32))
+        .collect();
+    let before = mls::deform_all(&controls_previous, controls_src, &positions, method);
+    let after = mls::deform_all(controls_dst, controls_src, &positions, method);
+
+    // Bounding box of the grid points that moved too much, in grid units.
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for ((&(column, row), b), a) in grid.iter().zip(&before).zip(&after) {
+        let change = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+        // Also catches points that became (or stopped being) non finite.
+        if change > tolerance || (change.is_nan() && a != b) {
+            bounds = Some(match bounds {
+                None => (column, row, column, row),
+                Some((left, top, right, bottom)) => (
+                    left.min(column),
+                    top.min(row),
+                    right.max(column),
+                    bottom.max(row),
+                ),
+            });
+        }
+    }
+
+    // The pixels in all the grid cells around the dirty grid points may have changed.
+    let (left, top, right, bottom) = bounds?;
+    let x = left.saturating_sub(1) * DIRTY_STEP;
+    let y = top.saturating_sub(1) * DIRTY_STEP;
+    let x_end = ((right + 1) * DIRTY_STEP).min(width.max(1) - 1);
+    let y_end = ((bottom + 1) * DIRTY_STEP).min(height.max(1) - 1);
+    Some(Region {
+        x,
+        y,
+        width: x_end - x + 1,
+        height: y_end - y + 1,
+    })
+}
+
+/// Re-warp densely, as [`reverse_dense_with`](crate::reverse_dense_with),
+/// only the pixels of the given region of the warped image.
+pub fn rewarp_region<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    warped: &mut Image<P>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    region: Region,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    let (width, height) = warped.dimensions();
+    for y in region.y..(region.y + region.height).min(height) {
+        for x in region.x..(region.x + region.width).min(width) {
+            let (x2, y2) = mapping((x as f32, y as f32));
+            warped.put_pixel(x, y, sampler.sample(img_src, x2, y2));
+        }
+    }
+}
+
+/// Update the warped image after the destination control point at index `handle`
+/// moved from `previous` to its current position in `controls_dst`,
+/// by only re-warping the region estimated by [`dirty_region`].
+///
+/// The warped image must be the result of [`reverse_dense_with`](crate::reverse_dense_with)
+/// with the previous control points and the same method and sampler.
+/// Returns the re-warped region, if any.
+#[allow(clippy::too_many_arguments)]
+pub fn rewarp_moved_handle<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    warped: &mut Image<P>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    handle: usize,
+    previous: (f32, f32),
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    tolerance: f32,
+) -> Option<Region>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = warped.dimensions();
+    let region = dirty_region(
+        width,
+        height,
+        controls_src,
+        controls_dst,
+        handle,
+        previous,
+        method,
+        tolerance,
+    )?;
+    rewarp_region(
+        img_src,
+        warped,
+        controls_src,
+        controls_dst,
+        method,
+        sampler,
+        region,
+    );
+    Some(region)
+}
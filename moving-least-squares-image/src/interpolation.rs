@@ -1,24 +1,75 @@
 // SPDX-License-Identifier: MPL-2.0
 
-//! Helper functions to interpolate / extrapolate warped images.
+//! Interpolation of images at floating point coordinates,
+//! usable for any remapping, not only MLS warps.
+//!
+//! The [`nearest`], [`bilinear`], [`bilinear_fixed`] and [`bicubic`] functions interpolate
+//! inside of the image, from 0 to `width - 1` horizontally and from 0 to `height - 1`
+//! vertically, included, and return `None` outside of it.
+//! They work with all the pixel types implementing [`CanLinearInterpolate`],
+//! except [`bilinear_fixed`], a faster version of [`bilinear`] for 8 bits images.
+//! For other borders and interpolations, see the [`Sampler`](crate::Sampler).
 
-use image::{ImageBuffer, Pixel, Primitive, Rgb};
+use crate::{Alpha, Interpolation};
+use image::{ImageBuffer, Luma, LumaA, Pixel, Primitive, Rgb, Rgba};
 use std::ops::{Add, Deref, Mul};
 
-/// Trait for types that can be linearly interpolated with the `linear` function.
+/// Trait for pixel types that can be linearly interpolated,
+/// by converting them to vectors, computing weighted sums of these vectors,
+/// and converting the result back.
 ///
 /// The `Vector` generic type refers to the intermediate type used during interpolations.
 /// It usually is the `f32` scalar or a vector of `f32` values.
 ///
 /// The `Output` type is the final type obtained after interpolation.
 /// It is obtained via conversion from the interpolated vector.
-pub trait CanLinearInterpolate<Vector, Output>
+///
+/// This trait is sealed: it is implemented for the channel types `u8`, `u16` and `f32`,
+/// for the pixel types of the image crate with these channels,
+/// and for arrays of these channels with any length, interpolated as a [`VecN`].
+pub trait CanLinearInterpolate<Vector, Output>: private::Sealed
 where
     Vector: Add<Output = Vector>,
     f32: Mul<Vector, Output = Vector>,
 {
+    /// Vector of the pixel, with its colors premultiplied by its alpha, if any.
     fn into_vector(self) -> Vector;
+
+    /// Output of the interpolated vector, inverse of `into_vector`.
     fn from_vector(v: Vector) -> Output;
+
+    /// Same as `into_vector`, but colors are not premultiplied by their alpha, if any.
+    fn into_straight_vector(self) -> Vector
+    where
+        Self: Sized,
+    {
+        self.into_vector()
+    }
+
+    /// Same as `from_vector`, for vectors obtained with `into_straight_vector`.
+    fn from_straight_vector(v: Vector) -> Output
+    where
+        Self: Sized,
+    {
+        Self::from_vector(v)
+    }
+}
+
+mod private {
+    use image::{Luma, LumaA, Primitive, Rgb, Rgba};
+
+    /// Restriction of the implementations of the interpolation trait to this crate.
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for f32 {}
+    impl<T> Sealed for (T, T, T) {}
+    impl<T, const N: usize> Sealed for [T; N] {}
+    impl<T: Primitive> Sealed for Rgb<T> {}
+    impl<T: Primitive> Sealed for Rgba<T> {}
+    impl<T: Primitive> Sealed for Luma<T> {}
+    impl<T: Primitive> Sealed for LumaA<T> {}
 }
 
 /// Implement CanLinearInterpolate for u8 with f32 outputs.
@@ -28,7 +79,7 @@ impl CanLinearInterpolate<f32, f32> for u8 {
         self as f32
     }
     fn from_vector(v: f32) -> f32 {
-        (v / 255.0).max(0.0).min(1.0)
+        (v / 255.0).clamp(0.0, 1.0)
     }
 }
 
@@ -49,7 +100,7 @@ impl CanLinearInterpolate<f32, f32> for u16 {
         self as f32
     }
     fn from_vector(v: f32) -> f32 {
-        (v / u16::MAX as f32).max(0.0).min(1.0)
+        (v / u16::MAX as f32).clamp(0.0, 1.0)
     }
 }
 
@@ -63,53 +114,170 @@ impl CanLinearInterpolate<f32, u16> for u16 {
     }
 }
 
+/// Implement CanLinearInterpolate for f32.
+/// Values are not clamped, so that high dynamic range images keep their range.
+impl CanLinearInterpolate<f32, f32> for f32 {
+    fn into_vector(self) -> f32 {
+        self
+    }
+    fn from_vector(v: f32) -> f32 {
+        v
+    }
+}
+
 /// Implement CanLinearInterpolate for (T,T,T) if T also implements it.
-impl<O, T: CanLinearInterpolate<f32, O>> CanLinearInterpolate<Vec3, (O, O, O)> for (T, T, T) {
-    fn into_vector(self) -> Vec3 {
-        Vec3 {
-            x: self.0.into_vector(),
-            y: self.1.into_vector(),
-            z: self.2.into_vector(),
-        }
+impl<O, T: CanLinearInterpolate<f32, O>> CanLinearInterpolate<VecN<3>, (O, O, O)> for (T, T, T) {
+    fn into_vector(self) -> VecN<3> {
+        VecN([
+            self.0.into_vector(),
+            self.1.into_vector(),
+            self.2.into_vector(),
+        ])
     }
-    fn from_vector(v: Vec3) -> (O, O, O) {
-        (
-            T::from_vector(v.x),
-            T::from_vector(v.y),
-            T::from_vector(v.z),
-        )
+    fn from_vector(v: VecN<3>) -> (O, O, O) {
+        let [x, y, z] = v.0;
+        (T::from_vector(x), T::from_vector(y), T::from_vector(z))
+    }
+}
+
+/// Implement CanLinearInterpolate for [T; N] if T also implements it,
+/// for images with an arbitrary number of channels, such as multispectral images.
+impl<O, T: CanLinearInterpolate<f32, O>, const N: usize> CanLinearInterpolate<VecN<N>, [O; N]>
+    for [T; N]
+{
+    fn into_vector(self) -> VecN<N> {
+        VecN(self.map(|c| c.into_vector()))
+    }
+    fn from_vector(v: VecN<N>) -> [O; N] {
+        v.0.map(T::from_vector)
     }
 }
 
 /// Implement CanLinearInterpolate for Rgb<T> if T also implements it.
-impl<T, O> CanLinearInterpolate<Vec3, Rgb<O>> for Rgb<T>
+impl<T, O> CanLinearInterpolate<VecN<3>, Rgb<O>> for Rgb<T>
 where
     T: Primitive + CanLinearInterpolate<f32, O>,
     O: Primitive,
 {
-    fn into_vector(self) -> Vec3 {
-        let [x, y, z] = self.0;
-        Vec3 {
-            x: x.into_vector(),
-            y: y.into_vector(),
-            z: z.into_vector(),
-        }
+    fn into_vector(self) -> VecN<3> {
+        VecN(self.0.map(|c| c.into_vector()))
+    }
+    fn from_vector(v: VecN<3>) -> Rgb<O> {
+        Rgb(v.0.map(T::from_vector))
+    }
+}
+
+/// Implement CanLinearInterpolate for Rgba<T> if T also implements it.
+///
+/// Colors are interpolated premultiplied by their alpha,
+/// so that the color of transparent pixels does not bleed into their neighbors.
+impl<T, O> CanLinearInterpolate<VecN<4>, Rgba<O>> for Rgba<T>
+where
+    T: Primitive + CanLinearInterpolate<f32, O>,
+    O: Primitive,
+{
+    fn into_vector(self) -> VecN<4> {
+        let [r, g, b, a] = self.0;
+        let alpha = a.into_vector();
+        VecN([
+            alpha * r.into_vector(),
+            alpha * g.into_vector(),
+            alpha * b.into_vector(),
+            alpha,
+        ])
     }
-    fn from_vector(v: Vec3) -> Rgb<O> {
-        Rgb([
-            T::from_vector(v.x),
-            T::from_vector(v.y),
-            T::from_vector(v.z),
+    fn from_vector(v: VecN<4>) -> Rgba<O> {
+        let [r, g, b, alpha] = v.0;
+        // Fully transparent pixels have no meaningful color.
+        let inv_alpha = if alpha > 0.0 { 1.0 / alpha } else { 0.0 };
+        Rgba([
+            T::from_vector(inv_alpha * r),
+            T::from_vector(inv_alpha * g),
+            T::from_vector(inv_alpha * b),
+            T::from_vector(alpha),
         ])
     }
+    fn into_straight_vector(self) -> VecN<4> {
+        VecN(self.0.map(|c| c.into_vector()))
+    }
+    fn from_straight_vector(v: VecN<4>) -> Rgba<O> {
+        Rgba(v.0.map(T::from_vector))
+    }
+}
+
+/// Implement CanLinearInterpolate for Luma<T> if T also implements it.
+impl<T, O> CanLinearInterpolate<f32, Luma<O>> for Luma<T>
+where
+    T: Primitive + CanLinearInterpolate<f32, O>,
+    O: Primitive,
+{
+    fn into_vector(self) -> f32 {
+        self.0[0].into_vector()
+    }
+    fn from_vector(v: f32) -> Luma<O> {
+        Luma([T::from_vector(v)])
+    }
+}
+
+/// Implement CanLinearInterpolate for LumaA<T> if T also implements it.
+///
+/// The gray level is interpolated premultiplied by the alpha, as for [`Rgba`] pixels.
+impl<T, O> CanLinearInterpolate<VecN<2>, LumaA<O>> for LumaA<T>
+where
+    T: Primitive + CanLinearInterpolate<f32, O>,
+    O: Primitive,
+{
+    fn into_vector(self) -> VecN<2> {
+        let [l, a] = self.0;
+        let alpha = a.into_vector();
+        VecN([alpha * l.into_vector(), alpha])
+    }
+    fn from_vector(v: VecN<2>) -> LumaA<O> {
+        let [l, alpha] = v.0;
+        // Fully transparent pixels have no meaningful gray level.
+        let inv_alpha = if alpha > 0.0 { 1.0 / alpha } else { 0.0 };
+        LumaA([T::from_vector(inv_alpha * l), T::from_vector(alpha)])
+    }
+    fn into_straight_vector(self) -> VecN<2> {
+        VecN(self.0.map(|c| c.into_vector()))
+    }
+    fn from_straight_vector(v: VecN<2>) -> LumaA<O> {
+        LumaA(v.0.map(T::from_vector))
+    }
+}
+
+/// Color of the closest pixel to the floating point coordinates,
+/// or `None` outside of the image.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub fn nearest<P, Container>(img: &ImageBuffer<P, Container>, x: f32, y: f32) -> Option<P>
+where
+    P: Pixel + 'static,
+    Container: Deref<Target = [P::Subpixel]>,
+{
+    let (width, height) = img.dimensions();
+    if inside(width, height, x, y) {
+        Some(*img.get_pixel(x.round() as u32, y.round() as u32))
+    } else {
+        None
+    }
 }
 
 /// Simple bilinear interpolation of a pixel with floating point coordinates.
+///
+/// Returns `None` outside of the image, which spans from 0 to `width - 1` horizontally
+/// and from 0 to `height - 1` vertically, included.
+/// On the last column and row, the missing neighbors are clamped to the image.
 #[allow(clippy::many_single_char_names)]
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::cast_sign_loss)]
 #[allow(clippy::cast_precision_loss)]
-pub fn bilinear<V, P, Container, O>(img: &ImageBuffer<P, Container>, x: f32, y: f32) -> Option<O>
+pub fn bilinear<V, P, Container, O>(
+    img: &ImageBuffer<P, Container>,
+    x: f32,
+    y: f32,
+    alpha: Alpha,
+) -> Option<O>
 where
     V: Add<Output = V>,
     f32: Mul<V, Output = V>,
@@ -118,61 +286,222 @@ where
     P: CanLinearInterpolate<V, O>,
 {
     let (width, height) = img.dimensions();
-    let u = x.floor();
-    let v = y.floor();
-    if u >= 0.0 && u < (width - 2) as f32 && v >= 0.0 && v < (height - 2) as f32 {
+    if inside(width, height, x, y) {
         // Linear interpolation inside boundaries.
+        let u = x.floor();
+        let v = y.floor();
         let u_0 = u as u32;
         let v_0 = v as u32;
-        let u_1 = u_0 + 1;
-        let v_1 = v_0 + 1;
+        let u_1 = (u_0 + 1).min(width - 1);
+        let v_1 = (v_0 + 1).min(height - 1);
         let a = x - u;
         let b = y - v;
-        let uv_00 = img.get_pixel(u_0, v_0).into_vector();
-        let uv_10 = img.get_pixel(u_1, v_0).into_vector();
-        let uv_01 = img.get_pixel(u_0, v_1).into_vector();
-        let uv_11 = img.get_pixel(u_1, v_1).into_vector();
+        let uv_00 = to_vector(*img.get_pixel(u_0, v_0), alpha);
+        let uv_10 = to_vector(*img.get_pixel(u_1, v_0), alpha);
+        let uv_01 = to_vector(*img.get_pixel(u_0, v_1), alpha);
+        let uv_11 = to_vector(*img.get_pixel(u_1, v_1), alpha);
         let interp = Mul::<f32>::mul(1.0 - b, 1.0 - a) * uv_00
             + Mul::<f32>::mul(b, 1.0 - a) * uv_01
             + Mul::<f32>::mul(1.0 - b, a) * uv_10
             + Mul::<f32>::mul(b, a) * uv_11;
-        Some(P::from_vector(interp))
+        Some(from_vector::<V, P, O>(interp, alpha))
     } else {
         None
     }
 }
 
-// 3D vector helper ############################################################
-// That's to avoid a dependency on a heavy package such as nalgebra
+/// Bilinear interpolation of a pixel of an 8 bits image with fixed point arithmetic,
+/// faster than [`bilinear`] since no channel is converted to floating point.
+///
+/// The weights are quantized to 1/256 of a pixel, and the channels are interpolated
+/// independently, so an alpha channel, if any, is interpolated straight.
+/// Returns `None` outside of the image, as [`bilinear`].
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub fn bilinear_fixed<P, Container>(img: &ImageBuffer<P, Container>, x: f32, y: f32) -> Option<P>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+    Container: Deref<Target = [u8]>,
+{
+    let (width, height) = img.dimensions();
+    if !inside(width, height, x, y) {
+        return None;
+    }
+    let u_0 = x as u32;
+    let v_0 = y as u32;
+    let u_1 = (u_0 + 1).min(width - 1);
+    let v_1 = (v_0 + 1).min(height - 1);
+    // 8.8 fixed point weights, in [0, 256].
+    let a = ((x - u_0 as f32) * 256.0).round() as u32;
+    let b = ((y - v_0 as f32) * 256.0).round() as u32;
+    let uv_00 = img.get_pixel(u_0, v_0).channels();
+    let uv_10 = img.get_pixel(u_1, v_0).channels();
+    let uv_01 = img.get_pixel(u_0, v_1).channels();
+    let uv_11 = img.get_pixel(u_1, v_1).channels();
+    let mut interp = *img.get_pixel(u_0, v_0);
+    for (c, channel) in interp.channels_mut().iter_mut().enumerate() {
+        let top = u32::from(uv_00[c]) * (256 - a) + u32::from(uv_10[c]) * a;
+        let bottom = u32::from(uv_01[c]) * (256 - a) + u32::from(uv_11[c]) * a;
+        // At most 255 * 256 * 256 + 2^15, which fits in a u32.
+        *channel = ((top * (256 - b) + bottom * b + (1 << 15)) >> 16) as u8;
+    }
+    Some(interp)
+}
 
-/// Vec3 represented by a 3x1 column vector.
-#[derive(Clone, Copy)]
-pub struct Vec3 {
+/// Catmull-Rom bicubic interpolation of a pixel with floating point coordinates,
+/// sharper than the bilinear interpolation, with a slight overshoot near edges.
+///
+/// Returns `None` outside of the image, as [`bilinear`].
+/// The neighbors outside of the image are clamped to its border.
+pub fn bicubic<V, P, Container, O>(
+    img: &ImageBuffer<P, Container>,
     x: f32,
     y: f32,
-    z: f32,
+    alpha: Alpha,
+) -> Option<O>
+where
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    P: Pixel + 'static,
+    Container: Deref<Target = [P::Subpixel]>,
+    P: CanLinearInterpolate<V, O>,
+{
+    let (width, height) = img.dimensions();
+    if !inside(width, height, x, y) {
+        return None;
+    }
+    let method = Interpolation::Bicubic;
+    let kernel = |t| method.weight(t);
+    let clamp = |i: i64, size: u32| i.clamp(0, i64::from(size) - 1) as u32;
+    let resolve = |i, j| (clamp(i, width), clamp(j, height));
+    Some(separable_with(
+        img,
+        x,
+        y,
+        method.radius(),
+        kernel,
+        resolve,
+        alpha,
+    ))
+}
+
+/// Check if the floating point coordinates are inside of the image,
+/// between its first and last pixels included.
+pub(crate) fn inside(width: u32, height: u32, x: f32, y: f32) -> bool {
+    let (right, bottom) = (width as f32 - 1.0, height as f32 - 1.0);
+    x >= 0.0 && x <= right && y >= 0.0 && y <= bottom
+}
+
+/// Separable interpolation of a pixel with floating point coordinates.
+///
+/// The pixels at a distance smaller than `radius` in each direction are weighted by `kernel`,
+/// and `resolve` gives the coordinates of the pixel to use for these neighbors
+/// (possibly outside of the image).
+/// The weights are normalized to sum to 1.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+pub fn separable_with<V, P, Container, O, K, R>(
+    img: &ImageBuffer<P, Container>,
+    x: f32,
+    y: f32,
+    radius: i64,
+    kernel: K,
+    resolve: R,
+    alpha: Alpha,
+) -> O
+where
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    P: Pixel + 'static,
+    Container: Deref<Target = [P::Subpixel]>,
+    P: CanLinearInterpolate<V, O>,
+    K: Fn(f32) -> f32,
+    R: Fn(i64, i64) -> (u32, u32),
+{
+    let u = x.floor();
+    let v = y.floor();
+    let (u_0, v_0) = (u as i64, v as i64);
+    let (a, b) = (x - u, y - v);
+    let taps = 1 - radius..=radius;
+    let norm_x: f32 = taps.clone().map(|k| kernel(a - k as f32)).sum();
+    let norm_y: f32 = taps.clone().map(|k| kernel(b - k as f32)).sum();
+    let mut interp = None;
+    for l in taps.clone() {
+        let w_y = kernel(b - l as f32) / norm_y;
+        for k in taps.clone() {
+            let w_x = kernel(a - k as f32) / norm_x;
+            let (i, j) = resolve(u_0 + k, v_0 + l);
+            let term = Mul::<f32>::mul(w_x, w_y) * to_vector(*img.get_pixel(i, j), alpha);
+            interp = Some(match interp {
+                None => term,
+                Some(acc) => acc + term,
+            });
+        }
+    }
+    from_vector::<V, P, O>(interp.expect("the radius must be at least 1"), alpha)
+}
+
+/// Vector of the pixel, with its colors premultiplied by its alpha or not.
+pub(crate) fn to_vector<V, P, O>(pixel: P, alpha: Alpha) -> V
+where
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    P: CanLinearInterpolate<V, O>,
+{
+    match alpha {
+        Alpha::Premultiplied => pixel.into_vector(),
+        Alpha::Straight => pixel.into_straight_vector(),
+    }
 }
 
+/// Pixel of the vector, inverse of [`to_vector`].
+pub(crate) fn from_vector<V, P, O>(v: V, alpha: Alpha) -> O
+where
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    P: CanLinearInterpolate<V, O>,
+{
+    match alpha {
+        Alpha::Premultiplied => P::from_vector(v),
+        Alpha::Straight => P::from_straight_vector(v),
+    }
+}
+
+/// Composite the color over the pixel, with the given opacity in [0, 1].
+pub(crate) fn blend<V, P>(pixel: &mut P, color: P, opacity: f32)
+where
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    P: CanLinearInterpolate<V, P> + Copy,
+{
+    let below: V = to_vector(*pixel, Alpha::Premultiplied);
+    let above: V = to_vector(color, Alpha::Premultiplied);
+    let mixed = (1.0 - opacity) * below + opacity * above;
+    *pixel = from_vector::<V, P, P>(mixed, Alpha::Premultiplied);
+}
+
+// N-dimensional vector helper ################################################
+// That's to avoid a dependency on a heavy package such as nalgebra
+
+/// Column vector of N `f32` values, the interpolation vector of pixels with N channels.
+#[derive(Clone, Copy)]
+pub struct VecN<const N: usize>([f32; N]);
+
 // Add two vectors
-impl Add for Vec3 {
+impl<const N: usize> Add for VecN<N> {
     type Output = Self;
-    fn add(self, rhs: Self) -> Self::Output {
-        Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
+    fn add(mut self, rhs: Self) -> Self::Output {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a += b;
         }
+        self
     }
 }
 
 // Scalar multiplication
-impl Mul<Vec3> for f32 {
-    type Output = Vec3;
-    fn mul(self, rhs: Vec3) -> Self::Output {
-        Vec3 {
-            x: self * rhs.x,
-            y: self * rhs.y,
-            z: self * rhs.z,
-        }
+impl<const N: usize> Mul<VecN<N>> for f32 {
+    type Output = VecN<N>;
+    fn mul(self, rhs: VecN<N>) -> Self::Output {
+        VecN(rhs.0.map(|a| self * a))
     }
 }
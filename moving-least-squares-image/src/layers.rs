@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warp of several aligned layers, such as color, depth, alpha and segmentation,
+//! with a single evaluation of the mapping per pixel.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::mapping::map_points;
+use crate::{reverse_mapping, DeformMethod, Image, Mapping, Sampler};
+use image::{ImageBuffer, Pixel};
+use std::marker::PhantomData;
+use std::ops::{Add, Deref, Mul};
+
+/// Layer warped by [`warp_layers`], whatever its pixel type.
+pub trait WarpLayer {
+    /// Size of the source layer, and of the warped layer.
+    fn dimensions(&self) -> (u32, u32);
+    /// Sample the source layer at the given locations, into the row `y` of the warped layer.
+    fn sample_row(&mut self, y: u32, sources: &[(f32, f32)]);
+}
+
+/// Source layer with its own sampler, such as nearest neighbor for segmentation labels,
+/// and its warped layer.
+pub struct Layer<'a, P: Pixel, C, V> {
+    img_src: &'a ImageBuffer<P, C>,
+    sampler: Sampler<P>,
+    warped: Image<P>,
+    vector: PhantomData<V>,
+}
+
+impl<'a, P, C, V> Layer<'a, P, C, V>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    /// Layer to warp from the source image, sampled with the given sampler.
+    pub fn new(img_src: &'a ImageBuffer<P, C>, sampler: Sampler<P>) -> Self {
+        let (width, height) = img_src.dimensions();
+        Self {
+            img_src,
+            sampler,
+            warped: ImageBuffer::new(width, height),
+            vector: PhantomData,
+        }
+    }
+
+    /// Warped layer.
+    pub fn warped(&self) -> &Image<P> {
+        &self.warped
+    }
+
+    /// Take the warped layer.
+    pub fn into_warped(self) -> Image<P> {
+        self.warped
+    }
+}
+
+impl<P, C, V> WarpLayer for Layer<'_, P, C, V>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    fn dimensions(&self) -> (u32, u32) {
+        self.img_src.dimensions()
+    }
+
+    fn sample_row(&mut self, y: u32, sources: &[(f32, f32)]) {
+        for (x, &(x2, y2)) in (0..).zip(sources) {
+            let pixel = self.sampler.sample(self.img_src, x2, y2);
+            self.warped.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+/// Warp all the layers by the given mapping, evaluated once per pixel,
+/// in parallel if the `rayon` feature is enabled.
+///
+/// The layers are warped row by row, so the mapping of a single row is kept in memory.
+/// Panics if the layers do not have the same dimensions.
+pub fn warp_layers<M: Mapping + ?Sized>(mapping: &M, layers: &mut [&mut dyn WarpLayer]) {
+    let (width, height) = match layers.first() {
+        Some(layer) => layer.dimensions(),
+        None => return,
+    };
+    assert!(
+        layers
+            .iter()
+            .all(|layer| layer.dimensions() == (width, height)),
+        "The layers must have the same dimensions"
+    );
+    for y in 0..height {
+        let positions: Vec<(f32, f32)> = (0..width).map(|x| (x as f32, y as f32)).collect();
+        let sources = map_points(&positions, mapping);
+        for layer in layers.iter_mut() {
+            layer.sample_row(y, &sources);
+        }
+    }
+}
+
+/// Same as [`warp_layers`], with the MLS deformation of [`reverse_dense`](crate::reverse_dense).
+pub fn reverse_dense_layers(
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    layers: &mut [&mut dyn WarpLayer],
+) {
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_layers(&mapping, layers)
+}
@@ -1,51 +1,254 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! Functions to compute warped images with an MLS algorithm.
+//! They work with any pixel type that can be linearly interpolated,
+//! such as `Rgb<u8>`, `Rgba<u8>`, `Luma<u8>`, `LumaA<u8>`, their 16 bits versions,
+//! or their `f32` versions for high dynamic range images.
+//! Pixels warped from outside of the source image are zero,
+//! which is black for RGB images and fully transparent for RGBA images.
 //! Two warping functions are provided:
 //!  - a dense warp where the deformation is computed for each pixel,
 //!  - a sparse warp where its only computed on a sparse grid,
 //!    and the other pixels locations are interpolated.
+//!
+//! Every pixel is computed independently, with the same operations in the same order,
+//! so the parallel warps of the `rayon` feature are bit-identical to serial ones,
+//! whatever the number of threads.
 
 #![warn(missing_docs)]
 
-use image::{Rgb, RgbImage};
+use image::{ImageBuffer, Pixel};
+use interpolation::CanLinearInterpolate;
+use moving_least_squares as mls;
+use num_traits::Zero;
+use std::cell::RefCell;
+use std::ops::{Add, Deref, DerefMut, Mul};
 
-mod interpolation;
+pub use mls::DeformMethod;
+/// Version of rayon used by the parallel warps, to build thread pools for them.
+#[cfg(feature = "rayon")]
+pub use rayon;
+
+mod adaptive;
+mod animation;
+mod annotations;
+mod bspline;
+mod canvas;
+mod draw;
+mod dynamic;
+mod estimate;
+mod ewa;
+mod ffd;
+mod field;
+mod fixed;
+mod forward;
+mod gpu;
+mod gray;
+mod heatmap;
+mod incremental;
+pub mod interpolation;
+mod layers;
+mod mapping;
+mod masked;
+mod mesh;
+mod monitor;
+mod morph;
+mod mosaic;
+mod pattern;
+mod sampling;
+mod sparse_grid;
+mod srgb;
+mod strength;
+#[cfg(feature = "rayon")]
+mod threads;
+mod tiled;
+mod tps;
+mod tracks;
+mod warper;
+
+pub use adaptive::{reverse_sparse_adaptive, warp_adaptive};
+pub use animation::{animate, keyframe_controls, Easing};
+pub use annotations::{warp_bbox, warp_points, warp_polygon, Direction};
+pub use bspline::bspline_prefilter;
+pub use canvas::Canvas;
+pub use draw::{draw_arrow, draw_controls, draw_point, draw_segment, ControlsStyle};
+pub use dynamic::warp_dynamic;
+pub use estimate::{auto_subresolution_factor, reverse_sparse_auto, sparse_error, SparseError};
+pub use ewa::reverse_dense_ewa;
+pub use ffd::{reverse_ffd, BSplineFfd};
+pub use field::{
+    opencv_fixed_point_maps, opencv_maps, remap, reverse_dense_field, reverse_sparse_field, Field,
+};
+pub use fixed::{reverse_dense_fixed, warp_dense_fixed};
+pub use forward::forward_dense;
+#[cfg(feature = "wgpu")]
+pub use gpu::GpuWarper;
+pub use gpu::{gpu_controls, gpu_params, textured_mesh, TexturedMesh, MLS_WGSL};
+pub use gray::{reverse_dense_gray, reverse_sparse_gray};
+pub use heatmap::{determinant_heatmap, jacobian_determinant};
+pub use incremental::{dirty_region, rewarp_moved_handle, rewarp_region, Region};
+pub use layers::{reverse_dense_layers, warp_layers, Layer, WarpLayer};
+pub use mapping::{
+    warp_dense, warp_dense_into, warp_dense_on, warp_sparse, warp_sparse_on, Mapping,
+};
+pub use masked::reverse_dense_masked;
+pub use mesh::{reverse_mesh, reverse_mesh_with};
+pub use monitor::{
+    reverse_dense_monitored, reverse_sparse_monitored, warp_dense_monitored, Cancelled, Monitor,
+};
+pub use morph::morph;
+pub use mosaic::{reverse_mosaic, Blending, Tile};
+pub use pattern::{overlay, reverse_pattern, Pattern};
+pub use sampling::{Alpha, Border, Interpolation, Sampler};
+pub use sparse_grid::SparseWarpGrid;
+pub use srgb::{reverse_dense_linear_light, reverse_sparse_linear_light, LinearLight};
+pub use strength::{reverse_dense_strength, Strength};
+#[cfg(feature = "rayon")]
+pub use threads::with_max_threads;
+pub use tiled::reverse_tiled;
+pub use tps::{tps_reverse_dense, tps_reverse_dense_with, tps_reverse_sparse};
+pub use tracks::{smooth_track, warp_tracked, Track};
+pub use warper::Warper;
+
+/// Image with the same pixel type than the warped image, returned by the warp functions.
+pub type Image<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+/// Behaves like `ImageBuffer::from_fn` but will be parallelized if the `rayon` feature is enabled
+#[cfg(not(feature = "rayon"))]
+fn image_from_fn<P, F>(width: u32, height: u32, f: F) -> Image<P>
+where
+    P: Pixel + 'static,
+    F: Fn(u32, u32) -> P,
+{
+    ImageBuffer::from_fn(width, height, f)
+}
+
+/// Behaves like `ImageBuffer::from_fn` but will be parallelized if the `rayon` feature is enabled
+#[cfg(feature = "rayon")]
+fn image_from_fn<P, F>(width: u32, height: u32, f: F) -> Image<P>
+where
+    P: Pixel + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    F: Fn(u32, u32) -> P + Send + Sync,
+{
+    let mut buf = ImageBuffer::new(width, height);
+    fill_from_fn(&mut buf, f);
+    buf
+}
 
-/// Behaves like `RgbImage::from_fn` but will be parallelized if the `rayon` feature is enabled
+/// Set every pixel of the image to `f(x, y)`,
+/// in parallel if the `rayon` feature is enabled.
 #[cfg(not(feature = "rayon"))]
-fn rgb_image_from_fn<F>(width: u32, height: u32, f: F) -> RgbImage
+fn fill_from_fn<P, O, F>(img: &mut ImageBuffer<P, O>, f: F)
 where
-    F: Fn(u32, u32) -> Rgb<u8>,
+    P: Pixel + 'static,
+    O: DerefMut<Target = [P::Subpixel]>,
+    F: Fn(u32, u32) -> P,
 {
-    RgbImage::from_fn(width, height, f)
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        *pixel = f(x, y);
+    }
 }
 
-/// Behaves like `RgbImage::from_fn` but will be parallelized if the `rayon` feature is enabled
+/// Set every pixel of the image to `f(x, y)`,
+/// in parallel if the `rayon` feature is enabled.
 #[cfg(feature = "rayon")]
-fn rgb_image_from_fn<F>(width: u32, height: u32, f: F) -> RgbImage
+fn fill_from_fn<P, O, F>(img: &mut ImageBuffer<P, O>, f: F)
 where
-    F: Fn(u32, u32) -> Rgb<u8> + Send + Sync,
+    P: Pixel + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    O: DerefMut<Target = [P::Subpixel]>,
+    F: Fn(u32, u32) -> P + Send + Sync,
+{
+    // one task per row instead of per pixel, for less scheduling overhead
+    // and contiguous writes within each task
+    let channels = P::CHANNEL_COUNT as usize;
+    fill_rows_from_fn(img, |y, row| {
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            pixel.copy_from_slice(f(x as u32, y).channels());
+        }
+    });
+}
+
+/// Set every row of the image with `f(y, row)`, the row being the channels of its pixels,
+/// in parallel if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+fn fill_rows_from_fn<P, O, F>(img: &mut ImageBuffer<P, O>, f: F)
+where
+    P: Pixel + 'static,
+    O: DerefMut<Target = [P::Subpixel]>,
+    F: Fn(u32, &mut [P::Subpixel]),
+{
+    let row_len = (img.width() * P::CHANNEL_COUNT as u32) as usize;
+    if row_len == 0 {
+        return;
+    }
+    for (y, row) in img.chunks_exact_mut(row_len).enumerate() {
+        f(y as u32, row);
+    }
+}
+
+/// Set every row of the image with `f(y, row)`, the row being the channels of its pixels,
+/// in parallel if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+fn fill_rows_from_fn<P, O, F>(img: &mut ImageBuffer<P, O>, f: F)
+where
+    P: Pixel + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    O: DerefMut<Target = [P::Subpixel]>,
+    F: Fn(u32, &mut [P::Subpixel]) + Send + Sync,
 {
     use rayon::iter::{IndexedParallelIterator, ParallelIterator};
     use rayon::slice::ParallelSliceMut;
 
-    let mut buf = RgbImage::new(width, height);
-
-    buf.par_chunks_exact_mut(3)
+    let row_len = (img.width() * P::CHANNEL_COUNT as u32) as usize;
+    if row_len == 0 {
+        return;
+    }
+    img.par_chunks_exact_mut(row_len)
         .enumerate()
-        .map(|(idx, pixel)| (idx as u32 % width, idx as u32 / width, pixel))
-        .for_each(|(x, y, pixel)| {
-            pixel.copy_from_slice(&f(x, y).0);
-        });
+        .for_each(|(y, row)| f(y as u32, row));
+}
 
-    buf
+/// Compute the images `f(0)` to `f(nb_frames - 1)`,
+/// in parallel if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+fn frames_from_fn<P, F>(nb_frames: usize, f: F) -> Vec<Image<P>>
+where
+    P: Pixel + 'static,
+    F: Fn(usize) -> Image<P>,
+{
+    (0..nb_frames).map(f).collect()
+}
+
+/// Compute the images `f(0)` to `f(nb_frames - 1)`,
+/// in parallel if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+fn frames_from_fn<P, F>(nb_frames: usize, f: F) -> Vec<Image<P>>
+where
+    P: Pixel + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    F: Fn(usize) -> Image<P> + Send + Sync,
+{
+    use rayon::iter::{IntoParallelIterator, ParallelIterator};
+    (0..nb_frames).into_par_iter().map(f).collect()
+}
+
+/// Pixel with all channels at zero, such as black for RGB images.
+fn zero_pixel<P: Pixel>() -> P {
+    let zeros = vec![P::Subpixel::zero(); P::CHANNEL_COUNT as usize];
+    *P::from_slice(&zeros)
 }
 
 // Dense interpolation #########################################################
 
+thread_local! {
+    /// Buffers reused for the deformation of every pixel of the current thread.
+    static SCRATCH: RefCell<mls::DeformScratch<f32>> = RefCell::new(mls::DeformScratch::new());
+}
+
 /// Compute the warped image with an MLS algorithm.
-/// The last argument is the MLS version you choose.
+/// The last argument is the MLS method you choose.
 ///
 /// The new image is back projected as if the source and destination
 /// control points were reversed.
@@ -53,26 +256,132 @@ where
 /// The warp is computed densely, for every pixel.
 ///
 /// Pixels interpolation is done with bilinear interpolation.
-#[allow(clippy::type_complexity)]
-pub fn reverse_dense(
-    img_src: &RgbImage,
+pub fn reverse_dense<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
     controls_src: &[(f32, f32)],
     controls_dst: &[(f32, f32)],
-    deform_function: fn(&[(f32, f32)], &[(f32, f32)], (f32, f32)) -> (f32, f32),
-) -> RgbImage {
-    let (width, height) = img_src.dimensions();
-    let color_outside = Rgb([0, 0, 0]);
-    rgb_image_from_fn(width, height, |x, y| {
-        let (x2, y2) = deform_function(controls_dst, controls_src, (x as f32, y as f32));
-        // nearest_neighbor(img_src, x2, y2).unwrap_or(color_outside)
-        interpolation::bilinear(img_src, x2, y2).unwrap_or(color_outside)
-    })
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let sampler = Sampler::default();
+    reverse_dense_with(img_src, controls_src, controls_dst, method, &sampler)
+}
+
+/// Same as [`reverse_dense`], but with the given options to sample the source image,
+/// such as the color outside of it.
+pub fn reverse_dense_with<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_dense(img_src, &mapping, sampler)
+}
+
+/// Same as [`reverse_dense`], but writes the warped image into the given buffer,
+/// to reuse its allocation.
+///
+/// The warped image has the dimensions of the buffer.
+pub fn reverse_dense_into<P, C, O, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    warped: &mut ImageBuffer<P, O>,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    O: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_dense_into(img_src, &mapping, &Sampler::default(), warped)
+}
+
+/// Same as [`reverse_dense_with`], but the warped image covers the given canvas
+/// instead of the frame of the source image.
+pub fn reverse_dense_on<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    canvas: &Canvas,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_dense_on(img_src, &mapping, sampler, canvas)
+}
+
+/// Back projection of the pixels of the warped image with the MLS deformation,
+/// reusing the buffers of the current thread.
+fn reverse_mapping<'a>(
+    controls_src: &'a [(f32, f32)],
+    controls_dst: &'a [(f32, f32)],
+    method: DeformMethod,
+) -> impl Fn((f32, f32)) -> (f32, f32) + Sync + 'a {
+    move |point| {
+        SCRATCH.with(|scratch| {
+            let mut scratch = scratch.borrow_mut();
+            scratch.deform(controls_dst, controls_src, point, method)
+        })
+    }
+}
+
+/// Compute the image warped by a chain of MLS deformations,
+/// each one given by its source and destination control points and its method.
+///
+/// This is equivalent to calling [`reverse_dense`] for each deformation in turn,
+/// but without resampling the intermediate images.
+pub fn reverse_dense_composed<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    deformations: &[mls::Deformation<f32>],
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    // Pixels are back projected, so the deformations are applied
+    // in reverse order, with source and destination control points reversed.
+    let reversed: Vec<mls::Deformation<f32>> = deformations
+        .iter()
+        .rev()
+        .map(|&(controls_src, controls_dst, method)| (controls_dst, controls_src, method))
+        .collect();
+    let mapping = |point| mls::deform_composed(&reversed, point);
+    warp_dense(img_src, &mapping, &Sampler::default())
 }
 
 // Sparse interpolation ########################################################
 
 /// Compute the warped image with an MLS algorithm.
-/// The last argument is the MLS version you choose.
+/// The last argument is the MLS method you choose.
 ///
 /// The new image is back projected as if the source and destination
 /// control points were reversed.
@@ -86,60 +395,224 @@ pub fn reverse_dense(
 /// with a minimal impact on the produced image.
 ///
 /// Pixels interpolation is done with bilinear interpolation.
-#[allow(clippy::type_complexity)]
-pub fn reverse_sparse(
-    img_src: &RgbImage,
+pub fn reverse_sparse<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
     controls_src: &[(f32, f32)],
     controls_dst: &[(f32, f32)],
     subresolution_factor: u32,
-    deform_function: fn(&[(f32, f32)], &[(f32, f32)], (f32, f32)) -> (f32, f32),
-) -> RgbImage {
-    let (width, height) = img_src.dimensions();
-    let color_outside = Rgb([0, 0, 0]);
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let sampler = Sampler::default();
+    reverse_sparse_with(
+        img_src,
+        controls_src,
+        controls_dst,
+        subresolution_factor,
+        method,
+        &sampler,
+    )
+}
 
-    // size of the subresolution matrix for which we actually compute the MLS reprojections
-    let sub_width = (width - 1) / subresolution_factor + 2;
-    let sub_height = (height - 1) / subresolution_factor + 2;
+/// Same as [`reverse_sparse`], but with the given options to sample the source image,
+/// such as the color outside of it.
+pub fn reverse_sparse_with<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = img_src.dimensions();
 
     // the anchors are the MLS reprojection of the subresolution matrix of points
-    let anchors: Vec<Vec<(f32, f32)>> = (0..sub_height)
-        .map(|v| {
-            let y = (v * subresolution_factor) as f32;
-            (0..sub_width)
-                .map(|u| {
-                    let x = (u * subresolution_factor) as f32;
-                    deform_function(controls_dst, controls_src, (x, y))
-                })
-                .collect()
-        })
-        .collect();
+    let anchors: Vec<(f32, f32)> = mls::deform_grid(
+        controls_dst,
+        controls_src,
+        width,
+        height,
+        subresolution_factor,
+        method,
+    );
+    let mut warped = ImageBuffer::new(width, height);
+    fill_from_anchors(
+        img_src,
+        &anchors,
+        subresolution_factor,
+        sampler,
+        &mut warped,
+    );
+    warped
+}
+
+/// Same as [`reverse_sparse`], but writes the warped image into the given buffer,
+/// to reuse its allocation.
+///
+/// The warped image has the dimensions of the buffer.
+pub fn reverse_sparse_into<P, C, O, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+    warped: &mut ImageBuffer<P, O>,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    O: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = warped.dimensions();
+    let anchors: Vec<(f32, f32)> = mls::deform_grid(
+        controls_dst,
+        controls_src,
+        width,
+        height,
+        subresolution_factor,
+        method,
+    );
+    let sampler = Sampler::default();
+    fill_from_anchors(img_src, &anchors, subresolution_factor, &sampler, warped);
+}
+
+/// Same as [`reverse_sparse_with`], but the warped image covers the given canvas
+/// instead of the frame of the source image.
+pub fn reverse_sparse_on<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    canvas: &Canvas,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_sparse_on(img_src, &mapping, subresolution_factor, sampler, canvas)
+}
+
+/// Fill the warped image from the reprojections of the sparse grid of anchors,
+/// by warping bilinearly the pixels inside each bloc of the grid.
+fn fill_from_anchors<P, C, O, V>(
+    img_src: &ImageBuffer<P, C>,
+    anchors: &[(f32, f32)],
+    subresolution_factor: u32,
+    sampler: &Sampler<P>,
+    warped: &mut ImageBuffer<P, O>,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    O: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    // size of the subresolution matrix for which we actually compute the reprojections
+    let sub_width = ((warped.width().max(1) - 1) / subresolution_factor + 2) as usize;
 
     // apply bilinear warp to compute the full warp
-    rgb_image_from_fn(width, height, |x, y| {
-        let sub_left = x / subresolution_factor;
-        let sub_top = y / subresolution_factor;
-        let top_left_corner = (
-            subresolution_factor * sub_left,
-            subresolution_factor * sub_top,
-        );
-        let bot_right_corner = (
-            top_left_corner.0 + subresolution_factor,
-            top_left_corner.1 + subresolution_factor,
-        );
-        let sub_left = sub_left as usize;
-        let sub_top = sub_top as usize;
-        // TODO: should try to avoid retrieving bloc corners for each pixel
-        let corners_dst = [
-            anchors[sub_top][sub_left],
-            anchors[sub_top][sub_left + 1],
-            anchors[sub_top + 1][sub_left],
-            anchors[sub_top + 1][sub_left + 1],
-        ];
-        let (x2, y2) = bilinear_warp(top_left_corner, bot_right_corner, corners_dst, (x, y));
-        interpolation::bilinear(img_src, x2, y2).unwrap_or(color_outside)
+    fill_rows_from_fn(warped, |y, row| {
+        fill_row_from_anchors(
+            img_src,
+            anchors,
+            sub_width,
+            subresolution_factor,
+            sampler,
+            y,
+            row,
+        )
     })
 }
 
+/// Fill the row y of the warped image, given by the channels of its pixels,
+/// bloc by bloc, retrieving the corners of a bloc once for all its pixels in the row.
+fn fill_row_from_anchors<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    anchors: &[(f32, f32)],
+    sub_width: usize,
+    subresolution_factor: u32,
+    sampler: &Sampler<P>,
+    y: u32,
+    row: &mut [P::Subpixel],
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let channels = P::CHANNEL_COUNT as usize;
+    let bloc_len = channels * subresolution_factor as usize;
+    for (sub_left, bloc) in row.chunks_mut(bloc_len).enumerate() {
+        let edges = bloc_edges(anchors, sub_width, subresolution_factor, (sub_left, y));
+        for (dx, pixel) in bloc.chunks_exact_mut(channels).enumerate() {
+            let (x2, y2) = lerp_edges(edges, subresolution_factor, dx as u32);
+            pixel.copy_from_slice(sampler.sample(img_src, x2, y2).channels());
+        }
+    }
+}
+
+/// Location of the pixel interpolated from the reprojections of the corners of its bloc.
+fn anchors_warp(
+    anchors: &[(f32, f32)],
+    sub_width: usize,
+    subresolution_factor: u32,
+    (x, y): (u32, u32),
+) -> (f32, f32) {
+    let sub_left = (x / subresolution_factor) as usize;
+    let edges = bloc_edges(anchors, sub_width, subresolution_factor, (sub_left, y));
+    lerp_edges(edges, subresolution_factor, x % subresolution_factor)
+}
+
+/// Reprojections of the left and right edges of a bloc at the row y,
+/// interpolated between the reprojections of its corners.
+fn bloc_edges(
+    anchors: &[(f32, f32)],
+    sub_width: usize,
+    subresolution_factor: u32,
+    (sub_left, y): (usize, u32),
+) -> [(f32, f32); 2] {
+    let sub_top = (y / subresolution_factor) as usize;
+    let top = sub_top * sub_width + sub_left;
+    let bot = top + sub_width;
+    let t = (y % subresolution_factor) as f32 / subresolution_factor as f32;
+    [
+        lerp(anchors[top], anchors[bot], t),
+        lerp(anchors[top + 1], anchors[bot + 1], t),
+    ]
+}
+
+/// Reprojection of the pixel at dx from the left edge of its bloc.
+fn lerp_edges([left, right]: [(f32, f32); 2], subresolution_factor: u32, dx: u32) -> (f32, f32) {
+    lerp(left, right, dx as f32 / subresolution_factor as f32)
+}
+
+/// Linear interpolation between a and b.
+fn lerp(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    ((1.0 - t) * a.0 + t * b.0, (1.0 - t) * a.1 + t * b.1)
+}
+
 /// Perform bilinear warping of the pixel.
 /// WARNING: make sure it is within the bloc corners.
 fn bilinear_warp(
@@ -175,3 +648,55 @@ fn bilinear_warp(
 
     (x, y)
 }
+
+// Blended deformation #########################################################
+
+/// Same as [`reverse_dense`], but with the destination control points
+/// only a fraction `t` (between 0 and 1) of the way from the source ones.
+///
+/// Rendering it for increasing values of `t` eases into the full deformation,
+/// with the source image at `t = 0` and the result of [`reverse_dense`] at `t = 1`.
+pub fn reverse_dense_blend<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    t: f32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let controls_t = mls::blend_controls(controls_src, controls_dst, t);
+    reverse_dense(img_src, controls_src, &controls_t, method)
+}
+
+/// Same as [`reverse_sparse`], but with the destination control points
+/// only a fraction `t` (between 0 and 1) of the way from the source ones.
+pub fn reverse_sparse_blend<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    t: f32,
+    subresolution_factor: u32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let controls_t = mls::blend_controls(controls_src, controls_dst, t);
+    reverse_sparse(
+        img_src,
+        controls_src,
+        &controls_t,
+        subresolution_factor,
+        method,
+    )
+}
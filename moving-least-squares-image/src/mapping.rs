@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warps of images by any mapping of the output pixels to the source image,
+//! not only the MLS deformations of this crate.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{fill_from_anchors, fill_from_fn, image_from_fn, Canvas, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use std::ops::{Add, Deref, DerefMut, Mul};
+
+/// Mapping of the pixels of the warped image to their location in the source image.
+///
+/// It is implemented for closures, so a closure capturing a precomputed deformer,
+/// or custom kernel parameters, can be used as a mapping.
+pub trait Mapping: Sync {
+    /// Location in the source image of the given pixel of the warped image.
+    fn source(&self, point: (f32, f32)) -> (f32, f32);
+}
+
+impl<F> Mapping for F
+where
+    F: Fn((f32, f32)) -> (f32, f32) + Sync,
+{
+    fn source(&self, point: (f32, f32)) -> (f32, f32) {
+        self(point)
+    }
+}
+
+/// Compute the image warped by the given mapping.
+///
+/// The mapping is evaluated densely, for every pixel.
+pub fn warp_dense<P, C, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    warp_dense_on(img_src, mapping, sampler, &Canvas::of(img_src))
+}
+
+/// Same as [`warp_dense`], but the warped image covers the given canvas
+/// instead of the frame of the source image.
+pub fn warp_dense_on<P, C, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    sampler: &Sampler<P>,
+    canvas: &Canvas,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    image_from_fn(canvas.width, canvas.height, |x, y| {
+        let (x2, y2) = mapping.source(canvas.position(x, y));
+        sampler.sample(img_src, x2, y2)
+    })
+}
+
+/// Same as [`warp_dense`], but writes the warped image into the given buffer,
+/// to reuse its allocation.
+///
+/// The warped image has the dimensions of the buffer.
+pub fn warp_dense_into<P, C, O, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    sampler: &Sampler<P>,
+    warped: &mut ImageBuffer<P, O>,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    O: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    fill_from_fn(warped, |x, y| {
+        let (x2, y2) = mapping.source((x as f32, y as f32));
+        sampler.sample(img_src, x2, y2)
+    })
+}
+
+/// Compute the image warped by the given mapping.
+///
+/// The mapping is only evaluated on a sparse grid of pixels,
+/// and the other pixels locations are interpolated bilinearly,
+/// as in [`reverse_sparse`](crate::reverse_sparse).
+pub fn warp_sparse<P, C, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    subresolution_factor: u32,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    let canvas = Canvas::of(img_src);
+    warp_sparse_on(img_src, mapping, subresolution_factor, sampler, &canvas)
+}
+
+/// Same as [`warp_sparse`], but the warped image covers the given canvas
+/// instead of the frame of the source image.
+pub fn warp_sparse_on<P, C, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    subresolution_factor: u32,
+    sampler: &Sampler<P>,
+    canvas: &Canvas,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    assert!(
+        subresolution_factor > 0,
+        "The subresolution factor must be strictly positive"
+    );
+    let positions = grid_positions(canvas, subresolution_factor);
+    let anchors = map_points(&positions, mapping);
+    let mut warped = ImageBuffer::new(canvas.width, canvas.height);
+    fill_from_anchors(
+        img_src,
+        &anchors,
+        subresolution_factor,
+        sampler,
+        &mut warped,
+    );
+    warped
+}
+
+/// Positions of the points of the sparse grid covering the canvas, row by row,
+/// in the same layout than [`mls::deform_grid`](moving_least_squares::deform_grid).
+pub(crate) fn grid_positions(canvas: &Canvas, step: u32) -> Vec<(f32, f32)> {
+    let nb_columns = (canvas.width.max(1) - 1) / step + 2;
+    let nb_rows = (canvas.height.max(1) - 1) / step + 2;
+    (0..nb_rows)
+        .flat_map(|row| (0..nb_columns).map(move |column| (column, row)))
+        .map(|(column, row)| canvas.position(column * step, row * step))
+        .collect()
+}
+
+/// Map all points, in parallel if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map_points<M: Mapping + ?Sized>(
+    points: &[(f32, f32)],
+    mapping: &M,
+) -> Vec<(f32, f32)> {
+    points.iter().map(|&point| mapping.source(point)).collect()
+}
+
+/// Map all points, in parallel if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+pub(crate) fn map_points<M: Mapping + ?Sized>(
+    points: &[(f32, f32)],
+    mapping: &M,
+) -> Vec<(f32, f32)> {
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+    points
+        .par_iter()
+        .map(|&point| mapping.source(point))
+        .collect()
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warps restricted to a masked region of the image,
+//! with the other pixels passing through untouched.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use crate::{image_from_fn, reverse_mapping, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Luma, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// Same as [`reverse_dense`](crate::reverse_dense), but only inside of the mask,
+/// at 255 where the image is warped and 0 where it is kept.
+///
+/// The pixels outside of the mask are copied from the source image, and are identical.
+/// Inside of the mask, the warped image is blended with the source image
+/// by the mask value, with its border feathered over `feather` pixels inward,
+/// so that binary masks do not leave visible seams.
+///
+/// Panics if the mask does not have the dimensions of the image.
+pub fn reverse_dense_masked<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    mask: &Image<Luma<u8>>,
+    feather: u32,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    assert_eq!(
+        mask.dimensions(),
+        img_src.dimensions(),
+        "The mask must have the dimensions of the image"
+    );
+    let (width, height) = img_src.dimensions();
+    let weights = feathered(mask, feather);
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    let sampler = Sampler::default();
+    image_from_fn(width, height, |x, y| {
+        let weight = weights[(y * width + x) as usize];
+        let original = *img_src.get_pixel(x, y);
+        if weight <= 0.0 {
+            return original;
+        }
+        let (x2, y2) = mapping((x as f32, y as f32));
+        let warped = sampler.sample(img_src, x2, y2);
+        if weight >= 1.0 {
+            return warped;
+        }
+        let mut blended = original;
+        interpolation::blend(&mut blended, warped, weight);
+        blended
+    })
+}
+
+/// Weights in [0, 1] of the warped image, row by row,
+/// with the mask blurred by a box of the given radius, but never above the mask,
+/// so that the feathering stays inside of the mask.
+fn feathered(mask: &Image<Luma<u8>>, feather: u32) -> Vec<f32> {
+    let (width, height) = (mask.width() as usize, mask.height() as usize);
+    let values: Vec<f32> = mask.pixels().map(|p| f32::from(p.0[0]) / 255.0).collect();
+    if feather == 0 {
+        return values;
+    }
+    let radius = feather as usize;
+    let mut blurred = values.clone();
+    // Horizontal then vertical box blurs, truncated at the image borders.
+    for y in 0..height {
+        let row: Vec<f32> = (0..width).map(|x| blurred[y * width + x]).collect();
+        for (x, value) in box_blur(&row, radius).into_iter().enumerate() {
+            blurred[y * width + x] = value;
+        }
+    }
+    for x in 0..width {
+        let column: Vec<f32> = (0..height).map(|y| blurred[y * width + x]).collect();
+        for (y, value) in box_blur(&column, radius).into_iter().enumerate() {
+            blurred[y * width + x] = value;
+        }
+    }
+    values.iter().zip(blurred).map(|(&v, b)| v.min(b)).collect()
+}
+
+/// Average of the values inside of a window of the given radius around each value.
+fn box_blur(values: &[f32], radius: usize) -> Vec<f32> {
+    let mut prefix = Vec::with_capacity(values.len() + 1);
+    prefix.push(0.0);
+    for v in values {
+        prefix.push(prefix[prefix.len() - 1] + v);
+    }
+    (0..values.len())
+        .map(|i| {
+            let first = i.saturating_sub(radius);
+            let last = (i + radius + 1).min(values.len());
+            (prefix[last] - prefix[first]) / (last - first) as f32
+        })
+        .collect()
+}
@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Piecewise affine warp on a Delaunay triangulation of the control points,
+//! as done by GPU pipelines and the piecewise affine warps of OpenCV.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{image_from_fn, reverse_mapping, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// Compute the warped image with an MLS algorithm only evaluated at the vertices
+/// of a triangle mesh, and the other pixels locations affinely interpolated in each triangle.
+///
+/// The mesh is the Delaunay triangulation of the destination control points
+/// inside of the image, and of points every `border_step` pixels on its border.
+/// Contrary to [`reverse_sparse`](crate::reverse_sparse), the deformation is exact
+/// at the control points whatever their layout,
+/// but it is only an approximation between them, coarser with fewer control points.
+pub fn reverse_mesh<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    border_step: u32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let sampler = Sampler::default();
+    reverse_mesh_with(
+        img_src,
+        controls_src,
+        controls_dst,
+        border_step,
+        method,
+        &sampler,
+    )
+}
+
+/// Same as [`reverse_mesh`], but with the given options to sample the source image,
+/// such as the color outside of it.
+pub fn reverse_mesh_with<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    border_step: u32,
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    assert!(border_step > 0, "The border step must be strictly positive");
+    let (width, height) = img_src.dimensions();
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+
+    // vertices of the mesh, in the warped image and in the source image
+    let vertices = mesh_vertices(width, height, controls_dst, border_step);
+    let sources: Vec<(f32, f32)> = vertices.iter().map(|&v| mapping(v)).collect();
+
+    // the source location of every pixel, interpolated in its triangle
+    let mut coordinates = vec![(f32::NAN, f32::NAN); (width * height) as usize];
+    for [a, b, c] in delaunay(&vertices) {
+        let triangle = [vertices[a], vertices[b], vertices[c]];
+        let triangle_src = [sources[a], sources[b], sources[c]];
+        rasterize(width, height, triangle, |x, y, weights| {
+            coordinates[(y * width + x) as usize] = barycentric(weights, triangle_src);
+        });
+    }
+    image_from_fn(width, height, |x, y| {
+        let (x2, y2) = match coordinates[(y * width + x) as usize] {
+            // Pixels missed by degenerate triangles are back projected exactly.
+            (x2, _) if x2.is_nan() => mapping((x as f32, y as f32)),
+            location => location,
+        };
+        sampler.sample(img_src, x2, y2)
+    })
+}
+
+/// Destination control points inside of the image, without duplicates,
+/// followed by points every `border_step` pixels on the border of the image.
+fn mesh_vertices(
+    width: u32,
+    height: u32,
+    controls_dst: &[(f32, f32)],
+    border_step: u32,
+) -> Vec<(f32, f32)> {
+    let (right, bottom) = ((width.max(1) - 1) as f32, (height.max(1) - 1) as f32);
+    let mut vertices: Vec<(f32, f32)> = Vec::new();
+    let mut push = |point: (f32, f32)| {
+        if !vertices.contains(&point) {
+            vertices.push(point);
+        }
+    };
+    for &(x, y) in controls_dst {
+        if x > 0.0 && x < right && y > 0.0 && y < bottom {
+            push((x, y));
+        }
+    }
+    let step = border_step as usize;
+    for x in (0..width.max(1) - 1).step_by(step) {
+        push((x as f32, 0.0));
+        push((x as f32, bottom));
+    }
+    for y in (0..height.max(1) - 1).step_by(step) {
+        push((0.0, y as f32));
+        push((right, y as f32));
+    }
+    push((right, bottom));
+    vertices
+}
+
+/// Call `f` with the barycentric coordinates of the pixels inside of the triangle,
+/// or on its edges, clipped to the image.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn rasterize<F: FnMut(u32, u32, [f32; 3])>(
+    width: u32,
+    height: u32,
+    [a, b, c]: [(f32, f32); 3],
+    mut f: F,
+) {
+    let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    if area == 0.0 {
+        return;
+    }
+    let x_min = a.0.min(b.0).min(c.0).ceil().max(0.0) as u32;
+    let y_min = a.1.min(b.1).min(c.1).ceil().max(0.0) as u32;
+    let x_max = (a.0.max(b.0).max(c.0).floor() as u32).min(width - 1);
+    let y_max = (a.1.max(b.1).max(c.1).floor() as u32).min(height - 1);
+    // Tolerance on the edges, so that no pixel falls between two triangles.
+    let epsilon = -1e-4;
+    for y in y_min..=y_max {
+        for x in x_min..=x_max {
+            let (px, py) = (x as f32, y as f32);
+            let wa = ((b.0 - px) * (c.1 - py) - (b.1 - py) * (c.0 - px)) / area;
+            let wb = ((c.0 - px) * (a.1 - py) - (c.1 - py) * (a.0 - px)) / area;
+            let wc = 1.0 - wa - wb;
+            if wa >= epsilon && wb >= epsilon && wc >= epsilon {
+                f(x, y, [wa, wb, wc]);
+            }
+        }
+    }
+}
+
+/// Point with the given barycentric coordinates in the triangle.
+fn barycentric([wa, wb, wc]: [f32; 3], [a, b, c]: [(f32, f32); 3]) -> (f32, f32) {
+    (
+        wa * a.0 + wb * b.0 + wc * c.0,
+        wa * a.1 + wb * b.1 + wc * c.1,
+    )
+}
+
+// Delaunay triangulation ######################################################
+
+/// Delaunay triangulation of the points, with the Bowyer-Watson algorithm.
+/// Returns the indices of the vertices of the triangles.
+fn delaunay(points: &[(f32, f32)]) -> Vec<[usize; 3]> {
+    let mut vertices: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(x, y)| (f64::from(x), f64::from(y)))
+        .collect();
+
+    // super triangle containing all the points
+    let (mut x_min, mut y_min) = (f64::INFINITY, f64::INFINITY);
+    let (mut x_max, mut y_max) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(x, y) in &vertices {
+        x_min = x_min.min(x);
+        y_min = y_min.min(y);
+        x_max = x_max.max(x);
+        y_max = y_max.max(y);
+    }
+    let size = (x_max - x_min).max(y_max - y_min).max(1.0) * 20.0;
+    let (cx, cy) = ((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+    let n = vertices.len();
+    vertices.push((cx - size, cy - size));
+    vertices.push((cx + size, cy - size));
+    vertices.push((cx, cy + size));
+
+    let mut triangles = vec![Triangle::new([n, n + 1, n + 2], &vertices)];
+    for i in 0..n {
+        let point = vertices[i];
+        let (bad, good): (Vec<Triangle>, Vec<Triangle>) = triangles
+            .into_iter()
+            .partition(|t| t.circumcircle_contains(point));
+        triangles = good;
+
+        // edges of the cavity, which are not shared by two bad triangles
+        let edges: Vec<(usize, usize)> = bad.iter().flat_map(|t| t.edges()).collect();
+        for &(e0, e1) in &edges {
+            let shared = edges
+                .iter()
+                .filter(|&&(f0, f1)| (f0, f1) == (e0, e1) || (f0, f1) == (e1, e0))
+                .count()
+                > 1;
+            if !shared {
+                triangles.push(Triangle::new([e0, e1, i], &vertices));
+            }
+        }
+    }
+    triangles
+        .into_iter()
+        .map(|t| t.vertices)
+        .filter(|v| v.iter().all(|&i| i < n))
+        .collect()
+}
+
+/// Triangle of the Delaunay triangulation, with its circumcircle.
+struct Triangle {
+    vertices: [usize; 3],
+    center: (f64, f64),
+    radius_squared: f64,
+}
+
+impl Triangle {
+    fn new(vertices: [usize; 3], points: &[(f64, f64)]) -> Self {
+        let [a, b, c] = [
+            points[vertices[0]],
+            points[vertices[1]],
+            points[vertices[2]],
+        ];
+        let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+        let (a2, b2, c2) = (
+            a.0 * a.0 + a.1 * a.1,
+            b.0 * b.0 + b.1 * b.1,
+            c.0 * c.0 + c.1 * c.1,
+        );
+        let center = (
+            (a2 * (b.1 - c.1) + b2 * (c.1 - a.1) + c2 * (a.1 - b.1)) / d,
+            (a2 * (c.0 - b.0) + b2 * (a.0 - c.0) + c2 * (b.0 - a.0)) / d,
+        );
+        let radius_squared = (a.0 - center.0).powi(2) + (a.1 - center.1).powi(2);
+        Self {
+            vertices,
+            center,
+            radius_squared,
+        }
+    }
+
+    /// Check if the point is strictly inside of the circumcircle.
+    /// Degenerate (flat) triangles contain every point, to be replaced.
+    fn circumcircle_contains(&self, (x, y): (f64, f64)) -> bool {
+        let distance_squared = (x - self.center.0).powi(2) + (y - self.center.1).powi(2);
+        !self.radius_squared.is_finite() || distance_squared < self.radius_squared
+    }
+
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.vertices;
+        [(a, b), (b, c), (c, a)]
+    }
+}
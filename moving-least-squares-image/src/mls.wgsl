@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Dense MLS warp as a render pipeline: a full screen triangle is drawn,
+// and every fragment back projects its pixel with the MLS deformation,
+// then samples the source texture with the hardware bilinear filtering.
+
+[[block]]
+struct Params {
+    // Number of control points in the storage buffer.
+    nb_controls: u32;
+    // 0: affine, 1: similarity, 2: rigid.
+    method: u32;
+    // Size of the source image, in pixels.
+    src_size: vec2<f32>;
+};
+
+struct Control {
+    // Control point in the warped image (p in the paper, since pixels are back projected).
+    p: vec2<f32>;
+    // Control point in the source image (q in the paper).
+    q: vec2<f32>;
+};
+
+[[block]]
+struct Controls {
+    data: [[stride(16)]] array<Control>;
+};
+
+[[group(0), binding(0)]] var<uniform> params: Params;
+[[group(0), binding(1)]] var<storage> controls: [[access(read)]] Controls;
+[[group(0), binding(2)]] var src_texture: texture_2d<f32>;
+[[group(0), binding(3)]] var src_sampler: sampler;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    // Full screen triangle.
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+// MLS deformation of the point v (eq 2 to 7 of the paper).
+fn deform(v: vec2<f32>) -> vec2<f32> {
+    // Weights and weighted centroids p* and q*.
+    var w_sum: f32 = 0.0;
+    var p_star: vec2<f32> = vec2<f32>(0.0, 0.0);
+    var q_star: vec2<f32> = vec2<f32>(0.0, 0.0);
+    for (var i: u32 = 0u; i < params.nb_controls; i = i + 1u) {
+        let c = controls.data[i];
+        let d = c.p - v;
+        let d2 = dot(d, d);
+        if (d2 == 0.0) {
+            return c.q;
+        }
+        let w = 1.0 / d2;
+        w_sum = w_sum + w;
+        p_star = p_star + w * c.p;
+        q_star = q_star + w * c.q;
+    }
+    p_star = p_star / w_sum;
+    q_star = q_star / w_sum;
+    let v_hat = v - p_star;
+
+    // Weighted sums of the centered control points.
+    var pp: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0); // sum of w p^T p as (xx, xy, yy)
+    var pq_0: vec2<f32> = vec2<f32>(0.0, 0.0); // first row of sum of w p^T q
+    var pq_1: vec2<f32> = vec2<f32>(0.0, 0.0); // second row of sum of w p^T q
+    var mu: f32 = 0.0; // sum of w |p|^2
+    var dot_pq: f32 = 0.0; // sum of w (p . q)
+    var cross_pq: f32 = 0.0; // sum of w (p x q)
+    for (var i: u32 = 0u; i < params.nb_controls; i = i + 1u) {
+        let c = controls.data[i];
+        let d = c.p - v;
+        let w = 1.0 / dot(d, d);
+        let p_hat = c.p - p_star;
+        let q_hat = c.q - q_star;
+        pp = pp + w * vec3<f32>(p_hat.x * p_hat.x, p_hat.x * p_hat.y, p_hat.y * p_hat.y);
+        pq_0 = pq_0 + (w * p_hat.x) * q_hat;
+        pq_1 = pq_1 + (w * p_hat.y) * q_hat;
+        mu = mu + w * dot(p_hat, p_hat);
+        dot_pq = dot_pq + w * dot(p_hat, q_hat);
+        cross_pq = cross_pq + w * (p_hat.x * q_hat.y - p_hat.y * q_hat.x);
+    }
+
+    if (params.method == 0u) {
+        // Affine: (v - p*) (sum w p^T p)^-1 (sum w p^T q) + q* (eq 5).
+        let det = pp.x * pp.z - pp.y * pp.y;
+        let r_x = (v_hat.x * pp.z - v_hat.y * pp.y) / det;
+        let r_y = (v_hat.y * pp.x - v_hat.x * pp.y) / det;
+        return r_x * pq_0 + r_y * pq_1 + q_star;
+    }
+    // Similarity (eq 7), and rigid which only keeps the rotation.
+    let rotated = vec2<f32>(
+        dot_pq * v_hat.x - cross_pq * v_hat.y,
+        cross_pq * v_hat.x + dot_pq * v_hat.y
+    );
+    if (params.method == 1u) {
+        return rotated / mu + q_star;
+    }
+    return length(v_hat) * normalize(rotated) + q_star;
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    // Fragments are at pixel centers, and pixels at integer coordinates in the deformation.
+    let src = deform(position.xy - vec2<f32>(0.5, 0.5));
+    let uv = (src + vec2<f32>(0.5, 0.5)) / params.src_size;
+    if (uv.x < 0.0 || uv.y < 0.0 || uv.x > 1.0 || uv.y > 1.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+    return textureSampleLevel(src_texture, src_sampler, uv, 0.0);
+}
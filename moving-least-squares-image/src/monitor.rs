@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Progress reporting and cancellation of long warps, such as warps of huge scans
+//! in an application with a progress bar and a cancel button.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::mapping::grid_positions;
+use crate::{
+    fill_row_from_anchors, fill_rows_from_fn, reverse_mapping, Canvas, DeformMethod, Field, Image,
+    Mapping, Sampler,
+};
+use image::{ImageBuffer, Pixel};
+use std::fmt;
+use std::ops::{Add, Deref, Mul};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Progress callback and cancellation flag of a warp.
+///
+/// Both are used from the threads of the warp, with the `rayon` feature.
+#[derive(Clone, Copy, Default)]
+pub struct Monitor<'a> {
+    /// Called with the number of rows of the warped image completed so far,
+    /// after each row, which may complete in any order.
+    pub progress: Option<&'a (dyn Fn(u32) + Sync)>,
+    /// Flag checked before each row, stopping the warp as soon as it is set.
+    pub cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a> Monitor<'a> {
+    /// Set the progress callback.
+    pub fn progress(mut self, progress: &'a (dyn Fn(u32) + Sync)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Set the cancellation flag.
+    pub fn cancel(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Check if the warp was cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+
+    /// Count one more completed row, and report it.
+    fn row_done(&self, done: &AtomicU32) {
+        let rows = done.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(progress) = self.progress {
+            progress(rows);
+        }
+    }
+}
+
+/// Error of a warp stopped by its cancellation flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the warp was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Same as [`reverse_dense_with`](crate::reverse_dense_with),
+/// but reports its progress and stops when cancelled.
+pub fn reverse_dense_monitored<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    monitor: &Monitor,
+) -> Result<Image<P>, Cancelled>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    warp_dense_monitored(img_src, &mapping, sampler, monitor)
+}
+
+/// Same as [`warp_dense`](crate::warp_dense),
+/// but reports its progress and stops when cancelled.
+pub fn warp_dense_monitored<P, C, V, M>(
+    img_src: &ImageBuffer<P, C>,
+    mapping: &M,
+    sampler: &Sampler<P>,
+    monitor: &Monitor,
+) -> Result<Image<P>, Cancelled>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    M: Mapping + ?Sized,
+{
+    let (width, height) = img_src.dimensions();
+    let mut warped = ImageBuffer::new(width, height);
+    let channels = P::CHANNEL_COUNT as usize;
+    let done = AtomicU32::new(0);
+    fill_rows_from_fn(&mut warped, |y, row| {
+        if monitor.is_cancelled() {
+            return;
+        }
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            let (x2, y2) = mapping.source((x as f32, y as f32));
+            pixel.copy_from_slice(sampler.sample(img_src, x2, y2).channels());
+        }
+        monitor.row_done(&done);
+    });
+    finish(warped, done.into_inner())
+}
+
+/// Same as [`reverse_sparse_with`](crate::reverse_sparse_with),
+/// but reports its progress and stops when cancelled.
+///
+/// The anchors of the sparse grid are computed row by row beforehand,
+/// checking the cancellation flag before each row, without reporting progress.
+pub fn reverse_sparse_monitored<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    monitor: &Monitor,
+) -> Result<Image<P>, Cancelled>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let (width, height) = img_src.dimensions();
+    // size of the subresolution matrix for which we actually compute the reprojections
+    let sub_width = ((width.max(1) - 1) / subresolution_factor + 2) as usize;
+    let positions = grid_positions(&Canvas::new(width, height), subresolution_factor);
+    let sub_height = (positions.len() / sub_width) as u32;
+    // the anchors are computed as a field, with the same rows scheduling than the warp
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    let mut grid: Field = ImageBuffer::new(sub_width as u32, sub_height);
+    fill_rows_from_fn(&mut grid, |j, row| {
+        if monitor.is_cancelled() {
+            return;
+        }
+        let start = j as usize * sub_width;
+        let row_positions = &positions[start..start + sub_width];
+        for (anchor, &position) in row.chunks_exact_mut(2).zip(row_positions) {
+            let (x, y) = mapping(position);
+            anchor.copy_from_slice(&[x, y]);
+        }
+    });
+    if monitor.is_cancelled() {
+        return Err(Cancelled);
+    }
+    let anchors: Vec<(f32, f32)> = grid.chunks_exact(2).map(|a| (a[0], a[1])).collect();
+
+    let mut warped = ImageBuffer::new(width, height);
+    let done = AtomicU32::new(0);
+    fill_rows_from_fn(&mut warped, |y, row| {
+        if monitor.is_cancelled() {
+            return;
+        }
+        let factor = subresolution_factor;
+        fill_row_from_anchors(img_src, &anchors, sub_width, factor, sampler, y, row);
+        monitor.row_done(&done);
+    });
+    finish(warped, done.into_inner())
+}
+
+/// The warped image, unless the warp was cancelled before completing all its rows.
+fn finish<P: Pixel + 'static>(warped: Image<P>, rows_done: u32) -> Result<Image<P>, Cancelled> {
+    // an image without columns has no row to fill
+    if rows_done == warped.height() || warped.width() == 0 {
+        Ok(warped)
+    } else {
+        Err(Cancelled)
+    }
+}
@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Morphing between two images, warping both of them toward
+//! an intermediate configuration of their control points and cross-dissolving them.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use crate::{reverse_dense_on, Canvas, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, Mul};
+
+/// Morph at time `t` in [0, 1] between two images,
+/// with control points at corresponding features of both images.
+///
+/// Both images are warped toward the control points linearly interpolated at `t`,
+/// and then cross-dissolved with the weight `t` for the second image.
+/// The morph is the first image at `t = 0` and the second image at `t = 1`.
+/// It has the dimensions of the first image.
+pub fn morph<P, C, V>(
+    img_a: &ImageBuffer<P, C>,
+    img_b: &ImageBuffer<P, C>,
+    controls_a: &[(f32, f32)],
+    controls_b: &[(f32, f32)],
+    t: f32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    assert_eq!(
+        controls_a.len(),
+        controls_b.len(),
+        "unmatched control points"
+    );
+    let controls = mls::blend_controls(controls_a, controls_b, t);
+    let canvas = Canvas::of(img_a);
+    let sampler = Sampler::default();
+    let mut morphed = reverse_dense_on(img_a, controls_a, &controls, method, &sampler, &canvas);
+    let warped_b = reverse_dense_on(img_b, controls_b, &controls, method, &sampler, &canvas);
+    for (pixel, &other) in morphed.pixels_mut().zip(warped_b.pixels()) {
+        interpolation::blend(pixel, other, t);
+    }
+    morphed
+}
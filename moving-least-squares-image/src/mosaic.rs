@@ -0,0 +1,449 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Mosaics of several overlapping images, each one warped into a common frame
+//! by its own control points, and blended where they overlap.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use crate::mapping::map_points;
+use crate::{image_from_fn, reverse_mapping, zero_pixel, Alpha, Canvas, DeformMethod, Image};
+use image::{ImageBuffer, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// Image of a mosaic, with control points in the image and in the common frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Tile<'a, P: Pixel, C> {
+    /// Source image.
+    pub img: &'a ImageBuffer<P, C>,
+    /// Control points in the source image.
+    pub controls_src: &'a [(f32, f32)],
+    /// Matching control points in the common frame.
+    pub controls_dst: &'a [(f32, f32)],
+}
+
+/// How overlapping images are blended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blending {
+    /// Weighted average, with weights growing with the distance to the image borders,
+    /// to hide the seams.
+    Feather,
+    /// Multiband blending with the given number of levels (Burt and Adelson):
+    /// low frequencies are blended over wide regions, and high frequencies
+    /// over narrow ones, to hide the seams without ghosting the details.
+    Multiband(u32),
+}
+
+/// Warp all the images into the common frame covered by the canvas,
+/// and blend them where they overlap.
+///
+/// Pixels covered by no image are zero, and images without any pixel are skipped.
+pub fn reverse_mosaic<P, C, V>(
+    tiles: &[Tile<'_, P, C>],
+    canvas: &Canvas,
+    method: DeformMethod,
+    blending: Blending,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V> + Copy + Send + Sync,
+    f32: Mul<V, Output = V>,
+{
+    let zero: V = interpolation::to_vector(zero_pixel::<P>(), Alpha::Premultiplied);
+    let warped: Vec<Warped<V>> = tiles
+        .iter()
+        .filter(|tile| tile.img.width() > 0 && tile.img.height() > 0)
+        .map(|tile| warp_tile(tile, canvas, method, zero))
+        .collect();
+    if warped.is_empty() {
+        return ImageBuffer::new(canvas.width, canvas.height);
+    }
+    let (width, height) = (canvas.width as usize, canvas.height as usize);
+    let blended = match blending {
+        Blending::Feather => feather(&warped, width * height, zero),
+        Blending::Multiband(levels) => multiband(&warped, (width, height), levels, zero),
+    };
+    image_from_fn(canvas.width, canvas.height, |x, y| {
+        let index = y as usize * width + x as usize;
+        let covered = warped.iter().any(|w| w.weights[index] > 0.0);
+        if covered {
+            interpolation::from_vector::<V, P, P>(blended[index], Alpha::Premultiplied)
+        } else {
+            zero_pixel()
+        }
+    })
+}
+
+/// Image warped into the common frame, with the blending weight of every pixel,
+/// 0 where it is not covered by the image.
+struct Warped<V> {
+    colors: Vec<V>,
+    weights: Vec<f32>,
+}
+
+/// Warp the image of the tile into the common frame.
+fn warp_tile<P, C, V>(
+    tile: &Tile<'_, P, C>,
+    canvas: &Canvas,
+    method: DeformMethod,
+    zero: V,
+) -> Warped<V>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    let positions: Vec<(f32, f32)> = (0..canvas.height)
+        .flat_map(|y| (0..canvas.width).map(move |x| (x, y)))
+        .map(|(x, y)| canvas.position(x, y))
+        .collect();
+    let mapping = reverse_mapping(tile.controls_src, tile.controls_dst, method);
+    let sources = map_points(&positions, &mapping);
+    let mut colors = Vec::with_capacity(sources.len());
+    let mut weights = Vec::with_capacity(sources.len());
+    let dimensions = tile.img.dimensions();
+    for &(x, y) in &sources {
+        let sample = covered((x, y), dimensions).and_then(|(x, y)| {
+            interpolation::bilinear::<V, P, C, P>(tile.img, x, y, Alpha::Premultiplied)
+        });
+        match sample {
+            Some(pixel) => {
+                colors.push(interpolation::to_vector(pixel, Alpha::Premultiplied));
+                weights.push(border_distance((x, y), dimensions));
+            }
+            None => {
+                colors.push(zero);
+                weights.push(0.0);
+            }
+        }
+    }
+    Warped { colors, weights }
+}
+
+/// Point clamped to the image, if it is at most half a pixel away from it,
+/// so that the pixels on the border of the image are not lost to rounding errors.
+fn covered((x, y): (f32, f32), (width, height): (u32, u32)) -> Option<(f32, f32)> {
+    let (right, bottom) = (width as f32 - 0.5, height as f32 - 0.5);
+    if x >= -0.5 && x <= right && y >= -0.5 && y <= bottom {
+        Some((x.clamp(0.0, right - 0.5), y.clamp(0.0, bottom - 0.5)))
+    } else {
+        None
+    }
+}
+
+/// Distance of the point to the closest border of the image, plus one,
+/// so that the pixels on the border have a positive weight.
+fn border_distance((x, y): (f32, f32), (width, height): (u32, u32)) -> f32 {
+    let right = width as f32 - 1.0 - x;
+    let bottom = height as f32 - 1.0 - y;
+    1.0 + x.min(y).min(right).min(bottom).max(0.0)
+}
+
+/// Weighted average of the warped images.
+fn feather<V>(warped: &[Warped<V>], size: usize, zero: V) -> Vec<V>
+where
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    (0..size)
+        .map(|i| {
+            let total: f32 = warped.iter().map(|w| w.weights[i]).sum();
+            if total <= 0.0 {
+                return zero;
+            }
+            warped
+                .iter()
+                .filter(|w| w.weights[i] > 0.0)
+                .fold(zero, |acc, w| acc + (w.weights[i] / total) * w.colors[i])
+        })
+        .collect()
+}
+
+// Multiband blending ##########################################################
+
+/// Image of vectors, row by row, for the pyramids of the multiband blending.
+#[derive(Clone)]
+struct Plane<T> {
+    width: usize,
+    height: usize,
+    data: Vec<T>,
+}
+
+/// Multiband blending of the warped images, with masks selecting for each pixel
+/// the image with the largest weight.
+///
+/// The colors of each image are extrapolated where it is not covered,
+/// and the masks where no image is covered, so that no zero leaks into the coarse levels.
+fn multiband<V>(
+    warped: &[Warped<V>],
+    (width, height): (usize, usize),
+    levels: u32,
+    zero: V,
+) -> Vec<V>
+where
+    V: Add<Output = V> + Copy,
+    f32: Mul<V, Output = V>,
+{
+    // Keep at least one pixel in the coarsest level.
+    let max_levels = (usize::BITS - width.min(height).max(1).leading_zeros()) as usize;
+    let levels = (levels as usize).clamp(1, max_levels);
+    let masks = selection_masks(warped, width * height);
+    let coverage = |covered: &dyn Fn(usize) -> bool| Plane {
+        width,
+        height,
+        data: (0..width * height)
+            .map(|i| if covered(i) { 1.0 } else { 0.0 })
+            .collect(),
+    };
+    let any_coverage = coverage(&|i| warped.iter().any(|w| w.weights[i] > 0.0));
+    let mut blended: Vec<Plane<V>> = Vec::new();
+    let mut totals: Vec<Plane<f32>> = Vec::new();
+    for (w, mask) in warped.iter().zip(masks) {
+        let colors = Plane {
+            width,
+            height,
+            data: w.colors.clone(),
+        };
+        let colors = extrapolate(colors, &coverage(&|i| w.weights[i] > 0.0), zero);
+        let mask = Plane {
+            width,
+            height,
+            data: mask,
+        };
+        let mask = extrapolate::<f32>(mask, &any_coverage, 0.0);
+        let laplacian = laplacian_pyramid(colors, levels);
+        let gaussian = gaussian_pyramid::<f32>(mask, levels);
+        if blended.is_empty() {
+            blended = laplacian
+                .iter()
+                .map(|l| Plane {
+                    width: l.width,
+                    height: l.height,
+                    data: vec![zero; l.data.len()],
+                })
+                .collect();
+            totals = gaussian
+                .iter()
+                .map(|g| Plane {
+                    width: g.width,
+                    height: g.height,
+                    data: vec![0.0; g.data.len()],
+                })
+                .collect();
+        }
+        for level in 0..levels {
+            let (l, g) = (&laplacian[level], &gaussian[level]);
+            let (b, t) = (&mut blended[level], &mut totals[level]);
+            for i in 0..l.data.len() {
+                b.data[i] = b.data[i] + g.data[i] * l.data[i];
+                t.data[i] += g.data[i];
+            }
+        }
+    }
+    // Normalize the weights of every level, and collapse the pyramid.
+    for (b, t) in blended.iter_mut().zip(&totals) {
+        for (v, &total) in b.data.iter_mut().zip(&t.data) {
+            *v = if total > 0.0 {
+                (1.0 / total) * *v
+            } else {
+                zero
+            };
+        }
+    }
+    let mut collapsed = blended.pop().expect("at least one level");
+    while let Some(level) = blended.pop() {
+        let up = upsample(&collapsed, level.width, level.height);
+        collapsed = Plane {
+            width: level.width,
+            height: level.height,
+            data: level
+                .data
+                .iter()
+                .zip(up.data)
+                .map(|(&l, u)| l + u)
+                .collect(),
+        };
+    }
+    collapsed.data
+}
+
+/// Masks at 1 where the warped image has the largest weight, and 0 elsewhere.
+fn selection_masks<V>(warped: &[Warped<V>], size: usize) -> Vec<Vec<f32>> {
+    let best: Vec<Option<usize>> = (0..size)
+        .map(|i| {
+            let candidates = warped
+                .iter()
+                .enumerate()
+                .filter(|(_, w)| w.weights[i] > 0.0);
+            let best = candidates.max_by(|(_, a), (_, b)| a.weights[i].total_cmp(&b.weights[i]));
+            best.map(|(k, _)| k)
+        })
+        .collect();
+    (0..warped.len())
+        .map(|k| {
+            let selected = best.iter().map(|&b| if b == Some(k) { 1.0 } else { 0.0 });
+            selected.collect()
+        })
+        .collect()
+}
+
+/// Fill the values where the coverage is 0 with a smooth extrapolation
+/// of the covered values, keeping the covered values as is (push-pull).
+///
+/// A constant plane stays constant, wherever it is covered.
+fn extrapolate<T>(plane: Plane<T>, coverage: &Plane<f32>, zero: T) -> Plane<T>
+where
+    T: Add<Output = T> + Copy,
+    f32: Mul<T, Output = T>,
+{
+    // Push: average the covered values, down to a single pixel.
+    let weighted = plane
+        .data
+        .iter()
+        .zip(&coverage.data)
+        .map(|(&v, &c)| c * v)
+        .collect();
+    let mut values = vec![Plane {
+        width: plane.width,
+        height: plane.height,
+        data: weighted,
+    }];
+    let mut coverages = vec![coverage.clone()];
+    while values[values.len() - 1].width > 1 || values[values.len() - 1].height > 1 {
+        values.push(downsample(&values[values.len() - 1]));
+        coverages.push(downsample::<f32>(&coverages[coverages.len() - 1]));
+    }
+    // Pull: complete the partially covered pixels with the coarser level.
+    let coarsest = values.pop().expect("at least one level");
+    let coverage = coverages.pop().expect("at least one level");
+    let data = coarsest.data.iter().zip(&coverage.data);
+    let mut filled = Plane {
+        width: coarsest.width,
+        height: coarsest.height,
+        data: data
+            .map(|(&v, &c)| if c > 0.0 { (1.0 / c) * v } else { zero })
+            .collect(),
+    };
+    while let (Some(level), Some(coverage)) = (values.pop(), coverages.pop()) {
+        let up = upsample(&filled, level.width, level.height);
+        let data = level.data.iter().zip(&coverage.data).zip(up.data);
+        filled = Plane {
+            width: level.width,
+            height: level.height,
+            data: data.map(|((&v, &c), u)| v + (1.0 - c) * u).collect(),
+        };
+    }
+    filled
+}
+
+/// Gaussian pyramid, from the full resolution to the coarsest level.
+fn gaussian_pyramid<T>(plane: Plane<T>, levels: usize) -> Vec<Plane<T>>
+where
+    T: Add<Output = T> + Copy,
+    f32: Mul<T, Output = T>,
+{
+    let mut pyramid = vec![plane];
+    for _ in 1..levels {
+        let next = downsample(&pyramid[pyramid.len() - 1]);
+        pyramid.push(next);
+    }
+    pyramid
+}
+
+/// Laplacian pyramid, the differences between the levels of the Gaussian pyramid,
+/// except for the coarsest level, kept as is.
+fn laplacian_pyramid<T>(plane: Plane<T>, levels: usize) -> Vec<Plane<T>>
+where
+    T: Add<Output = T> + Copy,
+    f32: Mul<T, Output = T>,
+{
+    let gaussian = gaussian_pyramid(plane, levels);
+    let mut pyramid: Vec<Plane<T>> = gaussian
+        .windows(2)
+        .map(|pair| {
+            let up = upsample(&pair[1], pair[0].width, pair[0].height);
+            let data = pair[0]
+                .data
+                .iter()
+                .zip(up.data)
+                .map(|(&g, u)| g + (-1.0) * u)
+                .collect();
+            Plane {
+                width: pair[0].width,
+                height: pair[0].height,
+                data,
+            }
+        })
+        .collect();
+    pyramid.push(gaussian[gaussian.len() - 1].clone());
+    pyramid
+}
+
+/// Plane of half the size, averaging blocs of 2x2 pixels.
+fn downsample<T>(plane: &Plane<T>) -> Plane<T>
+where
+    T: Add<Output = T> + Copy,
+    f32: Mul<T, Output = T>,
+{
+    let (width, height) = ((plane.width + 1) / 2, (plane.height + 1) / 2);
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let xs = (2 * x..(2 * x + 2).min(plane.width)).collect::<Vec<_>>();
+            let ys = 2 * y..(2 * y + 2).min(plane.height);
+            let mut count = 0;
+            let mut sum = None;
+            for j in ys {
+                for &i in &xs {
+                    let v = plane.data[j * plane.width + i];
+                    sum = Some(sum.map_or(v, |s| s + v));
+                    count += 1;
+                }
+            }
+            data.push((1.0 / count as f32) * sum.expect("at least one pixel"));
+        }
+    }
+    Plane {
+        width,
+        height,
+        data,
+    }
+}
+
+/// Plane of the given size, bilinearly interpolated from the coarser plane.
+fn upsample<T>(plane: &Plane<T>, width: usize, height: usize) -> Plane<T>
+where
+    T: Add<Output = T> + Copy,
+    f32: Mul<T, Output = T>,
+{
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (y0, y1, b) = taps(y, plane.height);
+        for x in 0..width {
+            let (x0, x1, a) = taps(x, plane.width);
+            let [w00, w10, w01, w11] = bilinear_weights(a, b);
+            let at = |i, j| plane.data[j * plane.width + i];
+            data.push(w00 * at(x0, y0) + w10 * at(x1, y0) + w01 * at(x0, y1) + w11 * at(x1, y1));
+        }
+    }
+    Plane {
+        width,
+        height,
+        data,
+    }
+}
+
+/// Indices of the two coarse pixels around the center of the fine pixel,
+/// and the interpolation factor between them.
+fn taps(fine: usize, coarse_size: usize) -> (usize, usize, f32) {
+    let z = ((fine as f32 + 0.5) / 2.0 - 0.5).clamp(0.0, coarse_size as f32 - 1.0);
+    let z0 = z.floor() as usize;
+    let z1 = (z0 + 1).min(coarse_size - 1);
+    (z0, z1, z - z0 as f32)
+}
+
+/// Weights of the top left, top right, bottom left and bottom right pixels.
+fn bilinear_weights(a: f32, b: f32) -> [f32; 4] {
+    [(1.0 - a) * (1.0 - b), a * (1.0 - b), (1.0 - a) * b, a * b]
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warped checkerboards and grids, to visualize a deformation
+//! like the figures of the MLS paper.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use crate::{image_from_fn, reverse_mapping, DeformMethod, Image};
+use image::{ImageBuffer, Luma, Pixel};
+use std::ops::{Add, DerefMut, Mul};
+
+/// Pattern drawn in the frame of the source image, with cells of the given size in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Alternating filled and empty squares.
+    Checkerboard(u32),
+    /// Lines of one pixel wide.
+    Grid(u32),
+}
+
+impl Pattern {
+    /// Coverage in [0, 1] of the pattern at the given location of the source image.
+    fn coverage(self, (x, y): (f32, f32)) -> f32 {
+        match self {
+            Pattern::Checkerboard(cell) => {
+                let cell = cell.max(1) as f32;
+                let parity = (x / cell).floor() + (y / cell).floor();
+                if parity.rem_euclid(2.0) < 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Pattern::Grid(cell) => {
+                let cell = cell.max(1) as f32;
+                // Distance to the closest line, anti-aliased over a pixel.
+                let distance = |z: f32| (z - (z / cell).round() * cell).abs();
+                (1.0 - distance(x).min(distance(y))).max(0.0)
+            }
+        }
+    }
+}
+
+/// Mask of the pattern warped like [`reverse_dense`](crate::reverse_dense),
+/// at 255 on the pattern and 0 elsewhere.
+///
+/// The pattern covers the whole plane, so the deformation is visible
+/// even where the warped image is outside of the source image.
+pub fn reverse_pattern(
+    width: u32,
+    height: u32,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    pattern: Pattern,
+) -> Image<Luma<u8>> {
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    image_from_fn(width, height, |x, y| {
+        let coverage = pattern.coverage(mapping((x as f32, y as f32)));
+        Luma([(255.0 * coverage).round() as u8])
+    })
+}
+
+/// Composite the color over the image, with the mask as opacity,
+/// such as a mask given by [`reverse_pattern`] over the warped image.
+///
+/// The mask and the image must have the same dimensions.
+pub fn overlay<P, C, V>(img: &mut ImageBuffer<P, C>, mask: &Image<Luma<u8>>, color: P)
+where
+    P: Pixel + CanLinearInterpolate<V, P> + 'static,
+    C: DerefMut<Target = [P::Subpixel]>,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    assert_eq!(img.dimensions(), mask.dimensions(), "mask of another size");
+    for (pixel, opacity) in img.pixels_mut().zip(mask.pixels()) {
+        let opacity = f32::from(opacity.0[0]) / 255.0;
+        if opacity > 0.0 {
+            interpolation::blend(pixel, color, opacity);
+        }
+    }
+}
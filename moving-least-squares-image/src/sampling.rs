@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Sampling options of the source image at the back projected pixel locations.
+
+use crate::interpolation::{self, CanLinearInterpolate};
+use crate::zero_pixel;
+use image::{ImageBuffer, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// How pixels are sampled outside of the source image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Border<P> {
+    /// Locations outside of the image have the given color.
+    ///
+    /// Inside of it, up to its last rows and columns, the kernel neighbors
+    /// falling outside are clamped to the border pixels instead.
+    Constant(P),
+    /// The pixels on the image border are replicated outside of it.
+    Clamp,
+    /// The image is mirrored at its border, without repeating the border pixels,
+    /// like `gfedcb|abcdefgh|gfedcb`.
+    Reflect,
+    /// The image is repeated periodically, like `abcdefgh|abcdefgh|abcdefgh`.
+    Wrap,
+}
+
+impl<P> Border<P> {
+    /// Index inside `[0, size)` of the pixel to use at the given index,
+    /// or `None` if the constant color must be used instead.
+    pub(crate) fn resolve(&self, index: i64, size: u32) -> Option<u32> {
+        let size = i64::from(size);
+        if (0..size).contains(&index) {
+            return Some(index as u32);
+        }
+        let resolved = match self {
+            Border::Constant(_) => return None,
+            Border::Clamp => index.clamp(0, size - 1),
+            Border::Reflect if size == 1 => 0,
+            Border::Reflect => {
+                let period = 2 * (size - 1);
+                let i = index.rem_euclid(period);
+                if i < size {
+                    i
+                } else {
+                    period - i
+                }
+            }
+            Border::Wrap => index.rem_euclid(size),
+        };
+        Some(resolved as u32)
+    }
+}
+
+/// Interpolation method of the source image at floating point coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Color of the closest pixel.
+    Nearest,
+    /// Linear interpolation of the 2x2 closest pixels.
+    Bilinear,
+    /// Catmull-Rom cubic interpolation of the 4x4 closest pixels.
+    /// Sharper than bilinear, with a slight overshoot near edges.
+    Bicubic,
+    /// Mitchell-Netravali cubic filter (B = C = 1/3) of the 4x4 closest pixels.
+    /// Softer than bicubic, with almost no ringing.
+    Mitchell,
+    /// Cubic B-spline filter of the 4x4 closest pixels, the smoothest, without ringing.
+    /// It blurs the image, unless sampling its coefficients given by
+    /// [`bspline_prefilter`](crate::bspline_prefilter) with the reflect border.
+    BSpline,
+    /// Lanczos interpolation with a window of 3 pixels (6x6 closest pixels).
+    /// The sharpest, but also the slowest.
+    Lanczos3,
+}
+
+impl Interpolation {
+    /// Number of pixels in each direction from the interpolated location
+    /// with a non-zero weight.
+    pub(crate) fn radius(self) -> i64 {
+        match self {
+            Interpolation::Nearest | Interpolation::Bilinear => 1,
+            Interpolation::Bicubic | Interpolation::Mitchell | Interpolation::BSpline => 2,
+            Interpolation::Lanczos3 => 3,
+        }
+    }
+
+    /// Weight of a pixel at the signed distance t from the interpolated location.
+    pub(crate) fn weight(self, t: f32) -> f32 {
+        let t = t.abs();
+        match self {
+            Interpolation::Nearest => {
+                if t < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Interpolation::Bilinear => (1.0 - t).max(0.0),
+            // Catmull-Rom spline, the cubic convolution with a = -0.5.
+            Interpolation::Bicubic if t < 1.0 => (1.5 * t - 2.5) * t * t + 1.0,
+            Interpolation::Bicubic if t < 2.0 => ((-0.5 * t + 2.5) * t - 4.0) * t + 2.0,
+            Interpolation::Bicubic => 0.0,
+            Interpolation::Mitchell if t < 1.0 => ((7.0 * t - 12.0) * t * t + 16.0 / 3.0) / 6.0,
+            Interpolation::Mitchell if t < 2.0 => {
+                (((-7.0 / 3.0 * t + 12.0) * t - 20.0) * t + 32.0 / 3.0) / 6.0
+            }
+            Interpolation::Mitchell => 0.0,
+            Interpolation::BSpline if t < 1.0 => ((3.0 * t - 6.0) * t * t + 4.0) / 6.0,
+            Interpolation::BSpline if t < 2.0 => (2.0 - t).powi(3) / 6.0,
+            Interpolation::BSpline => 0.0,
+            Interpolation::Lanczos3 if t < 3.0 => sinc(t) * sinc(t / 3.0),
+            Interpolation::Lanczos3 => 0.0,
+        }
+    }
+}
+
+/// Normalized sinc function, sin(pi t) / (pi t).
+fn sinc(t: f32) -> f32 {
+    if t == 0.0 {
+        1.0
+    } else {
+        let pi_t = std::f32::consts::PI * t;
+        pi_t.sin() / pi_t
+    }
+}
+
+/// How colors are interpolated with their alpha channel, for images with one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alpha {
+    /// Colors are premultiplied by their alpha before being interpolated,
+    /// so that the color of transparent pixels does not bleed into their neighbors.
+    Premultiplied,
+    /// Colors are interpolated independently of their alpha,
+    /// for images whose transparent pixels have meaningful colors.
+    Straight,
+}
+
+/// Options to sample the source image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler<P> {
+    /// Interpolation method of the source image.
+    pub interpolation: Interpolation,
+    /// How pixels are sampled outside of the source image.
+    pub border: Border<P>,
+    /// How colors are interpolated with their alpha channel.
+    pub alpha: Alpha,
+}
+
+impl<P: Pixel> Default for Sampler<P> {
+    /// Bilinear interpolation, with pixels outside of the image at zero,
+    /// which is black for RGB images and transparent for RGBA images,
+    /// and premultiplied alpha.
+    fn default() -> Self {
+        Self {
+            interpolation: Interpolation::Bilinear,
+            border: Border::Constant(zero_pixel()),
+            alpha: Alpha::Premultiplied,
+        }
+    }
+}
+
+impl<P: Pixel> Sampler<P> {
+    /// Set the interpolation method of the source image.
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Set how pixels are sampled outside of the source image.
+    pub fn border(mut self, border: Border<P>) -> Self {
+        self.border = border;
+        self
+    }
+
+    /// Set how colors are interpolated with their alpha channel.
+    pub fn alpha(mut self, alpha: Alpha) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Sample the image at the given floating point coordinates.
+    pub fn sample<V, C>(&self, img: &ImageBuffer<P, C>, x: f32, y: f32) -> P
+    where
+        P: CanLinearInterpolate<V, P> + 'static,
+        C: Deref<Target = [P::Subpixel]>,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        let (width, height) = img.dimensions();
+        if let Border::Constant(color) = self.border {
+            if self.interpolation == Interpolation::Bilinear {
+                return interpolation::bilinear(img, x, y, self.alpha).unwrap_or(color);
+            }
+            // The constant color is only used outside of the image.
+            if !interpolation::inside(width, height, x, y) {
+                return color;
+            }
+        }
+        let resolve = |i: i64, j: i64| {
+            // The neighbors outside of the image are clamped for the constant border.
+            let clamp = |index: i64, size: u32| index.clamp(0, i64::from(size) - 1) as u32;
+            let i = self
+                .border
+                .resolve(i, width)
+                .unwrap_or_else(|| clamp(i, width));
+            let j = self
+                .border
+                .resolve(j, height)
+                .unwrap_or_else(|| clamp(j, height));
+            (i, j)
+        };
+        match self.interpolation {
+            Interpolation::Nearest => {
+                let (i, j) = resolve(round(x), round(y));
+                *img.get_pixel(i, j)
+            }
+            method => {
+                let kernel = |t| method.weight(t);
+                interpolation::separable_with(
+                    img,
+                    x,
+                    y,
+                    method.radius(),
+                    kernel,
+                    resolve,
+                    self.alpha,
+                )
+            }
+        }
+    }
+}
+
+/// Index of the closest pixel to the given coordinate.
+#[allow(clippy::cast_possible_truncation)]
+fn round(z: f32) -> i64 {
+    z.round() as i64
+}
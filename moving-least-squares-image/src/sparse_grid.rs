@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Sparse grid of anchors of a warp, computed once and reused,
+//! for rigs with fixed control points.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{anchors_warp, fill_from_anchors, image_from_fn, DeformMethod, Field, Image, Sampler};
+use image::{ImageBuffer, LumaA, Pixel};
+use moving_least_squares as mls;
+use std::ops::{Add, Deref, DerefMut, Mul};
+
+/// Location in the source image of a sparse grid of pixels of the warped image,
+/// the other pixels being interpolated bilinearly, as in [`reverse_sparse`](crate::reverse_sparse).
+///
+/// With the `serde` feature, it can be serialized to save the anchors
+/// and reuse them without any MLS computation.
+/// Deserializing fails if there is not one anchor per grid point.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawSparseWarpGrid"))]
+pub struct SparseWarpGrid {
+    width: u32,
+    height: u32,
+    subresolution_factor: u32,
+    /// Source location of the grid points, row by row.
+    anchors: Vec<(f32, f32)>,
+}
+
+/// Deserialized grid, before checking its number of anchors.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSparseWarpGrid {
+    width: u32,
+    height: u32,
+    subresolution_factor: u32,
+    anchors: Vec<(f32, f32)>,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<RawSparseWarpGrid> for SparseWarpGrid {
+    type Error = &'static str;
+
+    fn try_from(raw: RawSparseWarpGrid) -> Result<Self, Self::Error> {
+        if raw.subresolution_factor == 0 {
+            return Err("the subresolution factor must be strictly positive");
+        }
+        let (sub_width, sub_height) = grid_size(raw.width, raw.height, raw.subresolution_factor);
+        if raw.anchors.len() != sub_width * sub_height {
+            return Err("there must be one anchor per point of the grid");
+        }
+        Ok(Self {
+            width: raw.width,
+            height: raw.height,
+            subresolution_factor: raw.subresolution_factor,
+            anchors: raw.anchors,
+        })
+    }
+}
+
+impl SparseWarpGrid {
+    /// Compute the anchors of the warp of images of the given size,
+    /// with the same arguments than [`reverse_sparse`](crate::reverse_sparse).
+    pub fn new(
+        width: u32,
+        height: u32,
+        controls_src: &[(f32, f32)],
+        controls_dst: &[(f32, f32)],
+        subresolution_factor: u32,
+        method: DeformMethod,
+    ) -> Self {
+        assert!(
+            subresolution_factor > 0,
+            "The subresolution factor must be strictly positive"
+        );
+        // the anchors are the MLS reprojection of the subresolution matrix of points
+        let anchors = mls::deform_grid(
+            controls_dst,
+            controls_src,
+            width,
+            height,
+            subresolution_factor,
+            method,
+        );
+        Self {
+            width,
+            height,
+            subresolution_factor,
+            anchors,
+        }
+    }
+
+    /// Grid with the given anchors, such as the ones of another grid.
+    ///
+    /// Panics if the subresolution factor is zero,
+    /// or if there are not as many anchors as grid points.
+    pub fn from_anchors(
+        width: u32,
+        height: u32,
+        subresolution_factor: u32,
+        anchors: Vec<(f32, f32)>,
+    ) -> Self {
+        assert!(
+            subresolution_factor > 0,
+            "The subresolution factor must be strictly positive"
+        );
+        let (sub_width, sub_height) = grid_size(width, height, subresolution_factor);
+        assert_eq!(
+            anchors.len(),
+            sub_width * sub_height,
+            "There must be one anchor per point of the grid"
+        );
+        Self {
+            width,
+            height,
+            subresolution_factor,
+            anchors,
+        }
+    }
+
+    /// Size of the images this grid applies to.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Spacing of the grid points, in pixels.
+    pub fn subresolution_factor(&self) -> u32 {
+        self.subresolution_factor
+    }
+
+    /// Source location of the grid points, row by row.
+    pub fn anchors(&self) -> &[(f32, f32)] {
+        &self.anchors
+    }
+
+    /// Location in the source image of the given pixel of the warped image.
+    pub fn source(&self, x: u32, y: u32) -> (f32, f32) {
+        let (sub_width, _) = grid_size(self.width, self.height, self.subresolution_factor);
+        anchors_warp(&self.anchors, sub_width, self.subresolution_factor, (x, y))
+    }
+
+    /// Coordinates field of the warp, to [`remap`](crate::remap) images.
+    pub fn field(&self) -> Field {
+        image_from_fn(self.width, self.height, |x, y| {
+            let (x2, y2) = self.source(x, y);
+            LumaA([x2, y2])
+        })
+    }
+
+    /// Compute the warped image.
+    ///
+    /// The source image may have any size, the warped image has the dimensions of the grid.
+    pub fn warp<P, C, V>(&self, img_src: &ImageBuffer<P, C>, sampler: &Sampler<P>) -> Image<P>
+    where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        let mut warped = ImageBuffer::new(self.width, self.height);
+        self.warp_into(img_src, sampler, &mut warped);
+        warped
+    }
+
+    /// Same as [`warp`](SparseWarpGrid::warp), but writes the warped image into the given buffer,
+    /// to reuse its allocation.
+    ///
+    /// Panics if the buffer does not have the dimensions of the grid.
+    pub fn warp_into<P, C, O, V>(
+        &self,
+        img_src: &ImageBuffer<P, C>,
+        sampler: &Sampler<P>,
+        warped: &mut ImageBuffer<P, O>,
+    ) where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        O: DerefMut<Target = [P::Subpixel]>,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        assert_eq!(
+            warped.dimensions(),
+            self.dimensions(),
+            "The warped image must have the dimensions of the grid"
+        );
+        fill_from_anchors(
+            img_src,
+            &self.anchors,
+            self.subresolution_factor,
+            sampler,
+            warped,
+        );
+    }
+}
+
+/// Number of columns and rows of the grid of anchors.
+fn grid_size(width: u32, height: u32, subresolution_factor: u32) -> (usize, usize) {
+    let sub_width = (width.max(1) - 1) / subresolution_factor + 2;
+    let sub_height = (height.max(1) - 1) / subresolution_factor + 2;
+    (sub_width as usize, sub_height as usize)
+}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warp in linear light of sRGB encoded images.
+//!
+//! Interpolating gamma encoded values darkens high contrast edges,
+//! so the source image is decoded to linear `f32` values before the warp,
+//! and the warped image is encoded back to sRGB after it.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{image_from_fn, reverse_dense, reverse_sparse, DeformMethod, Image};
+use image::{ImageBuffer, Luma, Pixel, Primitive, Rgb, Rgba};
+use num_traits::{NumCast, Unsigned};
+use std::ops::{Add, Deref, Mul};
+
+/// Pixels encoded in sRGB, with a linear light equivalent with `f32` values in [0, 1].
+/// The alpha channel, if any, is not encoded.
+pub trait LinearLight: Pixel {
+    /// Pixel type in linear light.
+    type Linear: Pixel<Subpixel = f32>;
+    /// Decode the pixel to linear light.
+    fn to_linear(&self) -> Self::Linear;
+    /// Encode the pixel from linear light.
+    fn from_linear(linear: &Self::Linear) -> Self;
+}
+
+impl<T: Primitive + Unsigned + 'static> LinearLight for Luma<T> {
+    type Linear = Luma<f32>;
+    fn to_linear(&self) -> Luma<f32> {
+        Luma([decode(self.0[0])])
+    }
+    fn from_linear(linear: &Luma<f32>) -> Self {
+        Luma([encode(linear.0[0])])
+    }
+}
+
+impl<T: Primitive + Unsigned + 'static> LinearLight for Rgb<T> {
+    type Linear = Rgb<f32>;
+    fn to_linear(&self) -> Rgb<f32> {
+        let [r, g, b] = self.0;
+        Rgb([decode(r), decode(g), decode(b)])
+    }
+    fn from_linear(linear: &Rgb<f32>) -> Self {
+        let [r, g, b] = linear.0;
+        Rgb([encode(r), encode(g), encode(b)])
+    }
+}
+
+impl<T: Primitive + Unsigned + 'static> LinearLight for Rgba<T> {
+    type Linear = Rgba<f32>;
+    fn to_linear(&self) -> Rgba<f32> {
+        let [r, g, b, a] = self.0;
+        Rgba([decode(r), decode(g), decode(b), normalize(a)])
+    }
+    fn from_linear(linear: &Rgba<f32>) -> Self {
+        let [r, g, b, a] = linear.0;
+        Rgba([encode(r), encode(g), encode(b), quantize(a)])
+    }
+}
+
+/// Same as [`reverse_dense`], but interpolating the sRGB source image in linear light.
+pub fn reverse_dense_linear_light<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: LinearLight + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]>,
+    P::Linear: CanLinearInterpolate<V, P::Linear> + Send + Sync + 'static,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let linear = to_linear(img_src);
+    from_linear(&reverse_dense(&linear, controls_src, controls_dst, method))
+}
+
+/// Same as [`reverse_sparse`], but interpolating the sRGB source image in linear light.
+pub fn reverse_sparse_linear_light<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+    method: DeformMethod,
+) -> Image<P>
+where
+    P: LinearLight + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]>,
+    P::Linear: CanLinearInterpolate<V, P::Linear> + Send + Sync + 'static,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let linear = to_linear(img_src);
+    let warped = reverse_sparse(
+        &linear,
+        controls_src,
+        controls_dst,
+        subresolution_factor,
+        method,
+    );
+    from_linear(&warped)
+}
+
+/// Decode the whole image to linear light.
+fn to_linear<P, C>(img: &ImageBuffer<P, C>) -> Image<P::Linear>
+where
+    P: LinearLight + 'static,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let (width, height) = img.dimensions();
+    ImageBuffer::from_fn(width, height, |x, y| img.get_pixel(x, y).to_linear())
+}
+
+/// Encode the whole image from linear light.
+fn from_linear<P>(linear: &Image<P::Linear>) -> Image<P>
+where
+    P: LinearLight + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    P::Linear: Sync,
+{
+    let (width, height) = linear.dimensions();
+    image_from_fn(width, height, |x, y| P::from_linear(linear.get_pixel(x, y)))
+}
+
+// sRGB transfer functions #####################################################
+
+/// Decode the sRGB channel value to linear light in [0, 1].
+fn decode<T: Primitive>(value: T) -> f32 {
+    let c = normalize(value);
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode the linear light value in [0, 1] to an sRGB channel value.
+fn encode<T: Primitive>(linear: f32) -> T {
+    let l = linear.max(0.0);
+    let c = if l <= 0.003_130_8 {
+        12.92 * l
+    } else {
+        1.055 * l.powf(1.0 / 2.4) - 0.055
+    };
+    quantize(c)
+}
+
+/// Channel value scaled to [0, 1].
+fn normalize<T: Primitive>(value: T) -> f32 {
+    let max: f32 = NumCast::from(T::max_value()).expect("max value of the channel");
+    let value: f32 = NumCast::from(value).expect("value of the channel");
+    value / max
+}
+
+/// Channel value of the value in [0, 1].
+fn quantize<T: Primitive>(value: f32) -> T {
+    let max: f32 = NumCast::from(T::max_value()).expect("max value of the channel");
+    NumCast::from((value * max).max(0.0).min(max).round()).expect("value of the channel")
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warps attenuated by a strength, global or per pixel,
+//! to feather edits spatially or freeze parts of the image.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{reverse_mapping, warp_dense, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Luma, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// Strength of the warp, scaling the displacement of the pixels.
+#[derive(Debug, Clone, Copy)]
+pub enum Strength<'a> {
+    /// Same strength for all pixels, from 0 for the original image to 1 for the full warp.
+    Global(f32),
+    /// Strength of every pixel of the warped image, from 0 (frozen) to 255 (full warp),
+    /// such as a painted freeze mask.
+    Mask(&'a Image<Luma<u8>>),
+}
+
+impl Strength<'_> {
+    /// Strength in [0, 1] at the given pixel.
+    fn at(&self, x: u32, y: u32) -> f32 {
+        match self {
+            Strength::Global(strength) => *strength,
+            Strength::Mask(mask) => f32::from(mask.get_pixel(x, y).0[0]) / 255.0,
+        }
+    }
+}
+
+/// Same as [`reverse_dense`](crate::reverse_dense), but with the displacement of every pixel
+/// scaled by the strength, blending the warp with the original image geometry.
+///
+/// Panics if a strength mask does not have the dimensions of the image.
+pub fn reverse_dense_strength<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    strength: Strength,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    if let Strength::Mask(mask) = strength {
+        assert_eq!(
+            mask.dimensions(),
+            img_src.dimensions(),
+            "The strength mask must have the dimensions of the image"
+        );
+    }
+    let mapping = attenuated_mapping(controls_src, controls_dst, method, strength);
+    warp_dense(img_src, &mapping, &Sampler::default())
+}
+
+/// Back projection of the pixels with their displacement scaled by the strength.
+fn attenuated_mapping<'a>(
+    controls_src: &'a [(f32, f32)],
+    controls_dst: &'a [(f32, f32)],
+    method: DeformMethod,
+    strength: Strength<'a>,
+) -> impl Fn((f32, f32)) -> (f32, f32) + Sync + 'a {
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    move |(x, y)| {
+        let s = strength.at(x as u32, y as u32);
+        if s == 0.0 {
+            return (x, y);
+        }
+        let (x2, y2) = mapping((x, y));
+        (x + s * (x2 - x), y + s * (y2 - y))
+    }
+}
@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Threads of the parallel warps, with the `rayon` feature.
+//!
+//! The warps run their parallel iterators on the current rayon thread pool,
+//! which is the global one unless they are called inside of [`ThreadPool::install`].
+//! Warping inside of the `install` of a dedicated pool, built once and reused,
+//! keeps the other cores free, for example for the user interface or video decoding.
+
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+/// Run `f`, such as a warp, on a new thread pool with at most the given number of threads.
+///
+/// Building a thread pool spawns its threads, so when warping repeatedly,
+/// prefer building one [`ThreadPool`] and calling the warps inside of its `install`.
+pub fn with_max_threads<R, F>(max_threads: usize, f: F) -> Result<R, ThreadPoolBuildError>
+where
+    R: Send,
+    F: FnOnce() -> R + Send,
+{
+    let pool: ThreadPool = ThreadPoolBuilder::new()
+        .num_threads(max_threads.max(1))
+        .build()?;
+    Ok(pool.install(f))
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warp of huge images, tile by tile, loading only the window of the source image
+//! needed by each tile, so that memory stays bounded.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{image_from_fn, reverse_mapping, Canvas, DeformMethod, Field, Image, Region, Sampler};
+use image::{LumaA, Pixel};
+use std::ops::{Add, Mul};
+
+/// Number of pixels added around the source window of a tile,
+/// for the neighbors used by the interpolation (Lanczos3 uses 3 pixels on each side).
+const WINDOW_MARGIN: u32 = 3;
+
+/// Compute the warped image tile by tile, as [`reverse_dense_on`](crate::reverse_dense_on)
+/// for a source image of size `source_size`.
+///
+/// For each tile of `tile_size` x `tile_size` pixels of the canvas,
+/// the window of the source image it needs is requested to `load`,
+/// which must return the pixels of that region of the source image.
+/// The warped tile is then given to `write`, with its region in the canvas.
+/// Only one tile and its source window are in memory at any time.
+///
+/// The `Reflect` and `Wrap` borders are resolved inside of the source window,
+/// so they only give the same result than the full warp for tiles warped from
+/// inside of the source image.
+///
+/// Panics if the tile size is zero, or if `load` does not return an image
+/// with the size of the requested region.
+#[allow(clippy::too_many_arguments)]
+pub fn reverse_tiled<P, V, L, W>(
+    source_size: (u32, u32),
+    canvas: &Canvas,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    tile_size: u32,
+    mut load: L,
+    mut write: W,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    L: FnMut(Region) -> Image<P>,
+    W: FnMut(Region, Image<P>),
+{
+    assert!(tile_size > 0, "The tile size must be strictly positive");
+    let mapping = reverse_mapping(controls_src, controls_dst, method);
+    for y in (0..canvas.height).step_by(tile_size as usize) {
+        for x in (0..canvas.width).step_by(tile_size as usize) {
+            let tile = Region {
+                x,
+                y,
+                width: tile_size.min(canvas.width - x),
+                height: tile_size.min(canvas.height - y),
+            };
+            let coordinates: Field = image_from_fn(tile.width, tile.height, |i, j| {
+                let (x2, y2) = mapping(canvas.position(x + i, y + j));
+                LumaA([x2, y2])
+            });
+            let window = source_window(&coordinates, source_size);
+            let img_window = load(window);
+            assert_eq!(
+                img_window.dimensions(),
+                (window.width, window.height),
+                "The loaded source window must have the size of the requested region"
+            );
+            let (left, top) = (window.x as f32, window.y as f32);
+            let warped = image_from_fn(tile.width, tile.height, |i, j| {
+                let [x2, y2] = coordinates.get_pixel(i, j).0;
+                sampler.sample(&img_window, x2 - left, y2 - top)
+            });
+            write(tile, warped);
+        }
+    }
+}
+
+/// Region of the source image containing all the coordinates of the field
+/// and their neighbors, clamped to the source image.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn source_window(coordinates: &Field, (width, height): (u32, u32)) -> Region {
+    let (right, bottom) = ((width.max(1) - 1) as f32, (height.max(1) - 1) as f32);
+    let (mut x_min, mut y_min) = (f32::INFINITY, f32::INFINITY);
+    let (mut x_max, mut y_max) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for pixel in coordinates.pixels() {
+        let [x, y] = pixel.0;
+        if x.is_finite() && y.is_finite() {
+            let (x, y) = (x.max(0.0).min(right), y.max(0.0).min(bottom));
+            x_min = x_min.min(x);
+            y_min = y_min.min(y);
+            x_max = x_max.max(x);
+            y_max = y_max.max(y);
+        }
+    }
+    if x_min > x_max {
+        // No finite coordinates, any pixel will do.
+        return Region {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+    }
+    let x = (x_min.floor() as u32).saturating_sub(WINDOW_MARGIN);
+    let y = (y_min.floor() as u32).saturating_sub(WINDOW_MARGIN);
+    let x_end = (x_max.ceil() as u32 + WINDOW_MARGIN).min(right as u32);
+    let y_end = (y_max.ceil() as u32 + WINDOW_MARGIN).min(bottom as u32);
+    Region {
+        x,
+        y,
+        width: x_end - x + 1,
+        height: y_end - y + 1,
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warps of images by a thin-plate spline (TPS) of the control points,
+//! to compare the smoothness of TPS and MLS on the same data.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{warp_dense, warp_sparse, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use moving_least_squares::ThinPlateSpline;
+use std::ops::{Add, Deref, Mul};
+
+/// Compute the image warped by the thin-plate spline
+/// moving the source control points to the destination ones.
+///
+/// Same as [`reverse_dense`](crate::reverse_dense), but with a TPS instead of MLS.
+///
+/// Panics if there are less than 3 control points, or if they are all colinear.
+pub fn tps_reverse_dense<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    tps_reverse_dense_with(img_src, controls_src, controls_dst, &Sampler::default())
+}
+
+/// Same as [`tps_reverse_dense`], but with the given options to sample the source image.
+pub fn tps_reverse_dense_with<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    sampler: &Sampler<P>,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let tps = reverse_tps(controls_src, controls_dst);
+    warp_dense(img_src, &|point| tps.deform(point), sampler)
+}
+
+/// Same as [`tps_reverse_dense`], but the spline is only evaluated on a sparse grid,
+/// as in [`reverse_sparse`](crate::reverse_sparse).
+///
+/// Evaluating the spline costs one term per control point, for every pixel,
+/// so the sparse warp is much faster with many control points.
+pub fn tps_reverse_sparse<P, C, V>(
+    img_src: &ImageBuffer<P, C>,
+    controls_src: &[(f32, f32)],
+    controls_dst: &[(f32, f32)],
+    subresolution_factor: u32,
+) -> Image<P>
+where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+{
+    let tps = reverse_tps(controls_src, controls_dst);
+    let sampler = Sampler::default();
+    warp_sparse(
+        img_src,
+        &|point| tps.deform(point),
+        subresolution_factor,
+        &sampler,
+    )
+}
+
+/// Thin-plate spline mapping the pixels of the warped image to the source image.
+fn reverse_tps(controls_src: &[(f32, f32)], controls_dst: &[(f32, f32)]) -> ThinPlateSpline {
+    match ThinPlateSpline::new(controls_dst, controls_src) {
+        Ok(tps) => tps,
+        Err(err) => panic!(
+            "The thin-plate spline needs at least 3 control points, not all colinear: {}",
+            err
+        ),
+    }
+}
@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warps of video clips driven by control points moving over time,
+//! such as landmarks tracked in every frame.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{reverse_mapping, warp_dense_into, DeformMethod, Image, Sampler};
+use image::{ImageBuffer, Pixel};
+use std::ops::{Add, Deref, Mul};
+
+/// Control points of every frame of a clip.
+///
+/// It is implemented for slices and vectors of control points per frame,
+/// and for closures of the frame index.
+pub trait Track {
+    /// Control points of the given frame.
+    fn controls(&self, frame: usize) -> Vec<(f32, f32)>;
+}
+
+impl<K: AsRef<[(f32, f32)]>> Track for [K] {
+    fn controls(&self, frame: usize) -> Vec<(f32, f32)> {
+        self[frame].as_ref().to_vec()
+    }
+}
+
+impl<K: AsRef<[(f32, f32)]>> Track for Vec<K> {
+    fn controls(&self, frame: usize) -> Vec<(f32, f32)> {
+        self[frame].as_ref().to_vec()
+    }
+}
+
+impl<F: Fn(usize) -> Vec<(f32, f32)>> Track for F {
+    fn controls(&self, frame: usize) -> Vec<(f32, f32)> {
+        self(frame)
+    }
+}
+
+/// Warp every frame of a clip with its own control points,
+/// giving each warped frame with its index to `f`.
+///
+/// The source track gives the control points in each frame,
+/// such as tracked landmarks, and the destination track where they are moved.
+/// The warped frames are written in the same buffer, to reuse its allocation,
+/// so `f` must copy or encode a warped frame before the next one.
+pub fn warp_tracked<P, C, V, I, S, D, F>(
+    frames: I,
+    track_src: &S,
+    track_dst: &D,
+    method: DeformMethod,
+    sampler: &Sampler<P>,
+    mut f: F,
+) where
+    P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+    P::Subpixel: Send + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+    V: Add<Output = V>,
+    f32: Mul<V, Output = V>,
+    I: IntoIterator<Item = ImageBuffer<P, C>>,
+    S: Track + ?Sized,
+    D: Track + ?Sized,
+    F: FnMut(usize, &Image<P>),
+{
+    let mut warped: Image<P> = ImageBuffer::new(0, 0);
+    for (index, frame) in frames.into_iter().enumerate() {
+        if warped.dimensions() != frame.dimensions() {
+            warped = ImageBuffer::new(frame.width(), frame.height());
+        }
+        let controls_src = track_src.controls(index);
+        let controls_dst = track_dst.controls(index);
+        let mapping = reverse_mapping(&controls_src, &controls_dst, method);
+        warp_dense_into(&frame, &mapping, sampler, &mut warped);
+        f(index, &warped);
+    }
+}
+
+/// Smooth the positions of the control points over time,
+/// with a Gaussian of standard deviation `sigma`, in frames,
+/// to remove the jitter of tracked landmarks.
+///
+/// The Gaussian is truncated at the first and last frames.
+/// All the frames must have the same number of control points.
+pub fn smooth_track<K: AsRef<[(f32, f32)]>>(track: &[K], sigma: f32) -> Vec<Vec<(f32, f32)>> {
+    let radius = (3.0 * sigma).ceil().max(0.0) as usize;
+    let weight = |offset: usize| match offset {
+        0 => 1.0,
+        _ => (-0.5 * (offset as f32 / sigma).powi(2)).exp(),
+    };
+    (0..track.len())
+        .map(|frame| {
+            let first = frame.saturating_sub(radius);
+            let last = (frame + radius).min(track.len() - 1);
+            let mut smoothed = vec![(0.0, 0.0); track[frame].as_ref().len()];
+            let mut total_weight = 0.0;
+            for (other, controls) in track.iter().enumerate().take(last + 1).skip(first) {
+                let w = weight(frame.max(other) - frame.min(other));
+                total_weight += w;
+                for (s, &(x, y)) in smoothed.iter_mut().zip(controls.as_ref()) {
+                    s.0 += w * x;
+                    s.1 += w * y;
+                }
+            }
+            smoothed
+                .iter()
+                .map(|&(x, y)| (x / total_weight, y / total_weight))
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Warp precomputed once, and applied to many images of the same size,
+//! such as successive frames of a video with fixed control points.
+
+use crate::interpolation::CanLinearInterpolate;
+use crate::{
+    fill_from_fn, frames_from_fn, image_from_fn, DeformMethod, Field, Image, Sampler,
+    SparseWarpGrid,
+};
+use image::{ImageBuffer, LumaA, Pixel};
+use std::ops::{Add, Deref, DerefMut, Mul};
+
+/// Location in the source image of every pixel of the warped image,
+/// computed once for the given control points.
+///
+/// Warping an image with it only samples the source image,
+/// without any more MLS computation.
+#[derive(Debug, Clone)]
+pub struct Warper {
+    width: u32,
+    height: u32,
+    /// Source location of every pixel, row by row.
+    coordinates: Vec<(f32, f32)>,
+}
+
+impl Warper {
+    /// Precompute the warp of images of the given size,
+    /// with the same arguments than [`reverse_sparse`](crate::reverse_sparse).
+    ///
+    /// A subresolution factor of 1 computes the MLS deformation of every pixel,
+    /// as [`reverse_dense`](crate::reverse_dense).
+    pub fn new(
+        width: u32,
+        height: u32,
+        controls_src: &[(f32, f32)],
+        controls_dst: &[(f32, f32)],
+        subresolution_factor: u32,
+        method: DeformMethod,
+    ) -> Self {
+        let grid = SparseWarpGrid::new(
+            width,
+            height,
+            controls_src,
+            controls_dst,
+            subresolution_factor,
+            method,
+        );
+        Self::from_grid(&grid)
+    }
+
+    /// Precompute the warp of all the pixels interpolated from the anchors of the grid.
+    pub fn from_grid(grid: &SparseWarpGrid) -> Self {
+        let (width, height) = grid.dimensions();
+        let coordinates = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| grid.source(x, y))
+            .collect();
+        Self {
+            width,
+            height,
+            coordinates,
+        }
+    }
+
+    /// Size of the images this warper applies to.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Location in the source image of the given pixel of the warped image.
+    pub fn source(&self, x: u32, y: u32) -> (f32, f32) {
+        self.coordinates[(y * self.width + x) as usize]
+    }
+
+    /// Coordinates field of the precomputed warp.
+    pub fn field(&self) -> Field {
+        image_from_fn(self.width, self.height, |x, y| {
+            let (x2, y2) = self.source(x, y);
+            LumaA([x2, y2])
+        })
+    }
+
+    /// Compute the warped image.
+    ///
+    /// Panics if the image does not have the dimensions of the warper.
+    pub fn warp<P, C, V>(&self, img_src: &ImageBuffer<P, C>, sampler: &Sampler<P>) -> Image<P>
+    where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        let mut warped = ImageBuffer::new(self.width, self.height);
+        self.warp_into(img_src, sampler, &mut warped);
+        warped
+    }
+
+    /// Same as [`warp`](Warper::warp), but writes the warped image into the given buffer,
+    /// to reuse its allocation.
+    ///
+    /// Panics if the image or the buffer do not have the dimensions of the warper.
+    pub fn warp_into<P, C, O, V>(
+        &self,
+        img_src: &ImageBuffer<P, C>,
+        sampler: &Sampler<P>,
+        warped: &mut ImageBuffer<P, O>,
+    ) where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        O: DerefMut<Target = [P::Subpixel]>,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        assert_eq!(
+            img_src.dimensions(),
+            self.dimensions(),
+            "The image must have the dimensions of the warper"
+        );
+        assert_eq!(
+            warped.dimensions(),
+            self.dimensions(),
+            "The warped image must have the dimensions of the warper"
+        );
+        fill_from_fn(warped, |x, y| {
+            let (x2, y2) = self.source(x, y);
+            sampler.sample(img_src, x2, y2)
+        })
+    }
+
+    /// Warp a sequence of frames, such as the frames of a video decoded one by one,
+    /// giving each warped frame with its index to `f`.
+    ///
+    /// The warped frames are written in the same buffer, to reuse its allocation,
+    /// so `f` must copy or encode a warped frame before the next one.
+    ///
+    /// Panics if a frame does not have the dimensions of the warper.
+    pub fn warp_each<P, C, V, I, F>(&self, frames: I, sampler: &Sampler<P>, mut f: F)
+    where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+        I: IntoIterator<Item = ImageBuffer<P, C>>,
+        F: FnMut(usize, &Image<P>),
+    {
+        let mut warped = ImageBuffer::new(self.width, self.height);
+        for (index, frame) in frames.into_iter().enumerate() {
+            self.warp_into(&frame, sampler, &mut warped);
+            f(index, &warped);
+        }
+    }
+
+    /// Warp all the frames, in parallel if the `rayon` feature is enabled.
+    ///
+    /// Panics if a frame does not have the dimensions of the warper.
+    pub fn warp_all<P, C, V>(
+        &self,
+        frames: &[ImageBuffer<P, C>],
+        sampler: &Sampler<P>,
+    ) -> Vec<Image<P>>
+    where
+        P: Pixel + CanLinearInterpolate<V, P> + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+        C: Deref<Target = [P::Subpixel]> + Sync,
+        V: Add<Output = V>,
+        f32: Mul<V, Output = V>,
+    {
+        frames_from_fn(frames.len(), |index| self.warp(&frames[index], sampler))
+    }
+}
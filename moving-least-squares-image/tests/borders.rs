@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Regression checks of the sampling up to the last rows and columns
+//! of the source image, including images of only 1 or 2 pixels wide.
+
+use image::{GrayImage, Luma};
+use moving_least_squares_image::{warp_dense, Interpolation, Sampler};
+
+/// Sizes of the checked images.
+const SIZES: [(u32, u32); 8] = [
+    (1, 1),
+    (1, 4),
+    (4, 1),
+    (2, 2),
+    (2, 5),
+    (5, 2),
+    (4, 3),
+    (8, 8),
+];
+
+/// Interpolations exact at the pixels.
+const INTERPOLATING: [Interpolation; 4] = [
+    Interpolation::Nearest,
+    Interpolation::Bilinear,
+    Interpolation::Bicubic,
+    Interpolation::Lanczos3,
+];
+
+/// All the interpolations.
+const ALL: [Interpolation; 6] = [
+    Interpolation::Nearest,
+    Interpolation::Bilinear,
+    Interpolation::Bicubic,
+    Interpolation::Mitchell,
+    Interpolation::BSpline,
+    Interpolation::Lanczos3,
+];
+
+/// Image where every pixel has a distinct value.
+fn gradient(width: u32, height: u32) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| Luma([(10 + 20 * x + 50 * y) as u8]))
+}
+
+#[test]
+fn identity_keeps_the_whole_image() {
+    for &interpolation in &INTERPOLATING {
+        let sampler = Sampler::default().interpolation(interpolation);
+        for &(width, height) in &SIZES {
+            let img = gradient(width, height);
+            let warped = warp_dense(&img, &|point| point, &sampler);
+            assert_eq!(
+                warped, img,
+                "{:?} identity warp of a {}x{} image",
+                interpolation, width, height
+            );
+        }
+    }
+}
+
+#[test]
+fn identity_keeps_constant_images_up_to_their_border() {
+    for &interpolation in &ALL {
+        let sampler = Sampler::default().interpolation(interpolation);
+        for &(width, height) in &SIZES {
+            let img = GrayImage::from_pixel(width, height, Luma([200]));
+            let warped = warp_dense(&img, &|point| point, &sampler);
+            assert_eq!(
+                warped, img,
+                "{:?} identity warp of a {}x{} image",
+                interpolation, width, height
+            );
+        }
+    }
+}
+
+#[test]
+fn samples_up_to_the_last_column_and_row() {
+    let img = gradient(2, 2);
+    let sampler = Sampler::default();
+    assert_eq!(sampler.sample(&img, 1.0, 1.0), Luma([80]));
+    assert_eq!(sampler.sample(&img, 1.0, 0.5), Luma([55]));
+    assert_eq!(sampler.sample(&img, 0.5, 1.0), Luma([70]));
+    assert_eq!(sampler.sample(&img, 0.5, 0.5), Luma([45]));
+}
+
+#[test]
+fn single_pixel_wide_images() {
+    let sampler = Sampler::default();
+    let column = gradient(1, 3);
+    assert_eq!(sampler.sample(&column, 0.0, 1.5), Luma([85]));
+    assert_eq!(sampler.sample(&column, 0.0, 2.0), Luma([110]));
+    let row = gradient(3, 1);
+    assert_eq!(sampler.sample(&row, 1.5, 0.0), Luma([40]));
+    assert_eq!(sampler.sample(&row, 2.0, 0.0), Luma([50]));
+    let pixel = gradient(1, 1);
+    assert_eq!(sampler.sample(&pixel, 0.0, 0.0), Luma([10]));
+}
+
+#[test]
+fn outside_of_the_image_is_zero() {
+    let sampler = Sampler::default();
+    for &(width, height) in &[(1, 1), (2, 2), (3, 2)] {
+        let img = gradient(width, height);
+        let right = width as f32 - 1.0;
+        let bottom = height as f32 - 1.0;
+        for &(x, y) in &[
+            (-0.1, 0.0),
+            (0.0, -0.1),
+            (right + 0.1, 0.0),
+            (0.0, bottom + 0.1),
+        ] {
+            assert_eq!(sampler.sample(&img, x, y), Luma([0]), "({}, {})", x, y);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Checks that the warped images are bit-identical to a serial computation,
+//! and with the `rayon` feature, whatever the number of threads.
+
+use image::{Rgb, RgbImage};
+use moving_least_squares::{self as mls, DeformMethod};
+use moving_least_squares_image::{
+    reverse_dense, reverse_dense_monitored, reverse_sparse, reverse_sparse_monitored, Monitor,
+    Sampler, SparseWarpGrid, Warper,
+};
+
+const METHODS: [DeformMethod; 3] = [
+    DeformMethod::Affine,
+    DeformMethod::Similarity,
+    DeformMethod::Rigid,
+];
+
+const CONTROLS_SRC: [(f32, f32); 5] = [
+    (10.0, 10.0),
+    (60.0, 12.0),
+    (35.0, 40.0),
+    (12.0, 55.0),
+    (70.0, 50.0),
+];
+
+const CONTROLS_DST: [(f32, f32); 5] = [
+    (14.0, 8.0),
+    (55.0, 18.0),
+    (38.0, 36.0),
+    (10.0, 58.0),
+    (70.0, 50.0),
+];
+
+/// Image with a different color at every pixel, so that any change of coordinates is visible.
+fn source_image() -> RgbImage {
+    RgbImage::from_fn(83, 67, |x, y| {
+        Rgb([(7 * x + 3 * y) as u8, (x * y) as u8, (x ^ (5 * y)) as u8])
+    })
+}
+
+/// Dense warp computed pixel by pixel, on a single thread.
+fn serial_dense(img: &RgbImage, method: DeformMethod) -> RgbImage {
+    let sampler = Sampler::default();
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let point = (x as f32, y as f32);
+        let (x2, y2) = mls::deform(&CONTROLS_DST, &CONTROLS_SRC, point, method);
+        sampler.sample(img, x2, y2)
+    })
+}
+
+/// Sparse warp computed pixel by pixel, on a single thread.
+fn serial_sparse(img: &RgbImage, factor: u32, method: DeformMethod) -> RgbImage {
+    let (width, height) = img.dimensions();
+    let grid = SparseWarpGrid::new(width, height, &CONTROLS_SRC, &CONTROLS_DST, factor, method);
+    let sampler = Sampler::default();
+    RgbImage::from_fn(width, height, |x, y| {
+        let (x2, y2) = grid.source(x, y);
+        sampler.sample(img, x2, y2)
+    })
+}
+
+/// All the parallel warps checked, with their names.
+fn parallel_warps(img: &RgbImage, method: DeformMethod) -> Vec<(&'static str, RgbImage)> {
+    let sampler = Sampler::default();
+    let monitor = Monitor::default();
+    let (width, height) = img.dimensions();
+    let warper = Warper::new(width, height, &CONTROLS_SRC, &CONTROLS_DST, 1, method);
+    vec![
+        (
+            "reverse_dense",
+            reverse_dense(img, &CONTROLS_SRC, &CONTROLS_DST, method),
+        ),
+        (
+            "reverse_dense_monitored",
+            reverse_dense_monitored(
+                img,
+                &CONTROLS_SRC,
+                &CONTROLS_DST,
+                method,
+                &sampler,
+                &monitor,
+            )
+            .unwrap(),
+        ),
+        ("Warper", warper.warp(img, &sampler)),
+        (
+            "reverse_sparse",
+            reverse_sparse(img, &CONTROLS_SRC, &CONTROLS_DST, 3, method),
+        ),
+        (
+            "reverse_sparse_monitored",
+            reverse_sparse_monitored(
+                img,
+                &CONTROLS_SRC,
+                &CONTROLS_DST,
+                3,
+                method,
+                &sampler,
+                &monitor,
+            )
+            .unwrap(),
+        ),
+    ]
+}
+
+/// Serial reference of each warp of [`parallel_warps`].
+fn serial_warps(img: &RgbImage, method: DeformMethod) -> Vec<RgbImage> {
+    let dense = serial_dense(img, method);
+    let sparse = serial_sparse(img, 3, method);
+    vec![dense.clone(), dense.clone(), dense, sparse.clone(), sparse]
+}
+
+#[test]
+fn warps_match_the_serial_computation() {
+    let img = source_image();
+    for &method in &METHODS {
+        let expected = serial_warps(&img, method);
+        for ((name, warped), expected) in parallel_warps(&img, method).into_iter().zip(expected) {
+            assert!(warped == expected, "{} with {:?}", name, method);
+        }
+    }
+}
+
+#[test]
+fn warps_are_identical_when_repeated() {
+    let img = source_image();
+    for &method in &METHODS {
+        let first = parallel_warps(&img, method);
+        let second = parallel_warps(&img, method);
+        for ((name, a), (_, b)) in first.into_iter().zip(second) {
+            assert!(a == b, "{} with {:?}", name, method);
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn warps_do_not_depend_on_the_number_of_threads() {
+    use moving_least_squares_image::rayon::ThreadPoolBuilder;
+
+    let img = source_image();
+    for &method in &METHODS {
+        let expected = serial_warps(&img, method);
+        for &threads in &[1, 2, 3, 8] {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let warps = pool.install(|| parallel_warps(&img, method));
+            for ((name, warped), expected) in warps.into_iter().zip(&expected) {
+                assert!(
+                    &warped == expected,
+                    "{} with {:?} on {} threads",
+                    name,
+                    method,
+                    threads
+                );
+            }
+        }
+    }
+}
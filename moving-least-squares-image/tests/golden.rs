@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Regression tests of the warped images against stored expected outputs,
+//! within a tolerance on the largest channel difference and on the PSNR,
+//! so that refactors of the math or of the interpolation are caught.
+//!
+//! After an intended change of the outputs, rewrite them with
+//! `UPDATE_GOLDEN=1 cargo test --test golden`, and review them before committing.
+
+use image::RgbImage;
+use moving_least_squares_image::{
+    forward_dense, reverse_dense, reverse_dense_ewa, reverse_dense_fixed,
+    reverse_dense_linear_light, reverse_dense_with, reverse_ffd, reverse_mesh, reverse_sparse,
+    tps_reverse_dense, DeformMethod, Interpolation, Sampler,
+};
+use std::path::PathBuf;
+
+/// Largest accepted difference of a channel.
+const MAX_DELTA: u8 = 3;
+
+/// Smallest accepted PSNR, in dB.
+const MIN_PSNR: f64 = 45.0;
+
+const CONTROLS_SRC: [(f32, f32); 4] = [(8.0, 8.0), (56.0, 6.0), (40.0, 22.0), (10.0, 40.0)];
+const CONTROLS_DST: [(f32, f32); 4] = [(12.0, 6.0), (54.0, 10.0), (34.0, 26.0), (10.0, 40.0)];
+
+/// Path of a fixture image.
+fn fixture(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.join("tests")
+        .join("golden")
+        .join(format!("{}.ppm", name))
+}
+
+/// Source image of all the warps.
+fn source() -> RgbImage {
+    image::open(fixture("source")).unwrap().into_rgb8()
+}
+
+/// Differences between two images of the same size.
+struct Diff {
+    /// Largest difference of a channel.
+    max_delta: u8,
+    /// Peak signal to noise ratio, in dB, infinite for identical images.
+    psnr: f64,
+}
+
+fn diff(a: &RgbImage, b: &RgbImage) -> Diff {
+    let deltas: Vec<u8> = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&u, &v)| u.max(v) - u.min(v))
+        .collect();
+    let max_delta = deltas.iter().copied().max().unwrap_or(0);
+    let squares: f64 = deltas.iter().map(|&d| f64::from(d) * f64::from(d)).sum();
+    let mse = squares / deltas.len().max(1) as f64;
+    let psnr = 10.0 * (255.0 * 255.0 / mse).log10();
+    Diff { max_delta, psnr }
+}
+
+/// Compare the warped image to its stored expected output,
+/// or rewrite the expected output if `UPDATE_GOLDEN` is set.
+fn check_golden(name: &str, warped: &RgbImage) {
+    let path = fixture(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        warped.save(&path).unwrap();
+        return;
+    }
+    let expected = match image::open(&path) {
+        Ok(img) => img.into_rgb8(),
+        Err(err) => panic!(
+            "{}: cannot open {:?} ({}), write it with UPDATE_GOLDEN=1",
+            name, path, err
+        ),
+    };
+    assert_eq!(warped.dimensions(), expected.dimensions(), "{}", name);
+    let d = diff(warped, &expected);
+    assert!(
+        d.max_delta <= MAX_DELTA && d.psnr >= MIN_PSNR,
+        "{}: max delta {} (at most {}), PSNR {:.1} dB (at least {})",
+        name,
+        d.max_delta,
+        MAX_DELTA,
+        d.psnr,
+        MIN_PSNR
+    );
+}
+
+#[test]
+fn dense_affine() {
+    let method = DeformMethod::Affine;
+    let warped = reverse_dense(&source(), &CONTROLS_SRC, &CONTROLS_DST, method);
+    check_golden("dense_affine", &warped);
+}
+
+#[test]
+fn dense_similarity() {
+    let method = DeformMethod::Similarity;
+    let warped = reverse_dense(&source(), &CONTROLS_SRC, &CONTROLS_DST, method);
+    check_golden("dense_similarity", &warped);
+}
+
+#[test]
+fn dense_rigid() {
+    let method = DeformMethod::Rigid;
+    let warped = reverse_dense(&source(), &CONTROLS_SRC, &CONTROLS_DST, method);
+    check_golden("dense_rigid", &warped);
+}
+
+#[test]
+fn dense_bicubic() {
+    let sampler = Sampler::default().interpolation(Interpolation::Bicubic);
+    let method = DeformMethod::Rigid;
+    let warped = reverse_dense_with(&source(), &CONTROLS_SRC, &CONTROLS_DST, method, &sampler);
+    check_golden("dense_bicubic", &warped);
+}
+
+#[test]
+fn sparse() {
+    let method = DeformMethod::Rigid;
+    let warped = reverse_sparse(&source(), &CONTROLS_SRC, &CONTROLS_DST, 4, method);
+    check_golden("sparse", &warped);
+}
+
+#[test]
+fn mesh() {
+    let method = DeformMethod::Rigid;
+    let warped = reverse_mesh(&source(), &CONTROLS_SRC, &CONTROLS_DST, 8, method);
+    check_golden("mesh", &warped);
+}
+
+#[test]
+fn forward() {
+    let method = DeformMethod::Rigid;
+    let warped = forward_dense(&source(), &CONTROLS_SRC, &CONTROLS_DST, method);
+    check_golden("forward", &warped);
+}
+
+#[test]
+fn ewa() {
+    let sampler = Sampler::default();
+    let method = DeformMethod::Rigid;
+    let warped = reverse_dense_ewa(&source(), &CONTROLS_SRC, &CONTROLS_DST, method, &sampler);
+    check_golden("ewa", &warped);
+}
+
+#[test]
+fn fixed_point() {
+    let method = DeformMethod::Rigid;
+    let warped = reverse_dense_fixed(&source(), &CONTROLS_SRC, &CONTROLS_DST, method);
+    check_golden("fixed_point", &warped);
+}
+
+#[test]
+fn linear_light() {
+    let method = DeformMethod::Rigid;
+    let warped = reverse_dense_linear_light(&source(), &CONTROLS_SRC, &CONTROLS_DST, method);
+    check_golden("linear_light", &warped);
+}
+
+#[test]
+fn ffd() {
+    let method = DeformMethod::Rigid;
+    let warped = reverse_ffd(&source(), &CONTROLS_SRC, &CONTROLS_DST, 8, method);
+    check_golden("ffd", &warped);
+}
+
+#[test]
+fn tps() {
+    let warped = tps_reverse_dense(&source(), &CONTROLS_SRC, &CONTROLS_DST);
+    check_golden("tps", &warped);
+}
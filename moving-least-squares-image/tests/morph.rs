@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Checks of the two-image morph at its ends and in between,
+//! for the warp of the features and the cross-dissolve of the colors.
+
+use image::{GrayImage, Luma};
+use moving_least_squares_image::{morph, DeformMethod};
+
+/// Control points around a feature at the given location,
+/// so that moving all of them translates the feature.
+fn controls_around((x, y): (f32, f32)) -> Vec<(f32, f32)> {
+    vec![(x - 8.0, y - 8.0), (x + 8.0, y - 8.0), (x, y + 8.0)]
+}
+
+/// Dark image with a bright square of 3x3 pixels centered at the given location.
+fn square_at((cx, cy): (u32, u32)) -> GrayImage {
+    GrayImage::from_fn(40, 20, |x, y| {
+        let inside = x + 1 >= cx && x <= cx + 1 && y + 1 >= cy && y <= cy + 1;
+        Luma([if inside { 200 } else { 0 }])
+    })
+}
+
+/// Largest channel difference between two images of the same size.
+fn max_difference(a: &GrayImage, b: &GrayImage) -> u8 {
+    assert_eq!(a.dimensions(), b.dimensions());
+    let diffs = a.pixels().zip(b.pixels()).map(|(p, q)| {
+        let (p, q) = (p.0[0], q.0[0]);
+        p.max(q) - p.min(q)
+    });
+    diffs.max().unwrap_or(0)
+}
+
+#[test]
+fn ends_are_the_input_images() {
+    let img_a = square_at((10, 10));
+    let img_b = square_at((30, 10));
+    let controls_a = controls_around((10.0, 10.0));
+    let controls_b = controls_around((30.0, 10.0));
+    let start = morph(
+        &img_a,
+        &img_b,
+        &controls_a,
+        &controls_b,
+        0.0,
+        DeformMethod::Affine,
+    );
+    let end = morph(
+        &img_a,
+        &img_b,
+        &controls_a,
+        &controls_b,
+        1.0,
+        DeformMethod::Affine,
+    );
+    assert!(max_difference(&start, &img_a) <= 1);
+    assert!(max_difference(&end, &img_b) <= 1);
+}
+
+#[test]
+fn features_move_along_the_controls() {
+    let img_a = square_at((10, 10));
+    let img_b = square_at((30, 10));
+    let controls_a = controls_around((10.0, 10.0));
+    let controls_b = controls_around((30.0, 10.0));
+    let middle = morph(
+        &img_a,
+        &img_b,
+        &controls_a,
+        &controls_b,
+        0.5,
+        DeformMethod::Affine,
+    );
+    // Both squares are warped onto the middle one, so it is not dissolved.
+    assert!(max_difference(&middle, &square_at((20, 10))) <= 1);
+}
+
+#[test]
+fn colors_are_cross_dissolved() {
+    let img_a = GrayImage::from_pixel(16, 16, Luma([0]));
+    let img_b = GrayImage::from_pixel(16, 16, Luma([200]));
+    let controls = controls_around((8.0, 8.0));
+    for &(t, expected) in &[(0.25, 50), (0.5, 100), (0.75, 150)] {
+        let morphed = morph(&img_a, &img_b, &controls, &controls, t, DeformMethod::Rigid);
+        let pixel = morphed.get_pixel(8, 8).0[0];
+        assert!((i32::from(pixel) - expected).abs() <= 1, "t = {}", t);
+    }
+}
+
+#[test]
+fn morph_has_the_size_of_the_first_image() {
+    let img_a = GrayImage::from_pixel(16, 12, Luma([10]));
+    let img_b = GrayImage::from_pixel(30, 25, Luma([90]));
+    let controls = controls_around((8.0, 6.0));
+    let morphed = morph(
+        &img_a,
+        &img_b,
+        &controls,
+        &controls,
+        0.5,
+        DeformMethod::Rigid,
+    );
+    assert_eq!(morphed.dimensions(), (16, 12));
+    assert_eq!(morphed.get_pixel(15, 11).0[0], 50);
+}
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Checks of the blending of the mosaics, which must leave identical images unchanged.
+
+use image::{Rgb, RgbImage};
+use moving_least_squares_image::{reverse_mosaic, Blending, Canvas, DeformMethod, Tile};
+
+const BLENDINGS: [Blending; 4] = [
+    Blending::Feather,
+    Blending::Multiband(1),
+    Blending::Multiband(3),
+    Blending::Multiband(6),
+];
+
+const CONTROLS: [(f32, f32); 3] = [(0.0, 0.0), (30.0, 0.0), (0.0, 30.0)];
+
+/// Control points translated by the given offset.
+fn translated((dx, dy): (f32, f32)) -> Vec<(f32, f32)> {
+    CONTROLS.iter().map(|&(x, y)| (x + dx, y + dy)).collect()
+}
+
+#[test]
+fn overlapping_constant_tiles_stay_constant() {
+    let color = Rgb([200, 120, 40]);
+    let img = RgbImage::from_pixel(40, 40, color);
+    let (left, right) = (translated((4.0, 10.0)), translated((20.0, 14.0)));
+    let tiles = [
+        Tile {
+            img: &img,
+            controls_src: &CONTROLS,
+            controls_dst: &left,
+        },
+        Tile {
+            img: &img,
+            controls_src: &CONTROLS,
+            controls_dst: &right,
+        },
+    ];
+    let canvas = Canvas::new(64, 64);
+    for &blending in &BLENDINGS {
+        let mosaic = reverse_mosaic(&tiles, &canvas, DeformMethod::Rigid, blending);
+        for (x, y, &pixel) in mosaic.enumerate_pixels() {
+            let inside = |offset: (u32, u32)| {
+                (offset.0..offset.0 + 40).contains(&x) && (offset.1..offset.1 + 40).contains(&y)
+            };
+            let expected = if inside((4, 10)) || inside((20, 14)) {
+                color
+            } else {
+                Rgb([0, 0, 0])
+            };
+            assert_eq!(pixel, expected, "{:?} at ({}, {})", blending, x, y);
+        }
+    }
+}
+
+#[test]
+fn no_tile_gives_a_zero_image() {
+    let tiles: [Tile<'_, Rgb<u8>, Vec<u8>>; 0] = [];
+    let canvas = Canvas::new(16, 8);
+    for &blending in &BLENDINGS {
+        let mosaic = reverse_mosaic(&tiles, &canvas, DeformMethod::Rigid, blending);
+        assert_eq!(mosaic, RgbImage::new(16, 8), "{:?}", blending);
+    }
+}
+
+#[test]
+fn empty_tiles_are_skipped() {
+    let color = Rgb([200, 120, 40]);
+    let img = RgbImage::from_pixel(40, 40, color);
+    let (wide, tall) = (RgbImage::new(40, 0), RgbImage::new(0, 40));
+    // Half pixel offsets, to sample the empty images right on their border.
+    let offset = translated((4.5, 10.5));
+    let tile = |img| Tile {
+        img,
+        controls_src: &CONTROLS,
+        controls_dst: &offset,
+    };
+    let canvas = Canvas::new(64, 64);
+    for &blending in &BLENDINGS {
+        let alone = reverse_mosaic(&[tile(&img)], &canvas, DeformMethod::Rigid, blending);
+        let tiles = [tile(&wide), tile(&img), tile(&tall)];
+        let mosaic = reverse_mosaic(&tiles, &canvas, DeformMethod::Rigid, blending);
+        assert_eq!(mosaic, alone, "{:?}", blending);
+        let empty = reverse_mosaic(
+            &[tile(&wide), tile(&tall)],
+            &canvas,
+            DeformMethod::Rigid,
+            blending,
+        );
+        assert_eq!(empty, RgbImage::new(64, 64), "{:?}", blending);
+    }
+}
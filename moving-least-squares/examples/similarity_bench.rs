@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compare the timings of the matrix and complex number similarity deformations.
+//!
+//! Run with `cargo run --release --example similarity_bench`.
+
+use moving_least_squares as mls;
+use std::time::Instant;
+
+fn main() {
+    // Control points on a circle, displaced by a twist.
+    let nb_controls = 200;
+    let (controls_p, controls_q): (Vec<_>, Vec<_>) = (0..nb_controls)
+        .map(|i| {
+            let angle = i as f32 * std::f32::consts::TAU / nb_controls as f32;
+            let p = (500.0 + 300.0 * angle.cos(), 500.0 + 300.0 * angle.sin());
+            let twist = angle + 0.2 * angle.sin();
+            let q = (500.0 + 320.0 * twist.cos(), 500.0 + 280.0 * twist.sin());
+            (p, q)
+        })
+        .unzip();
+    let points: Vec<(f32, f32)> = (0..100)
+        .flat_map(|y| (0..100).map(move |x| (10.0 * x as f32, 10.0 * y as f32)))
+        .collect();
+
+    let time = |name: &str, f: &dyn Fn((f32, f32)) -> (f32, f32)| {
+        let start = Instant::now();
+        let checksum: f32 = points.iter().map(|&v| f(v).0).sum();
+        println!("{}: {:?} (checksum {})", name, start.elapsed(), checksum);
+    };
+    time("matrices", &|v| {
+        mls::deform_similarity(&controls_p, &controls_q, v)
+    });
+    time("complex ", &|v| {
+        mls::deform_similarity_complex(&controls_p, &controls_q, v)
+    });
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Evaluation of the deformation on many points,
+//! parallelized if the `rayon` feature is enabled.
+
+use crate::{Centered, DeformError, DeformMethod, MlsOptions, Scalar};
+
+/// Compute the deformation of all the given points, such as the vertices of a mesh
+/// or a point cloud, returned in the same order.
+///
+/// This is faster than calling [`deform`](crate::deform) for each point
+/// since the same internal buffers are reused for all points,
+/// and the points are split across cores if the `rayon` feature is enabled.
+pub fn deform_all<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    points: &[(T, T)],     // v in the paper
+    method: DeformMethod,
+) -> Vec<(T, T)> {
+    MlsOptions::new(method).deform_all(controls_p, controls_q, points)
+}
+
+/// Same as [`deform_all`], but returns an error instead of NaN or meaningless results
+/// when the configuration is degenerate at any of the points, as [`try_deform`](crate::try_deform).
+///
+/// It never panics, so it is suited to long-running servers,
+/// where a panic in a worker thread would abort the whole batch.
+pub fn try_deform_all<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    points: &[(T, T)],     // v in the paper
+    method: DeformMethod,
+) -> Result<Vec<(T, T)>, DeformError> {
+    MlsOptions::new(method).try_deform_all(controls_p, controls_q, points)
+}
+
+/// Behaves like `items.iter().map(f).collect()`,
+/// but will be parallelized if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map_all<I, O, F>(items: &[I], f: F) -> Vec<O>
+where
+    F: Fn(&I) -> O,
+{
+    items.iter().map(f).collect()
+}
+
+/// Behaves like `items.iter().map(f).collect()`,
+/// but will be parallelized if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+pub(crate) fn map_all<I, O, F>(items: &[I], f: F) -> Vec<O>
+where
+    I: Sync,
+    O: Send,
+    F: Fn(&I) -> O + Send + Sync,
+{
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+    items.par_iter().map(f).collect()
+}
+
+/// Behaves like `items.iter().map(f).collect()`, with internal buffers created by `buffers`
+/// and reused between items,
+/// but will be parallelized if the `rayon` feature is enabled.
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map_with_buffers<T, I, O, B, F>(items: &[I], buffers: B, f: F) -> Vec<O>
+where
+    T: Scalar,
+    B: Fn() -> Centered<T>,
+    F: Fn(&mut Centered<T>, &I) -> O,
+{
+    let mut buffers = buffers();
+    items.iter().map(|item| f(&mut buffers, item)).collect()
+}
+
+/// Behaves like `items.iter().map(f).collect()`, with internal buffers created by `buffers`
+/// and reused between items,
+/// but will be parallelized if the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+pub(crate) fn map_with_buffers<T, I, O, B, F>(items: &[I], buffers: B, f: F) -> Vec<O>
+where
+    T: Scalar,
+    B: Fn() -> Centered<T> + Send + Sync,
+    I: Sync,
+    O: Send,
+    F: Fn(&mut Centered<T>, &I) -> O + Send + Sync,
+{
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+    // One set of buffers per rayon job, instead of one per item.
+    items
+        .par_iter()
+        .map_init(buffers, |buffers, item| f(buffers, item))
+        .collect()
+}
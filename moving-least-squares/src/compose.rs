@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Composition of multiple MLS deformations.
+
+use crate::batch::map_all;
+use crate::{deform, DeformMethod, Scalar};
+
+/// One MLS deformation, given by its original control points p,
+/// its displaced control points q, and its method.
+pub type Deformation<'a, T> = (&'a [(T, T)], &'a [(T, T)], DeformMethod);
+
+/// Move a given point through a chain of deformations,
+/// applying the first one, then the second one on the result, and so on.
+pub fn deform_composed<T: Scalar>(deformations: &[Deformation<T>], point: (T, T)) -> (T, T) {
+    deformations
+        .iter()
+        .fold(point, |v, &(controls_p, controls_q, method)| {
+            deform(controls_p, controls_q, v, method)
+        })
+}
+
+/// Bake a chain of deformations into a single set of control points,
+/// by sampling the composed deformation at the given points.
+///
+/// The samples are the new original control points,
+/// and the returned points are their displaced locations.
+/// The MLS deformation of these control points approximates the composition,
+/// exactly at the samples and more loosely in between,
+/// so the samples should cover the domain of interest,
+/// typically with a regular grid.
+pub fn bake_composed<T: Scalar>(
+    deformations: &[Deformation<T>],
+    samples: &[(T, T)],
+) -> Vec<(T, T)> {
+    map_all(samples, |&point| deform_composed(deformations, point))
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Owned set of control points, to save and load handle configurations.
+
+use crate::{deform, DeformMethod, Scalar};
+
+/// Pairs of original (source) and displaced (destination) control points.
+///
+/// With the `serde` feature, it can be serialized to save handle configurations,
+/// for example in JSON or RON.
+/// Deserializing fails if there are not as many displaced control points as original ones.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawControlPoints<T>"))]
+pub struct ControlPoints<T> {
+    /// Original control points, p in the paper.
+    src: Vec<(T, T)>,
+    /// Displaced control points, q in the paper.
+    dst: Vec<(T, T)>,
+}
+
+/// Deserialized control points, before checking their lengths.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawControlPoints<T> {
+    src: Vec<(T, T)>,
+    dst: Vec<(T, T)>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> std::convert::TryFrom<RawControlPoints<T>> for ControlPoints<T> {
+    type Error = crate::DeformError;
+
+    fn try_from(raw: RawControlPoints<T>) -> Result<Self, Self::Error> {
+        if raw.src.len() == raw.dst.len() {
+            Ok(Self {
+                src: raw.src,
+                dst: raw.dst,
+            })
+        } else {
+            Err(crate::DeformError::MismatchedLengths)
+        }
+    }
+}
+
+impl<T: Scalar> ControlPoints<T> {
+    /// Create a set of control points.
+    ///
+    /// Panics if `src` and `dst` do not have the same length.
+    pub fn new(src: Vec<(T, T)>, dst: Vec<(T, T)>) -> Self {
+        assert_eq!(
+            src.len(),
+            dst.len(),
+            "There must be as many displaced control points as original ones"
+        );
+        Self { src, dst }
+    }
+
+    /// Same as [`ControlPoints::new`], but returns `None` instead of panicking
+    /// if `src` and `dst` do not have the same length.
+    pub fn try_new(src: Vec<(T, T)>, dst: Vec<(T, T)>) -> Option<Self> {
+        if src.len() == dst.len() {
+            Some(Self { src, dst })
+        } else {
+            None
+        }
+    }
+
+    /// Original control points, p in the paper.
+    pub fn src(&self) -> &[(T, T)] {
+        &self.src
+    }
+
+    /// Displaced control points, q in the paper.
+    pub fn dst(&self) -> &[(T, T)] {
+        &self.dst
+    }
+
+    /// Add a pair of original and displaced control points.
+    pub fn push(&mut self, src: (T, T), dst: (T, T)) {
+        self.src.push(src);
+        self.dst.push(dst);
+    }
+
+    /// Number of control point pairs.
+    pub fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    /// True if there is no control point.
+    pub fn is_empty(&self) -> bool {
+        self.src.is_empty()
+    }
+
+    /// Same control points, with source and destination swapped.
+    pub fn reversed(&self) -> Self {
+        Self {
+            src: self.dst.clone(),
+            dst: self.src.clone(),
+        }
+    }
+
+    /// Move a given point with the deformation of these control points, see [`deform`].
+    pub fn deform(&self, point: (T, T), method: DeformMethod) -> (T, T) {
+        deform(&self.src, &self.dst, point, method)
+    }
+}
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Damping of the deformation outside of the convex hull of the control points.
+//!
+//! Far from all control points, the deformation extrapolates the dominant
+//! affine transformation or similarity, which can send border pixels far away.
+//! Here the displacement is instead progressively reduced to zero
+//! with the distance to the convex hull of the original control points.
+
+use crate::{deform, DeformMethod, Point, Scalar};
+
+/// Damping of the displacements with the distance to the convex hull of the control points.
+pub struct HullDamping<T> {
+    /// Convex hull of the original control points, in counterclockwise order.
+    hull: Vec<Point<T>>,
+    /// Distance to the hull beyond which the deformation is the identity.
+    distance: T,
+}
+
+impl<T: Scalar> HullDamping<T> {
+    /// Compute the convex hull of the original control points `controls_p`.
+    /// Displacements are damped from the hull border, where they are unchanged,
+    /// to the given distance to the hull, beyond which they are zero.
+    pub fn new(controls_p: &[(T, T)], distance: T) -> Self {
+        Self {
+            hull: convex_hull(controls_p),
+            distance,
+        }
+    }
+
+    /// Damping factor at the given point, between 0 and 1.
+    ///
+    /// It is 1 inside the hull and decreases smoothly to 0 at the damping distance.
+    pub fn factor(&self, point: (T, T)) -> T {
+        let d = distance_to_hull(&self.hull, point.into());
+        if d >= self.distance {
+            return T::zero();
+        }
+        // Smoothstep of the relative distance to the damping limit.
+        let s = T::one() - d / self.distance;
+        s * s * (T::one() + T::one() + T::one() - s - s)
+    }
+
+    /// Damp the displacement from `point` to its deformed location `deformed`.
+    pub fn damp(&self, point: (T, T), deformed: (T, T)) -> (T, T) {
+        let v = Point::from(point);
+        (v + (Point::from(deformed) - v) * self.factor(point)).into()
+    }
+}
+
+/// Same as [`deform`], but with the displacement damped outside of the convex hull
+/// of the original control points, down to the identity at the given distance of the hull.
+///
+/// To deform many points, create a [`HullDamping`] once instead.
+pub fn deform_damped<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    distance: T,
+) -> (T, T) {
+    let damping = HullDamping::new(controls_p, distance);
+    damping.damp(point, deform(controls_p, controls_q, point, method))
+}
+
+/// Convex hull of the points, in counterclockwise order (Andrew's monotone chain).
+fn convex_hull<T: Scalar>(points: &[(T, T)]) -> Vec<Point<T>> {
+    let mut sorted: Vec<Point<T>> = points.iter().map(|&p| p.into()).collect();
+    sorted.sort_by(|a, b| {
+        (a.x, a.y)
+            .partial_cmp(&(b.x, b.y))
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+    sorted.dedup_by(|a, b| a.x == b.x && a.y == b.y);
+    if sorted.len() < 3 {
+        return sorted;
+    }
+    // Lower hull, then upper hull, without their last points,
+    // which are the first ones of the other half.
+    let mut hull = half_hull(sorted.iter().copied());
+    let mut upper = half_hull(sorted.iter().rev().copied());
+    hull.pop();
+    upper.pop();
+    hull.extend(upper);
+    hull
+}
+
+/// Half of the convex hull of points sorted along x, only keeping left turns.
+fn half_hull<T: Scalar>(sorted: impl Iterator<Item = Point<T>>) -> Vec<Point<T>> {
+    let mut chain: Vec<Point<T>> = Vec::new();
+    for p in sorted {
+        while chain.len() >= 2
+            && cross(chain[chain.len() - 2], chain[chain.len() - 1], p) <= T::zero()
+        {
+            chain.pop();
+        }
+        chain.push(p);
+    }
+    chain
+}
+
+/// Cross product of (b - a) and (c - a), positive for a left turn.
+fn cross<T: Scalar>(a: Point<T>, b: Point<T>, c: Point<T>) -> T {
+    let (ab, ac) = (b - a, c - a);
+    ab.x * ac.y - ab.y * ac.x
+}
+
+/// Distance from a point to a convex polygon in counterclockwise order,
+/// 0 if the point is inside.
+fn distance_to_hull<T: Scalar>(hull: &[Point<T>], v: Point<T>) -> T {
+    let n = hull.len();
+    if n == 0 {
+        return T::zero();
+    }
+    let edges = || (0..n).map(|i| (hull[i], hull[(i + 1) % n]));
+    if n >= 3 && edges().all(|(a, b)| cross(a, b, v) >= T::zero()) {
+        return T::zero();
+    }
+    edges()
+        .map(|(a, b)| {
+            // Distance to the segment [a, b].
+            let ab = b - a;
+            let length = ab.sqr_norm();
+            let t = if length > T::zero() {
+                let t = (v - a).dot(ab) / length;
+                if t > T::one() {
+                    T::one()
+                } else {
+                    t.max(T::zero())
+                }
+            } else {
+                T::zero()
+            };
+            (v - (a + ab * t)).sqr_norm()
+        })
+        .fold(None, |min: Option<T>, d| match min {
+            Some(m) if m <= d => Some(m),
+            _ => Some(d),
+        })
+        .map_or(T::zero(), |d| d.sqrt())
+}
@@ -0,0 +1,462 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Moving least squares deformation of 3D points.
+//!
+//! This uses the same handles interface as the 2D deformations,
+//! with (x, y, z) triples instead of (x, y) pairs.
+//! The affine deformation is the direct generalization of the 2D one.
+//! For the similarity and rigid deformations, there is no closed form
+//! in 3D so the optimal rotation is computed with the quaternion method
+//! of Horn ("Closed-form solution of absolute orientation using unit quaternions", 1987).
+
+use crate::{DeformMethod, Scalar};
+use core::iter::Sum;
+use core::ops::{Add, Mul, Sub};
+
+/// Move a given 3D point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is the one of the chosen method.
+pub fn deform<T: Scalar>(
+    controls_p: &[(T, T, T)], // p in the paper
+    controls_q: &[(T, T, T)], // q in the paper
+    point: (T, T, T),         // v in the paper
+    method: DeformMethod,
+) -> (T, T, T) {
+    match method {
+        DeformMethod::Affine => deform_affine(controls_p, controls_q, point),
+        DeformMethod::Similarity => deform_similarity(controls_p, controls_q, point),
+        DeformMethod::Rigid => deform_rigid(controls_p, controls_q, point),
+    }
+}
+
+/// Move a given 3D point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is an affine 3D transformation.
+/// It requires at least 4 control points that are not all coplanar.
+pub fn deform_affine<T: Scalar>(
+    controls_p: &[(T, T, T)], // p in the paper
+    controls_q: &[(T, T, T)], // q in the paper
+    point: (T, T, T),         // v in the paper
+) -> (T, T, T) {
+    let v = Point3::from(point);
+    let c = match centered(controls_p, controls_q, v) {
+        Ok(c) => c,
+        Err(index) => return controls_q[index],
+    };
+
+    // Compute the affine matrix M.
+    // mp is a 3x3 matrix.
+    let mp: Mat3<T> = c
+        .w_all
+        .iter()
+        .zip(&c.p_hat)
+        .map(|(&w, &p)| p.times_transpose(p) * w)
+        .sum();
+    // Compute the second part of M.
+    let mq: Mat3<T> = c
+        .w_all
+        .iter()
+        .zip(&c.p_hat)
+        .zip(&c.q_hat)
+        .map(|((&w, &ph), &qh)| (ph * w).times_transpose(qh))
+        .sum();
+
+    // Finally compute the projection of our original point.
+    ((v - c.p_star).transpose_mul(mp.inv()).transpose_mul(mq) + c.q_star).into()
+}
+
+/// Move a given 3D point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is a 3D similarity.
+pub fn deform_similarity<T: Scalar>(
+    controls_p: &[(T, T, T)], // p in the paper
+    controls_q: &[(T, T, T)], // q in the paper
+    point: (T, T, T),         // v in the paper
+) -> (T, T, T) {
+    let v = Point3::from(point);
+    let c = match centered(controls_p, controls_q, v) {
+        Ok(c) => c,
+        Err(index) => return controls_q[index],
+    };
+
+    // Compute mu_s.
+    let mu_s: T = c
+        .w_all
+        .iter()
+        .zip(&c.p_hat)
+        .map(|(&wi, pi)| wi * pi.sqr_norm())
+        .sum();
+
+    // The optimal scale is the largest eigen value divided by mu_s.
+    let (rotation, eigen_value) = optimal_rotation(&c);
+    let scale = eigen_value / mu_s;
+
+    // Finally compute the projection of our original point.
+    (rotation.mul_vec(v - c.p_star) * scale + c.q_star).into()
+}
+
+/// Move a given 3D point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is a 3D rigid deformation.
+pub fn deform_rigid<T: Scalar>(
+    controls_p: &[(T, T, T)], // p in the paper
+    controls_q: &[(T, T, T)], // q in the paper
+    point: (T, T, T),         // v in the paper
+) -> (T, T, T) {
+    let v = Point3::from(point);
+    let c = match centered(controls_p, controls_q, v) {
+        Ok(c) => c,
+        Err(index) => return controls_q[index],
+    };
+
+    // Finally compute the projection of our original point.
+    let (rotation, _) = optimal_rotation(&c);
+    (rotation.mul_vec(v - c.p_star) + c.q_star).into()
+}
+
+/// Weights, centroids and centered control points for a given point v.
+struct Centered<T> {
+    w_all: Vec<T>,
+    p_star: Point3<T>,
+    q_star: Point3<T>,
+    p_hat: Vec<Point3<T>>,
+    q_hat: Vec<Point3<T>>,
+}
+
+/// Compute the weights, the centroids p* and q*, as well as p_hat and q_hat.
+///
+/// Returns the index of the control point coinciding with v if there is one.
+fn centered<T: Scalar>(
+    controls_p: &[(T, T, T)],
+    controls_q: &[(T, T, T)],
+    v: Point3<T>,
+) -> Result<Centered<T>, usize> {
+    // The weight of a given control point depends on its distance to the current point.
+    // CAREFUL: this w can go to infinity.
+    let w_all: Vec<T> = controls_p
+        .iter()
+        .map(|&p| T::one() / (Point3::from(p) - v).sqr_norm())
+        .collect();
+    let w_sum: T = w_all.iter().copied().sum();
+    if w_sum.is_infinite() {
+        // Most probably, at least one of the weights is infinite,
+        // because our point basically coincide with a control point.
+        let index = w_all
+            .iter()
+            .position(|w| w.is_infinite())
+            .expect("There is an infinite sum of the weights but none is infinite");
+        return Err(index);
+    }
+
+    // Compute the centroids p* and q*.
+    let centroid = |controls: &[(T, T, T)]| -> Point3<T> {
+        let w_sum_inv = T::one() / w_sum;
+        w_all
+            .iter()
+            .zip(controls)
+            .map(|(&w, &p)| Point3::from(p) * w)
+            .sum::<Point3<T>>()
+            * w_sum_inv
+    };
+    let p_star = centroid(controls_p);
+    let q_star = centroid(controls_q);
+
+    // Compute p_hat and q_hat.
+    let hat = |controls: &[(T, T, T)], star: Point3<T>| {
+        controls.iter().map(|&p| Point3::from(p) - star).collect()
+    };
+    Ok(Centered {
+        p_hat: hat(controls_p, p_star),
+        q_hat: hat(controls_q, q_star),
+        w_all,
+        p_star,
+        q_star,
+    })
+}
+
+/// Compute the rotation R maximizing sum_i w_i * q_hat_i . (R p_hat_i).
+///
+/// Returns the rotation, and the corresponding maximum,
+/// which is the largest eigen value of Horn's symmetric 4x4 matrix N.
+/// The rotation is given by the unit quaternion of the corresponding eigen vector.
+fn optimal_rotation<T: Scalar>(c: &Centered<T>) -> (Mat3<T>, T) {
+    // Weighted cross-covariance matrix S = sum_i w_i p_hat_i q_hat_i^T
+    let s: Mat3<T> = c
+        .w_all
+        .iter()
+        .zip(&c.p_hat)
+        .zip(&c.q_hat)
+        .map(|((&w, &ph), &qh)| (ph * w).times_transpose(qh))
+        .sum();
+    let [[sxx, sxy, sxz], [syx, syy, syz], [szx, szy, szz]] = s.0;
+    let n = [
+        [sxx + syy + szz, syz - szy, szx - sxz, sxy - syx],
+        [syz - szy, sxx - syy - szz, sxy + syx, szx + sxz],
+        [szx - sxz, sxy + syx, syy - sxx - szz, syz + szy],
+        [sxy - syx, szx + sxz, syz + szy, szz - sxx - syy],
+    ];
+    let (eigen_value, [qw, qx, qy, qz]) = largest_eigen(n);
+
+    // Rotation matrix of the unit quaternion.
+    let two = T::one() + T::one();
+    let rotation = Mat3([
+        [
+            qw * qw + qx * qx - qy * qy - qz * qz,
+            two * (qx * qy - qw * qz),
+            two * (qx * qz + qw * qy),
+        ],
+        [
+            two * (qy * qx + qw * qz),
+            qw * qw - qx * qx + qy * qy - qz * qz,
+            two * (qy * qz - qw * qx),
+        ],
+        [
+            two * (qz * qx - qw * qy),
+            two * (qz * qy + qw * qx),
+            qw * qw - qx * qx - qy * qy + qz * qz,
+        ],
+    ]);
+    (rotation, eigen_value)
+}
+
+/// Largest eigen value, and the corresponding unit eigen vector,
+/// of a 4x4 symmetric matrix, computed with the cyclic Jacobi method.
+fn largest_eigen<T: Scalar>(mut a: [[T; 4]; 4]) -> (T, [T; 4]) {
+    let (zero, one) = (T::zero(), T::one());
+    let two = one + one;
+    // Eigen vectors are accumulated in the columns of v.
+    let mut v = [[zero; 4]; 4];
+    (0..4).for_each(|i| v[i][i] = one);
+    for _sweep in 0..50 {
+        let off_diag: T = (0..4)
+            .flat_map(|i| (i + 1..4).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        let diag: T = (0..4).map(|i| a[i][i] * a[i][i]).sum();
+        if off_diag <= T::epsilon() * T::epsilon() * diag {
+            break;
+        }
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q] == zero {
+                    continue;
+                }
+                // Rotation annihilating a[p][q].
+                let theta = (a[q][q] - a[p][p]) / (two * a[p][q]);
+                let sign = if theta < zero { -one } else { one };
+                let t = sign / (theta.abs() + (theta * theta + one).sqrt());
+                let cos = one / (t * t + one).sqrt();
+                let sin = t * cos;
+                // Apply the rotation to the columns p and q of a and v.
+                for row in a.iter_mut().chain(v.iter_mut()) {
+                    let (rp, rq) = (row[p], row[q]);
+                    row[p] = cos * rp - sin * rq;
+                    row[q] = sin * rp + cos * rq;
+                }
+                // Apply the rotation to the rows p and q of a.
+                let (row_p, row_q) = (a[p], a[q]);
+                for k in 0..4 {
+                    a[p][k] = cos * row_p[k] - sin * row_q[k];
+                    a[q][k] = sin * row_p[k] + cos * row_q[k];
+                }
+            }
+        }
+    }
+    let mut best = 0;
+    for i in 1..4 {
+        if a[i][i] > a[best][best] {
+            best = i;
+        }
+    }
+    let eigen_vector = [v[0][best], v[1][best], v[2][best], v[3][best]];
+    (a[best][best], eigen_vector)
+}
+
+// 3D points helper ############################################################
+// That's to avoid a dependency on a heavy package such as nalgebra
+
+/// Point represented by a 3x1 column vector.
+#[derive(Clone, Copy)]
+struct Point3<T> {
+    x: T,
+    y: T,
+    z: T,
+}
+
+impl<T: Scalar> Point3<T> {
+    /// 0
+    fn zero() -> Self {
+        Self {
+            x: T::zero(),
+            y: T::zero(),
+            z: T::zero(),
+        }
+    }
+
+    /// Square norm.
+    fn sqr_norm(self) -> T {
+        self.x * self.x + self.y * self.y + self.z * self.z
+    }
+
+    /// Create a 3x3 matrix from a 3x1 point
+    fn times_transpose(self, rhs: Self) -> Mat3<T> {
+        let (a, b) = ([self.x, self.y, self.z], [rhs.x, rhs.y, rhs.z]);
+        Mat3([
+            [a[0] * b[0], a[0] * b[1], a[0] * b[2]],
+            [a[1] * b[0], a[1] * b[1], a[1] * b[2]],
+            [a[2] * b[0], a[2] * b[1], a[2] * b[2]],
+        ])
+    }
+
+    /// Multiply with a Mat3 on the right.
+    /// Returns a Point3 even though it should be a line vector (no big deal).
+    fn transpose_mul(self, rhs: Mat3<T>) -> Self {
+        let m = rhs.0;
+        Self {
+            x: m[0][0] * self.x + m[1][0] * self.y + m[2][0] * self.z,
+            y: m[0][1] * self.x + m[1][1] * self.y + m[2][1] * self.z,
+            z: m[0][2] * self.x + m[1][2] * self.y + m[2][2] * self.z,
+        }
+    }
+}
+
+// Convert from (x,y,z) to Point3 { x, y, z }
+impl<T> From<(T, T, T)> for Point3<T> {
+    fn from((x, y, z): (T, T, T)) -> Self {
+        Point3 { x, y, z }
+    }
+}
+
+// Convert from Point3 { x, y, z } to (x,y,z)
+impl<T> From<Point3<T>> for (T, T, T) {
+    fn from(point: Point3<T>) -> (T, T, T) {
+        (point.x, point.y, point.z)
+    }
+}
+
+// Add two points
+impl<T: Scalar> Add for Point3<T> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+// Substract a point
+impl<T: Scalar> Sub for Point3<T> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+// Scalar multiplication
+impl<T: Scalar> Mul<T> for Point3<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self::Output {
+        Self {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+// Sum an iterator of points
+impl<T: Scalar> Sum for Point3<T> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |s, p| s + p)
+    }
+}
+
+// 3x3 matrix helper ###########################################################
+// That's to avoid a dependency on a heavy package such as nalgebra
+
+/// 3x3 Matrix, stored row by row.
+#[derive(Clone, Copy)]
+struct Mat3<T>([[T; 3]; 3]);
+
+impl<T: Scalar> Mat3<T> {
+    /// 0
+    fn zero() -> Self {
+        Self([[T::zero(); 3]; 3])
+    }
+
+    /// Multiply with a column vector on the right.
+    fn mul_vec(self, p: Point3<T>) -> Point3<T> {
+        let m = self.0;
+        Point3 {
+            x: m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z,
+            y: m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z,
+            z: m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z,
+        }
+    }
+
+    /// Inverse of a matrix (does not check if det is 0)
+    fn inv(self) -> Self {
+        let m = self.0;
+        // Cofactor of the coefficient (i, j).
+        let cofactor = |i: usize, j: usize| {
+            let (i1, i2) = ((i + 1) % 3, (i + 2) % 3);
+            let (j1, j2) = ((j + 1) % 3, (j + 2) % 3);
+            m[i1][j1] * m[i2][j2] - m[i1][j2] * m[i2][j1]
+        };
+        let det = m[0][0] * cofactor(0, 0) + m[0][1] * cofactor(0, 1) + m[0][2] * cofactor(0, 2);
+        let det_inv = T::one() / det;
+        // The inverse is the transposed cofactor matrix divided by the determinant.
+        let mut inv = [[T::zero(); 3]; 3];
+        for (i, row) in inv.iter_mut().enumerate() {
+            for (j, coef) in row.iter_mut().enumerate() {
+                *coef = cofactor(j, i) * det_inv;
+            }
+        }
+        Self(inv)
+    }
+}
+
+// Add two matrices
+impl<T: Scalar> Add for Mat3<T> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut m = self.0;
+        for (row, rhs_row) in m.iter_mut().zip(rhs.0.iter()) {
+            for (coef, &rhs_coef) in row.iter_mut().zip(rhs_row) {
+                *coef = *coef + rhs_coef;
+            }
+        }
+        Self(m)
+    }
+}
+
+// Scalar multiplication
+impl<T: Scalar> Mul<T> for Mat3<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self::Output {
+        let mut m = self.0;
+        m.iter_mut().flatten().for_each(|coef| *coef = *coef * rhs);
+        Self(m)
+    }
+}
+
+// Sum an iterator of matrices
+impl<T: Scalar> Sum for Mat3<T> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |s, m| s + m)
+    }
+}
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Precomputed deformation of a fixed set of points.
+//!
+//! As explained in section 2 of the paper, everything except the displaced
+//! control points q only depends on the original control points p
+//! and on the point v being deformed.
+//! So when the same points are deformed multiple times with different q,
+//! typically while interactively dragging handles,
+//! the weights, centroids and A_i coefficients can be computed once.
+
+use crate::{DeformMethod, Mat2, Point, Scalar};
+
+/// Deformation precomputed for fixed original control points and deformed points.
+///
+/// Create one with [`Deformer::new`], or directly with [`Deformer::affine`],
+/// [`Deformer::similarity`] or [`Deformer::rigid`], then call [`Deformer::deform`] every time the displaced control points change.
+pub struct Deformer<T> {
+    nb_controls: usize,
+    precomputed: Vec<Precomputed<T>>,
+}
+
+/// Data precomputed for one deformed point v.
+enum Precomputed<T> {
+    /// v coincides with the control point at this index.
+    Control(usize),
+    /// Normalized weights w_i / sum(w) (to compute q*)
+    /// and scalar A_j coefficients of eq (5).
+    Affine { weights: Vec<T>, a: Vec<T> },
+    /// Normalized weights and A_i / mu_s matrices of eq (7).
+    Similarity { weights: Vec<T>, a: Vec<Mat2<T>> },
+    /// Normalized weights, A_i matrices of eq (7) and |v - p*|.
+    Rigid {
+        weights: Vec<T>,
+        a: Vec<Mat2<T>>,
+        v_hat_norm: T,
+    },
+}
+
+impl<T: Scalar> Deformer<T> {
+    /// Precompute the deformation of the given points with the chosen method
+    /// for the original control points `controls_p`.
+    pub fn new(method: DeformMethod, controls_p: &[(T, T)], points: &[(T, T)]) -> Self {
+        match method {
+            DeformMethod::Affine => Self::affine(controls_p, points),
+            DeformMethod::Similarity => Self::similarity(controls_p, points),
+            DeformMethod::Rigid => Self::rigid(controls_p, points),
+        }
+    }
+
+    /// Precompute the affine deformation of the given points
+    /// for the original control points `controls_p`.
+    pub fn affine(controls_p: &[(T, T)], points: &[(T, T)]) -> Self {
+        Self::with_coefficients(controls_p, points, |weights, w, p_hat, v_hat| {
+            // mp is a 2x2 matrix.
+            let mp: Mat2<T> = w
+                .iter()
+                .zip(p_hat)
+                .map(|(&wi, &pi)| pi.times_transpose(pi) * wi)
+                .sum();
+            let v_mp_inv = v_hat.transpose_mul(mp.inv());
+            let a = w
+                .iter()
+                .zip(p_hat)
+                .map(|(&wj, &pj)| v_mp_inv.dot(pj) * wj)
+                .collect();
+            Precomputed::Affine { weights, a }
+        })
+    }
+
+    /// Precompute the similarity deformation of the given points
+    /// for the original control points `controls_p`.
+    pub fn similarity(controls_p: &[(T, T)], points: &[(T, T)]) -> Self {
+        Self::with_coefficients(controls_p, points, |weights, w, p_hat, v_hat| {
+            // Compute mu_s (eq 6).
+            let mu_s: T = w
+                .iter()
+                .zip(p_hat)
+                .map(|(&wi, pi)| wi * pi.sqr_norm())
+                .sum();
+            let a = similarity_coefficients(w, p_hat, v_hat, T::one() / mu_s);
+            Precomputed::Similarity { weights, a }
+        })
+    }
+
+    /// Precompute the rigid deformation of the given points
+    /// for the original control points `controls_p`.
+    pub fn rigid(controls_p: &[(T, T)], points: &[(T, T)]) -> Self {
+        Self::with_coefficients(controls_p, points, |weights, w, p_hat, v_hat| {
+            let a = similarity_coefficients(w, p_hat, v_hat, T::one());
+            Precomputed::Rigid {
+                weights,
+                a,
+                v_hat_norm: v_hat.sqr_norm().sqrt(),
+            }
+        })
+    }
+
+    /// Shared precomputation of the weights and centroid p*.
+    /// The method-specific coefficients are computed by the `coefficients` closure,
+    /// called with the normalized weights, the weights, p_hat and v - p*.
+    fn with_coefficients<F>(controls_p: &[(T, T)], points: &[(T, T)], coefficients: F) -> Self
+    where
+        F: Fn(Vec<T>, &[T], &[Point<T>], Point<T>) -> Precomputed<T>,
+    {
+        let precomputed = points
+            .iter()
+            .map(|&point| {
+                let v = Point::from(point);
+
+                // The weight of a given control point depends on its distance to the current point.
+                // CAREFUL: this w can go to infinity.
+                let w_all: Vec<T> = controls_p
+                    .iter()
+                    .map(|&p| T::one() / (Point::from(p) - v).sqr_norm())
+                    .collect();
+                let w_sum: T = w_all.iter().copied().sum();
+                if w_sum.is_infinite() {
+                    // Most probably, at least one of the weights is infinite,
+                    // because our point basically coincide with a control point.
+                    let index = w_all
+                        .iter()
+                        .position(|w| w.is_infinite())
+                        .expect("There is an infinite sum of the weights but none is infinite");
+                    return Precomputed::Control(index);
+                }
+
+                // Compute the centroid p*.
+                let wp_star_sum: Point<T> = w_all
+                    .iter()
+                    .zip(controls_p)
+                    .map(|(&w, &p)| Point::from(p) * w)
+                    .sum();
+                let p_star = wp_star_sum * (T::one() / w_sum);
+
+                // Compute p_hat.
+                let p_hat: Vec<Point<T>> = controls_p
+                    .iter()
+                    .map(|&p| Point::from(p) - p_star)
+                    .collect();
+
+                // Normalized weights, used to compute q* later.
+                let weights = w_all.iter().map(|&w| w / w_sum).collect();
+                coefficients(weights, &w_all, &p_hat, v - p_star)
+            })
+            .collect();
+        Self {
+            nb_controls: controls_p.len(),
+            precomputed,
+        }
+    }
+
+    /// Compute the new positions of the precomputed points,
+    /// according to the deformation that transforms the original control points
+    /// into the displaced ones `controls_q`.
+    ///
+    /// Panics if `controls_q` does not have the same length as the original control points.
+    pub fn deform(&self, controls_q: &[(T, T)]) -> Vec<(T, T)> {
+        assert_eq!(
+            controls_q.len(),
+            self.nb_controls,
+            "There must be as many displaced control points as original ones"
+        );
+        self.precomputed
+            .iter()
+            .map(|precomputed| match precomputed {
+                Precomputed::Control(index) => controls_q[*index],
+                Precomputed::Affine { weights, a } => {
+                    let q_star = centroid(weights, controls_q);
+                    // Finally compute the projection (eq 5).
+                    let fa: Point<T> = a
+                        .iter()
+                        .zip(controls_q)
+                        .map(|(&aj, &q)| (Point::from(q) - q_star) * aj)
+                        .sum();
+                    (fa + q_star).into()
+                }
+                Precomputed::Similarity { weights, a } => {
+                    let q_star = centroid(weights, controls_q);
+                    // Finally compute the projection (eq 7).
+                    let fs: Point<T> = a
+                        .iter()
+                        .zip(controls_q)
+                        .map(|(&ai, &q)| (Point::from(q) - q_star).transpose_mul(ai))
+                        .sum();
+                    (fs + q_star).into()
+                }
+                Precomputed::Rigid {
+                    weights,
+                    a,
+                    v_hat_norm,
+                } => {
+                    let q_star = centroid(weights, controls_q);
+                    // Finally compute the projection (eq 8).
+                    let fr: Point<T> = a
+                        .iter()
+                        .zip(controls_q)
+                        .map(|(&ai, &q)| (Point::from(q) - q_star).transpose_mul(ai))
+                        .sum();
+                    let scale = *v_hat_norm / fr.sqr_norm().sqrt();
+                    (fr * scale + q_star).into()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Centroid of the points, with normalized weights.
+fn centroid<T: Scalar>(weights: &[T], points: &[(T, T)]) -> Point<T> {
+    weights
+        .iter()
+        .zip(points)
+        .map(|(&w, &p)| Point::from(p) * w)
+        .sum()
+}
+
+/// Compute the A_i matrices of eq (7), multiplied by a scaling factor.
+///
+/// A_i = w_i * | p_hat_i  | * | v - p*     |^T
+///             | -p_hat_i⊥|   | -(v - p*)⊥ |
+fn similarity_coefficients<T: Scalar>(
+    w: &[T],
+    p_hat: &[Point<T>],
+    v_hat: Point<T>,
+    scale: T,
+) -> Vec<Mat2<T>> {
+    // (x, y)⊥ = (-y, x) so -(x, y)⊥ = (y, -x)
+    let v_mat_t = Mat2 {
+        m11: v_hat.x,
+        m21: v_hat.y,
+        m12: v_hat.y,
+        m22: -v_hat.x,
+    };
+    w.iter()
+        .zip(p_hat)
+        .map(|(&wi, pi)| {
+            let p_mat = Mat2 {
+                m11: pi.x,
+                m12: pi.y,
+                m21: pi.y,
+                m22: -pi.x,
+            };
+            p_mat * v_mat_t * (wi * scale)
+        })
+        .collect()
+}
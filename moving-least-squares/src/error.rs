@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Errors for degenerate deformation configurations.
+
+use crate::{Local, Point, Scalar};
+use std::fmt;
+
+/// Reasons why a deformation cannot be computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeformError {
+    /// There is no control point.
+    EmptyControls,
+    /// The system to solve is singular, typically because there are not enough
+    /// distinct control points, or because they are colinear for an affine deformation.
+    SingularSystem,
+    /// There are not as many displaced control points as original ones.
+    MismatchedLengths,
+    /// A coordinate of a control point or of the deformed point is NaN or infinite.
+    NonFinitePoint,
+    /// The step of a grid is zero.
+    ZeroStep,
+}
+
+impl fmt::Display for DeformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeformError::EmptyControls => write!(f, "there is no control point"),
+            DeformError::SingularSystem => {
+                write!(f, "the control points configuration is degenerate")
+            }
+            DeformError::MismatchedLengths => write!(
+                f,
+                "there are not as many displaced control points as original ones"
+            ),
+            DeformError::NonFinitePoint => write!(f, "a point coordinate is NaN or infinite"),
+            DeformError::ZeroStep => write!(f, "the grid step is zero"),
+        }
+    }
+}
+
+impl std::error::Error for DeformError {}
+
+/// Check that there are control points, with as many p as q,
+/// and that all coordinates are finite.
+pub(crate) fn check_inputs<T: Scalar>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    point: (T, T),
+) -> Result<(), DeformError> {
+    if controls_p.len() != controls_q.len() {
+        return Err(DeformError::MismatchedLengths);
+    }
+    if controls_p.is_empty() {
+        return Err(DeformError::EmptyControls);
+    }
+    let finite = |&(x, y): &(T, T)| x.is_finite() && y.is_finite();
+    let all_finite = finite(&point) && controls_p.iter().chain(controls_q).all(finite);
+    if !all_finite {
+        return Err(DeformError::NonFinitePoint);
+    }
+    Ok(())
+}
+
+/// Check that the local transformation is not singular and apply it.
+pub(crate) fn check_local<T: Scalar>(
+    local: Result<Local<T>, usize>,
+    v: Point<T>,
+    controls_q: &[(T, T)],
+) -> Result<(T, T), DeformError> {
+    match local {
+        Err(index) => Ok(controls_q[index]),
+        Ok(local) if local.singular => Err(DeformError::SingularSystem),
+        Ok(local) => {
+            let (x, y) = local.apply(v).into();
+            if x.is_finite() && y.is_finite() {
+                Ok((x, y))
+            } else {
+                Err(DeformError::SingularSystem)
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Barnes-Hut approximation of the contribution of far control points.
+//!
+//! All the sums of the MLS deformations are sums over control points of
+//! w_i, w_i p_i, w_i q_i, w_i p_i p_i^T and w_i p_i q_i^T (eq 5 to 8 once expanded).
+//! For a cluster of control points far from v, all weights are close to the weight
+//! of the cluster centroid, so these sums are approximated with the moments of the cluster,
+//! precomputed once in a tree of clusters.
+//! A cluster of radius r at a distance d of v is approximated when r < theta * d,
+//! so the relative error of each weight is of the order of theta,
+//! and the cost per point decreases from O(n) to O(log n) for a fixed theta.
+
+use crate::{DeformMethod, Local, Mat2, Point, Scalar};
+
+/// Maximum number of control points in a leaf of the tree.
+const LEAF_SIZE: usize = 8;
+
+/// Tree of clusters of control points, for fast approximate deformations.
+pub struct FarFieldTree<T> {
+    /// Control points, reordered such that each cluster is a contiguous range.
+    controls: Vec<(Point<T>, Point<T>)>,
+    /// Index in `controls` of each original control point.
+    positions: Vec<usize>,
+    /// Clusters, with the root cluster at index 0.
+    clusters: Vec<Cluster<T>>,
+}
+
+/// Cluster of control points, with their moments.
+struct Cluster<T> {
+    /// Range of the cluster control points in the reordered control points.
+    start: usize,
+    end: usize,
+    /// Indices of the two sub-clusters, if this is not a leaf.
+    children: Option<(usize, usize)>,
+    /// Mean of the original control points p.
+    p_mean: Point<T>,
+    /// Mean of the displaced control points q.
+    q_mean: Point<T>,
+    /// Largest distance between an original control point and `p_mean`.
+    radius: T,
+    /// sum_i (p_i - p_mean) (p_i - p_mean)^T
+    pp: Mat2<T>,
+    /// sum_i (p_i - p_mean) (q_i - q_mean)^T
+    pq: Mat2<T>,
+}
+
+impl<T: Scalar> FarFieldTree<T> {
+    /// Build the tree of clusters of the control points.
+    ///
+    /// Panics if there are not as many displaced control points as original ones.
+    pub fn new(controls_p: &[(T, T)], controls_q: &[(T, T)]) -> Self {
+        assert_eq!(
+            controls_p.len(),
+            controls_q.len(),
+            "There must be one displaced control point per original control point"
+        );
+        let mut order: Vec<usize> = (0..controls_p.len()).collect();
+        let mut clusters = Vec::new();
+        split(controls_p, &mut order, 0, &mut clusters);
+        let mut positions = vec![0; order.len()];
+        for (position, &index) in order.iter().enumerate() {
+            positions[index] = position;
+        }
+        let mut tree = Self {
+            controls: order
+                .iter()
+                .map(|&i| (controls_p[i].into(), controls_q[i].into()))
+                .collect(),
+            positions,
+            clusters,
+        };
+        tree.update_moments();
+        tree
+    }
+
+    /// Change the displaced control points, keeping the clusters of the original ones.
+    /// This is cheaper than rebuilding the tree when dragging handles.
+    ///
+    /// Panics if `controls_q` does not have as many points as the original control points.
+    pub fn update_q(&mut self, controls_q: &[(T, T)]) {
+        assert_eq!(
+            controls_q.len(),
+            self.positions.len(),
+            "There must be one displaced control point per original control point"
+        );
+        for (&position, &q) in self.positions.iter().zip(controls_q) {
+            self.controls[position].1 = q.into();
+        }
+        self.update_moments();
+    }
+
+    /// Move a given point with the deformation, approximating the clusters
+    /// of radius r at a distance d of the point such that r < theta * d.
+    ///
+    /// With theta = 0, the deformation is exact, up to rounding errors.
+    /// Values around 0.5 give a good trade-off between speed and accuracy.
+    pub fn deform(&self, point: (T, T), method: DeformMethod, theta: T) -> (T, T) {
+        if self.controls.is_empty() {
+            return point;
+        }
+        let v = Point::from(point);
+        // Sums with coordinates relative to v, to limit cancellation errors.
+        let mut w_sum = T::zero();
+        let mut sp = Point::zero();
+        let mut sq = Point::zero();
+        let mut spp = Mat2::zero();
+        let mut spq = Mat2::zero();
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let cluster = &self.clusters[i];
+            let sqr_dist = (cluster.p_mean - v).sqr_norm();
+            let far = cluster.radius * cluster.radius < theta * theta * sqr_dist;
+            match cluster.children {
+                _ if far => {
+                    let n = T::from_f64((cluster.end - cluster.start) as f64);
+                    let w = T::one() / sqr_dist;
+                    let (p, q) = (cluster.p_mean - v, cluster.q_mean - v);
+                    w_sum = w_sum + w * n;
+                    sp = sp + p * (w * n);
+                    sq = sq + q * (w * n);
+                    spp = spp + (cluster.pp + p.times_transpose(p) * n) * w;
+                    spq = spq + (cluster.pq + p.times_transpose(q) * n) * w;
+                }
+                Some((left, right)) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                None => {
+                    for &(p, q) in &self.controls[cluster.start..cluster.end] {
+                        let (p, q) = (p - v, q - v);
+                        let sqr_dist = p.sqr_norm();
+                        if sqr_dist == T::zero() {
+                            // v is at a control point, so it follows its translation.
+                            return (v + q - p).into();
+                        }
+                        let w = T::one() / sqr_dist;
+                        w_sum = w_sum + w;
+                        sp = sp + p * w;
+                        sq = sq + q * w;
+                        spp = spp + p.times_transpose(p) * w;
+                        spq = spq + p.times_transpose(q) * w;
+                    }
+                }
+            }
+        }
+
+        // Centroids and centered moments.
+        let p_star = sp * (T::one() / w_sum);
+        let q_star = sq * (T::one() / w_sum);
+        let mp = spp - p_star.times_transpose(p_star) * w_sum;
+        let mq = spq - p_star.times_transpose(q_star) * w_sum;
+        let m = match method {
+            DeformMethod::Affine => mp.inv() * mq,
+            DeformMethod::Similarity | DeformMethod::Rigid => {
+                // M of eq (6), from sum_i w_i p_hat_i . q_hat_i and sum_i w_i p_hat_i x q_hat_i.
+                let (dot, cross) = (mq.m11 + mq.m22, mq.m12 - mq.m21);
+                let mu = match method {
+                    DeformMethod::Similarity => mp.m11 + mp.m22,
+                    _ => (dot * dot + cross * cross).sqrt(),
+                };
+                Mat2 {
+                    m11: dot,
+                    m12: cross,
+                    m21: -cross,
+                    m22: dot,
+                } * (T::one() / mu)
+            }
+        };
+        let local = Local {
+            p_star,
+            q_star,
+            m,
+            singular: false,
+        };
+        (local.apply(Point::zero()) + v).into()
+    }
+
+    /// Recompute the moments of all clusters, from the leaves up to the root.
+    fn update_moments(&mut self) {
+        // Children always have larger indices than their parent.
+        for i in (0..self.clusters.len()).rev() {
+            let (start, end) = (self.clusters[i].start, self.clusters[i].end);
+            let controls = &self.controls[start..end];
+            let n = T::from_f64(controls.len() as f64);
+            let p_mean = controls.iter().map(|c| c.0).sum::<Point<T>>() * (T::one() / n);
+            let q_mean = controls.iter().map(|c| c.1).sum::<Point<T>>() * (T::one() / n);
+            let (mut pp, mut pq, mut radius) = (Mat2::zero(), Mat2::zero(), T::zero());
+            for &(p, q) in controls {
+                let (p, q) = (p - p_mean, q - q_mean);
+                pp = pp + p.times_transpose(p);
+                pq = pq + p.times_transpose(q);
+                radius = radius.max(p.sqr_norm());
+            }
+            let cluster = &mut self.clusters[i];
+            cluster.p_mean = p_mean;
+            cluster.q_mean = q_mean;
+            cluster.radius = radius.sqrt();
+            cluster.pp = pp;
+            cluster.pq = pq;
+        }
+    }
+}
+
+/// Recursively split the control points at the median of their widest coordinate,
+/// appending the clusters (with moments left to compute) in depth-first order.
+fn split<T: Scalar>(
+    controls_p: &[(T, T)],
+    order: &mut [usize],
+    start: usize,
+    clusters: &mut Vec<Cluster<T>>,
+) -> usize {
+    let id = clusters.len();
+    clusters.push(Cluster {
+        start,
+        end: start + order.len(),
+        children: None,
+        p_mean: Point::zero(),
+        q_mean: Point::zero(),
+        radius: T::zero(),
+        pp: Mat2::zero(),
+        pq: Mat2::zero(),
+    });
+    if order.len() <= LEAF_SIZE {
+        return id;
+    }
+    let extent = |coordinate: fn((T, T)) -> T| {
+        let first = coordinate(controls_p[order[0]]);
+        let (min, max) = order.iter().fold((first, first), |(min, max), &i| {
+            let x = coordinate(controls_p[i]);
+            (if x < min { x } else { min }, max.max(x))
+        });
+        max - min
+    };
+    let coordinate: fn((T, T)) -> T = if extent(|p| p.0) >= extent(|p| p.1) {
+        |p| p.0
+    } else {
+        |p| p.1
+    };
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |&a, &b| {
+        coordinate(controls_p[a])
+            .partial_cmp(&coordinate(controls_p[b]))
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+    let (left, right) = order.split_at_mut(mid);
+    let left_id = split(controls_p, left, start, clusters);
+    let right_id = split(controls_p, right, start + mid, clusters);
+    clusters[id].children = Some((left_id, right_id));
+    id
+}
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Fitting of the displaced control points to given correspondences,
+//! for example to compress a dense optical flow into a few editable handles.
+//!
+//! With fixed original control points p, the affine and similarity deformations
+//! are linear in the displaced control points q (eq 5 and 7).
+//! So the q minimizing sum_k |f(x_k) - y_k|^2 for sample correspondences (x_k, y_k)
+//! are the solution of a linear least squares problem.
+//! The rigid deformation is not linear in q, and is fitted with Gauss-Newton iterations
+//! starting from the similarity solution.
+
+use crate::{DeformMethod, Deformer, Scalar};
+
+/// Maximum number of Gauss-Newton iterations for the rigid deformation.
+const MAX_ITERATIONS: usize = 20;
+
+/// Find the displaced control points `controls_q` such that the deformation
+/// best maps the samples to their targets, in the least squares sense.
+///
+/// A control point with no sample in its neighborhood is barely constrained,
+/// so a small regularization keeps it close to its original position.
+///
+/// Panics if `samples` and `targets` do not have the same length.
+pub fn fit_controls<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    samples: &[(T, T)],
+    targets: &[(T, T)],
+    method: DeformMethod,
+) -> Vec<(T, T)> {
+    assert_eq!(
+        samples.len(),
+        targets.len(),
+        "There must be one target per sample"
+    );
+    let y = flatten(targets);
+    let p = flatten(controls_p);
+    let linear_method = match method {
+        DeformMethod::Affine => DeformMethod::Affine,
+        DeformMethod::Similarity | DeformMethod::Rigid => DeformMethod::Similarity,
+    };
+
+    // Linear least squares, with the columns of the linear map obtained
+    // by deforming with each coordinate of q set to 1 in turn.
+    let deformer = Deformer::new(linear_method, controls_p, samples);
+    let columns: Vec<Vec<T>> = (0..p.len())
+        .map(|i| {
+            let mut q = vec![T::zero(); p.len()];
+            q[i] = T::one();
+            flatten(&deformer.deform(&unflatten(&q)))
+        })
+        .collect();
+    let mut q = solve_least_squares(&columns, &y, &p);
+    if method != DeformMethod::Rigid {
+        return unflatten(&q);
+    }
+
+    // Gauss-Newton iterations for the rigid deformation.
+    let deformer = Deformer::rigid(controls_p, samples);
+    let residuals = |q: &[T]| -> Vec<T> {
+        let f = flatten(&deformer.deform(&unflatten(q)));
+        y.iter().zip(f).map(|(&yk, fk)| yk - fk).collect()
+    };
+    let sqr_norm = |r: &[T]| r.iter().map(|&x| x * x).sum::<T>();
+    let scale = p.iter().fold(T::one(), |m, &x| m.max(x.abs()));
+    let h = T::epsilon().sqrt() * scale;
+    let mut r = residuals(&q);
+    for _ in 0..MAX_ITERATIONS {
+        let f = flatten(&deformer.deform(&unflatten(&q)));
+        let jacobian: Vec<Vec<T>> = (0..q.len())
+            .map(|i| {
+                let mut q_h = q.clone();
+                q_h[i] = q_h[i] + h;
+                let f_h = flatten(&deformer.deform(&unflatten(&q_h)));
+                f_h.iter().zip(&f).map(|(&a, &b)| (a - b) / h).collect()
+            })
+            .collect();
+        let zero = vec![T::zero(); q.len()];
+        let dq = solve_least_squares(&jacobian, &r, &zero);
+        let q_next: Vec<T> = q.iter().zip(&dq).map(|(&a, &b)| a + b).collect();
+        let r_next = residuals(&q_next);
+        let improved = sqr_norm(&r_next) < sqr_norm(&r);
+        if !improved {
+            break;
+        }
+        q = q_next;
+        r = r_next;
+    }
+    unflatten(&q)
+}
+
+/// Same as [`fit_controls`], with the correspondences given by a dense displacement field,
+/// such as an optical flow, sampled every `step` pixels.
+///
+/// The displacement of the pixel (x, y) is at index `y * width + x` of `flow`.
+///
+/// Panics if `flow` does not have `width * height` elements.
+pub fn fit_controls_to_flow<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    flow: &[(T, T)],
+    width: u32,
+    height: u32,
+    step: u32,
+    method: DeformMethod,
+) -> Vec<(T, T)> {
+    assert_eq!(
+        flow.len(),
+        (width * height) as usize,
+        "The flow must have one displacement per pixel"
+    );
+    assert!(step > 0, "The sampling step must be strictly positive");
+    let mut samples = Vec::new();
+    let mut targets = Vec::new();
+    for y in (0..height).step_by(step as usize) {
+        for x in (0..width).step_by(step as usize) {
+            let (dx, dy) = flow[(y * width + x) as usize];
+            let (xf, yf) = (T::from_f64(x as f64), T::from_f64(y as f64));
+            samples.push((xf, yf));
+            targets.push((xf + dx, yf + dy));
+        }
+    }
+    fit_controls(controls_p, &samples, &targets, method)
+}
+
+/// Solve min |A x - y|^2 + lambda |x - prior|^2 with the normal equations,
+/// where `columns` are the columns of A,
+/// and lambda is a tiny fraction of the trace of A^T A.
+fn solve_least_squares<T: Scalar>(columns: &[Vec<T>], y: &[T], prior: &[T]) -> Vec<T> {
+    let n = columns.len();
+    let dot = |a: &[T], b: &[T]| a.iter().zip(b).map(|(&x, &y)| x * y).sum::<T>();
+    let mut ata: Vec<Vec<T>> = columns
+        .iter()
+        .map(|ci| columns.iter().map(|cj| dot(ci, cj)).collect())
+        .collect();
+    let trace: T = (0..n).map(|i| ata[i][i]).sum();
+    let lambda = T::epsilon().sqrt() * trace / T::from_f64(n.max(1) as f64);
+    let mut atb: Vec<T> = columns.iter().map(|ci| dot(ci, y)).collect();
+    for i in 0..n {
+        ata[i][i] = ata[i][i] + lambda;
+        atb[i] = atb[i] + lambda * prior[i];
+    }
+    solve(ata, atb)
+}
+
+/// Solve the square linear system A x = b with Gaussian elimination and partial pivoting.
+pub(crate) fn solve<T: Scalar>(mut a: Vec<Vec<T>>, mut b: Vec<T>) -> Vec<T> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| {
+                a[i][col]
+                    .abs()
+                    .partial_cmp(&a[j][col].abs())
+                    .unwrap_or(core::cmp::Ordering::Equal)
+            })
+            .unwrap_or(col);
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let (top, bottom) = a.split_at_mut(row);
+            for (x, &p) in bottom[0][col..].iter_mut().zip(&top[col][col..]) {
+                *x = *x - factor * p;
+            }
+            b[row] = b[row] - factor * b[col];
+        }
+    }
+    let mut x = vec![T::zero(); n];
+    for row in (0..n).rev() {
+        let sum: T = (row + 1..n).map(|j| a[row][j] * x[j]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x
+}
+
+/// Coordinates of the points, one after the other.
+fn flatten<T: Scalar>(points: &[(T, T)]) -> Vec<T> {
+    points.iter().flat_map(|&(x, y)| [x, y]).collect()
+}
+
+/// Points from their coordinates, one after the other.
+fn unflatten<T: Scalar>(coordinates: &[T]) -> Vec<(T, T)> {
+    coordinates.chunks_exact(2).map(|c| (c[0], c[1])).collect()
+}
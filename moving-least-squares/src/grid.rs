@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Evaluation of the deformation on a regular grid.
+
+use crate::batch::map_with_buffers;
+use crate::{deform_with, Centered, DeformError, DeformMethod, MlsOptions, Scalar};
+
+/// Compute the deformation of all the points of a regular grid with the given step,
+/// covering a width x height domain, such as an image.
+///
+/// The grid has `(width - 1) / step + 2` columns and `(height - 1) / step + 2` rows,
+/// so that every position (x, y) with x < width and y < height is inside a grid cell.
+/// The deformed grid points are returned row by row, starting at (0, 0),
+/// and the grid point at (column, row) is thus at index `row * nb_columns + column`.
+///
+/// This is faster than calling [`deform`](crate::deform) for each grid point
+/// since the same internal buffers are reused for all points,
+/// and the rows are split across cores if the `rayon` feature is enabled.
+pub fn deform_grid<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    width: u32,
+    height: u32,
+    step: u32,
+    method: DeformMethod,
+) -> Vec<(T, T)> {
+    assert!(step > 0, "The grid step must be strictly positive");
+    map_with_buffers(
+        &grid_positions(width, height, step),
+        Centered::new,
+        |buffers, &point| deform_with(controls_p, controls_q, point, method, buffers),
+    )
+}
+
+/// Same as [`deform_grid`], but returns an error instead of panicking when the step is zero,
+/// and instead of NaN or meaningless results when the configuration is degenerate,
+/// as [`try_deform`](crate::try_deform).
+pub fn try_deform_grid<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    width: u32,
+    height: u32,
+    step: u32,
+    method: DeformMethod,
+) -> Result<Vec<(T, T)>, DeformError> {
+    if step == 0 {
+        return Err(DeformError::ZeroStep);
+    }
+    let positions = grid_positions(width, height, step);
+    MlsOptions::new(method).try_deform_all(controls_p, controls_q, &positions)
+}
+
+/// Positions of the points of the grid, row by row.
+fn grid_positions<T: Scalar>(width: u32, height: u32, step: u32) -> Vec<(T, T)> {
+    let nb_columns = (width.max(1) - 1) / step + 2;
+    let nb_rows = (height.max(1) - 1) / step + 2;
+    (0..nb_rows)
+        .flat_map(|row| (0..nb_columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let x = T::from_f64((column * step) as f64);
+            let y = T::from_f64((row * step) as f64);
+            (x, y)
+        })
+        .collect()
+}
@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Numerical inversion of the MLS deformation.
+
+use crate::{deform, deform_transform, DeformMethod, Point, Scalar};
+
+/// Maximum number of Gauss-Newton iterations.
+const MAX_ITERATIONS: usize = 30;
+
+/// Find the original position of a point, given its position after the deformation
+/// that transforms the original control points into their displaced locations.
+///
+/// This is the inverse of [`deform`], computed with Gauss-Newton iterations,
+/// where the Jacobian is approximated by the local transformation of [`deform_transform`].
+/// The iterations start from the deformation with the control points reversed,
+/// which usually is a good approximation of the inverse.
+///
+/// Returns `None` if the iterations did not converge,
+/// for example if the deformation folds over itself around the point.
+pub fn inverse_deform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    warped_point: (T, T),
+    method: DeformMethod,
+) -> Option<(T, T)> {
+    let target = Point::from(warped_point);
+    let residual = |x: (T, T)| target - Point::from(deform(controls_p, controls_q, x, method));
+
+    // Start from the reversed deformation.
+    let mut x = deform(controls_q, controls_p, warped_point, method);
+    let mut r = residual(x);
+    for _ in 0..MAX_ITERATIONS {
+        if r.sqr_norm() == T::zero() {
+            break;
+        }
+        // Solve J dx = r with J approximated by the local linear transformation.
+        let [[a, b], [c, d]] = deform_transform(controls_p, controls_q, x, method).matrix;
+        let det = a * d - b * c;
+        let dx = (d * r.x - b * r.y) / det;
+        let dy = (a * r.y - c * r.x) / det;
+        let x_next = (x.0 + dx, x.1 + dy);
+        let r_next = residual(x_next);
+        // Stop when the residual does not decrease anymore (rounding errors level).
+        let decreasing = r_next.sqr_norm() < r.sqr_norm();
+        if !decreasing {
+            break;
+        }
+        x = x_next;
+        r = r_next;
+    }
+
+    // Accept the solution if the residual is small compared to the point magnitude.
+    let scale = T::one().max(target.x.abs()).max(target.y.abs());
+    let tolerance = T::epsilon().sqrt() * scale;
+    if r.sqr_norm() <= tolerance * tolerance {
+        Some(x)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Jacobian of the MLS deformation.
+
+use crate::{deform, DeformMethod, Scalar};
+
+/// Compute the 2x2 Jacobian matrix of the deformation at the given point,
+/// with `jacobian[i][j]` the derivative of the coordinate i of the deformed point
+/// with respect to the coordinate j of the original point.
+///
+/// It is estimated with central finite differences, with a step
+/// proportional to the magnitude of the point coordinates.
+/// This gives the local stretch and shear of the deformation,
+/// and its determinant is negative where the deformation folds over itself.
+pub fn jacobian<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> [[T; 2]; 2] {
+    let (x, y) = point;
+    // Step minimizing the truncation and rounding errors of central differences.
+    let h = T::epsilon().cbrt() * T::one().max(x.abs()).max(y.abs());
+    let f = |v| deform(controls_p, controls_q, v, method);
+    let two_h = h + h;
+    let (x_plus, x_minus) = (f((x + h, y)), f((x - h, y)));
+    let (y_plus, y_minus) = (f((x, y + h)), f((x, y - h)));
+    [
+        [
+            (x_plus.0 - x_minus.0) / two_h,
+            (y_plus.0 - y_minus.0) / two_h,
+        ],
+        [
+            (x_plus.1 - x_minus.1) / two_h,
+            (y_plus.1 - y_minus.1) / two_h,
+        ],
+    ]
+}
@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Weight kernels of the control points, and deformations with custom weights.
+
+use crate::{local_weighted, Centered, DeformMethod, MlsOptions, Point, Scalar};
+
+/// Weight of a control point as a function of its distance d to the deformed point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kernel<T> {
+    /// w = 1 / d^(2 alpha), as in the paper (eq 2).
+    /// The deformation interpolates the control points for any alpha > 0,
+    /// and the bigger alpha, the more local the influence of each control point.
+    InverseDistance {
+        /// Exponent of the weights, 1 in all the other functions of this crate.
+        alpha: T,
+    },
+    /// w = exp(-d^2 / (2 sigma^2)).
+    /// The weights stay finite, so the control points are only approximated,
+    /// but the influence of each control point is limited to a few sigma.
+    ///
+    /// The weights are only relative to each other, so they are scaled
+    /// to be 1 for the closest control point, and do not all vanish far from them.
+    /// Where the weights of the other control points are negligible,
+    /// the points follow the translation of the closest one.
+    Gaussian {
+        /// Standard deviation of the kernel.
+        sigma: T,
+    },
+}
+
+impl<T: Scalar> Default for Kernel<T> {
+    /// The kernel of the paper, with alpha = 1.
+    fn default() -> Self {
+        Kernel::InverseDistance { alpha: T::one() }
+    }
+}
+
+impl<T: Scalar> Kernel<T> {
+    /// Weight of a control point at the given squared distance.
+    pub fn weight(&self, sqr_dist: T) -> T {
+        match *self {
+            Kernel::InverseDistance { alpha } if alpha == T::one() => T::one() / sqr_dist,
+            Kernel::InverseDistance { alpha } => T::one() / sqr_dist.powf(alpha),
+            Kernel::Gaussian { sigma } => (-sqr_dist / (sigma * sigma + sigma * sigma)).exp(),
+        }
+    }
+
+    /// Squared distance to subtract from the ones of the control points to the point v,
+    /// before computing their weights.
+    ///
+    /// It is the one of the closest control point for the gaussian kernel,
+    /// whose weights would otherwise all underflow to zero far from the control points.
+    pub(crate) fn offset(&self, controls_p: &[(T, T)], v: Point<T>) -> T {
+        match self {
+            Kernel::InverseDistance { .. } => T::zero(),
+            Kernel::Gaussian { .. } => {
+                let mut sqr_dists = controls_p.iter().map(|&p| (Point::from(p) - v).sqr_norm());
+                let first = sqr_dists.next().unwrap_or_else(T::zero);
+                sqr_dists.fold(first, |min, d| if d < min { d } else { min })
+            }
+        }
+    }
+}
+
+/// Normalized weights of the control points at the given point,
+/// which are their relative influences on the deformation there.
+///
+/// They sum to 1, and are all zero except a single 1
+/// when the point coincides with a control point.
+pub fn influence_weights<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    point: (T, T),         // v in the paper
+    kernel: Kernel<T>,
+) -> Vec<T> {
+    let v = Point::from(point);
+    let offset = kernel.offset(controls_p, v);
+    let w_all: Vec<T> = controls_p
+        .iter()
+        .map(|&p| kernel.weight((Point::from(p) - v).sqr_norm() - offset))
+        .collect();
+    let w_sum: T = w_all.iter().copied().sum();
+    if w_sum.is_infinite() {
+        let index = w_all.iter().position(|w| w.is_infinite());
+        return (0..w_all.len())
+            .map(|i| {
+                if Some(i) == index {
+                    T::one()
+                } else {
+                    T::zero()
+                }
+            })
+            .collect();
+    }
+    w_all.into_iter().map(|w| w / w_sum).collect()
+}
+
+/// Same as [`deform`](crate::deform), but with the weights of the given kernel.
+pub fn deform_with_kernel<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    kernel: Kernel<T>,
+) -> (T, T) {
+    MlsOptions::new(method)
+        .kernel(kernel)
+        .deform(controls_p, controls_q, point)
+}
+
+/// Same as [`deform`](crate::deform), but with the exponent alpha of the weights (eq 2)
+/// depending on the location of the deformed point.
+///
+/// This gives a tighter locality of the deformation where alpha is bigger,
+/// and a smoother, more global deformation where alpha is smaller.
+/// Alpha should vary smoothly for the deformation to stay smooth.
+pub fn deform_with_alpha_map<T: Scalar, A: Fn((T, T)) -> T>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    alpha: A,
+) -> (T, T) {
+    let kernel = Kernel::InverseDistance {
+        alpha: alpha(point),
+    };
+    deform_with_kernel(controls_p, controls_q, point, method, kernel)
+}
+
+/// Same as [`deform`](crate::deform), but with the weight of each control point
+/// computed by `weight` from its squared distance to the point.
+///
+/// The weight must be infinite at distance 0 for the deformation
+/// to interpolate the control points.
+pub fn deform_with_weights<T: Scalar, F: Fn(T) -> T>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    weight: F,
+) -> (T, T) {
+    let v = Point::from(point);
+    let weight = |_, sqr_dist| weight(sqr_dist);
+    match local_weighted(
+        controls_p,
+        controls_q,
+        v,
+        method,
+        &mut Centered::new(),
+        weight,
+    ) {
+        Ok(local) => local.apply(v).into(),
+        Err(index) => controls_q[index],
+    }
+}
+
+/// Same as [`deform_affine`](crate::deform_affine), but with a custom weight function,
+/// see [`deform_with_weights`].
+pub fn deform_affine_with_weights<T: Scalar, F: Fn(T) -> T>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    weight: F,
+) -> (T, T) {
+    deform_with_weights(controls_p, controls_q, point, DeformMethod::Affine, weight)
+}
+
+/// Same as [`deform_similarity`](crate::deform_similarity), but with a custom weight function,
+/// see [`deform_with_weights`].
+pub fn deform_similarity_with_weights<T: Scalar, F: Fn(T) -> T>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    weight: F,
+) -> (T, T) {
+    deform_with_weights(
+        controls_p,
+        controls_q,
+        point,
+        DeformMethod::Similarity,
+        weight,
+    )
+}
+
+/// Same as [`deform_rigid`](crate::deform_rigid), but with a custom weight function,
+/// see [`deform_with_weights`].
+pub fn deform_rigid_with_weights<T: Scalar, F: Fn(T) -> T>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    weight: F,
+) -> (T, T) {
+    deform_with_weights(controls_p, controls_q, point, DeformMethod::Rigid, weight)
+}
@@ -5,76 +5,121 @@
 #![warn(missing_docs)]
 
 use core::iter::Sum;
-use core::ops::{Add, Mul, Sub};
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+mod batch;
+mod compose;
+mod controls;
+mod damping;
+pub mod deform3d;
+mod deformer;
+mod error;
+mod far_field;
+mod fit;
+mod grid;
+mod inverse;
+mod jacobian;
+mod kernel;
+mod normalize;
+mod options;
+mod periodic;
+mod points;
+mod projective;
+mod quality;
+mod radius;
+mod regions;
+mod rotations;
+mod scratch;
+pub mod segments;
+pub mod spatial;
+mod spherical;
+mod symmetry;
+mod tps;
+mod transform;
+mod validate;
+
+pub use batch::{deform_all, try_deform_all};
+pub use compose::{bake_composed, deform_composed, Deformation};
+pub use controls::ControlPoints;
+pub use damping::{deform_damped, HullDamping};
+pub use deformer::Deformer;
+pub use error::DeformError;
+pub use far_field::FarFieldTree;
+pub use fit::{fit_controls, fit_controls_to_flow};
+pub use grid::{deform_grid, try_deform_grid};
+pub use inverse::inverse_deform;
+pub use jacobian::jacobian;
+pub use kernel::{
+    deform_affine_with_weights, deform_rigid_with_weights, deform_similarity_with_weights,
+    deform_with_alpha_map, deform_with_kernel, deform_with_weights, influence_weights, Kernel,
+};
+pub use normalize::{deform_normalized, Normalized};
+pub use options::MlsOptions;
+pub use periodic::{deform_periodic, Periodicity};
+pub use points::{deform_points, Point2Like};
+pub use projective::deform_projective;
+pub use quality::{distortion, distortion_grid, residual_report, Distortion, ResidualReport};
+pub use radius::deform_bounded;
+pub use regions::{deform_with_rigid_regions, RigidRegion, RigidRegions};
+pub use rotations::{deform_rigid_with_rotations, deform_similarity_with_rotations};
+pub use scratch::DeformScratch;
+pub use spherical::{deform_equirectangular, deform_spherical};
+pub use symmetry::{deform_symmetric, SymmetryAxis};
+pub use tps::ThinPlateSpline;
+pub use transform::Affine2;
+pub use validate::{validate_controls, validate_controls_for, ControlIssue};
+
+/// Type of the local transformation estimated by the MLS deformation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeformMethod {
+    /// Affine 2d transformation, see [`deform_affine`].
+    Affine,
+    /// 2D similarity, see [`deform_similarity`].
+    Similarity,
+    /// 2D rigid deformation, see [`deform_rigid`].
+    Rigid,
+}
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is the one of the chosen method.
+pub fn deform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    match method {
+        DeformMethod::Affine => deform_affine(controls_p, controls_q, point),
+        DeformMethod::Similarity => deform_similarity(controls_p, controls_q, point),
+        DeformMethod::Rigid => deform_rigid(controls_p, controls_q, point),
+    }
+}
 
 /// Move a given point from its original position to its new position
 /// according to the deformation that transforms the original control points
 /// into their displaced locations.
 ///
 /// The estimated transformation is an affine 2d transformation.
-pub fn deform_affine(
-    controls_p: &[(f32, f32)], // p in the paper
-    controls_q: &[(f32, f32)], // q in the paper
-    point: (f32, f32),         // v in the paper
-) -> (f32, f32) {
+pub fn deform_affine<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> (T, T) {
     let v = Point::from(point);
-    let sqr_dist = |p: Point| (p - v).sqr_norm();
-
-    // The weight of a given control point depends on its distance to the current point.
-    // CAREFUL: this w can go to infinity.
-    let weight = |pt| 1.0 / sqr_dist(pt);
-    let w_all: Vec<_> = controls_p.iter().map(|&p| weight(p.into())).collect();
-    let w_sum: f32 = w_all.iter().sum();
-    if w_sum.is_infinite() {
-        // Most probably, at least one of the weights is infinite,
-        // because our point basically coincide with a control point.
-        let index = w_all
-            .iter()
-            .position(|w| w.is_infinite())
-            .expect("There is an infinite sum of the weights but none is infinite");
-        return controls_q[index];
+    match local(
+        controls_p,
+        controls_q,
+        v,
+        DeformMethod::Affine,
+        &mut Centered::new(),
+    ) {
+        Ok(local) => local.apply(v).into(),
+        Err(index) => controls_q[index],
     }
-
-    // Compute the centroid p*.
-    let wp_star_sum: Point = w_all
-        .iter()
-        .zip(controls_p)
-        .map(|(&w, &p)| w * Point::from(p))
-        .sum();
-    let p_star = (1.0 / w_sum) * wp_star_sum;
-
-    // Compute the centroid q*.
-    let wq_star_sum: Point = w_all
-        .iter()
-        .zip(controls_q)
-        .map(|(&w, &q)| w * Point::from(q))
-        .sum();
-    let q_star = (1.0 / w_sum) * wq_star_sum;
-
-    // Compute the affine matrix M.
-    let p_hat: Vec<Point> = controls_p
-        .iter()
-        .map(|&p| Point::from(p) - p_star)
-        .collect();
-    // mp is a 2x2 matrix.
-    let mp: Mat2 = w_all
-        .iter()
-        .zip(&p_hat)
-        .map(|(&w, &p)| w * p.times_transpose(p))
-        .sum();
-    // Compute the second part of M.
-    let mq: Mat2 = w_all
-        .iter()
-        .zip(&p_hat)
-        .zip(controls_q)
-        .map(|((&w, &ph), &q)| {
-            let qh = Point::from(q) - q_star;
-            (w * ph).times_transpose(qh)
-        })
-        .sum();
-
-    // Finally compute the projection of our original point.
-    ((v - p_star).transpose_mul(mp.inv()).transpose_mul(mq) + q_star).into()
 }
 
 /// Move a given point from its original position to its new position
@@ -82,167 +127,747 @@ pub fn deform_affine(
 /// into their displaced locations.
 ///
 /// The estimated transformation is a 2D similarity.
-pub fn deform_similarity(
-    controls_p: &[(f32, f32)], // p in the paper
-    controls_q: &[(f32, f32)], // q in the paper
-    point: (f32, f32),         // v in the paper
-) -> (f32, f32) {
+pub fn deform_similarity<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> (T, T) {
     let v = Point::from(point);
-    let sqr_dist = |p: Point| (p - v).sqr_norm();
+    match local(
+        controls_p,
+        controls_q,
+        v,
+        DeformMethod::Similarity,
+        &mut Centered::new(),
+    ) {
+        Ok(local) => local.apply(v).into(),
+        Err(index) => controls_q[index],
+    }
+}
 
-    // The weight of a given control point depends on its distance to the current point.
-    // CAREFUL: this w can go to infinity.
-    let weight = |pt| 1.0 / sqr_dist(pt);
-    let w_all: Vec<_> = controls_p.iter().map(|&p| weight(p.into())).collect();
-    let w_sum: f32 = w_all.iter().sum();
-    if w_sum.is_infinite() {
-        // Most probably, at least one of the weights is infinite,
-        // because our point basically coincide with a control point.
-        let index = w_all
-            .iter()
-            .position(|w| w.is_infinite())
-            .expect("There is an infinite sum of the weights but none is infinite");
+/// Same as [`deform_similarity`], but computed with complex numbers.
+///
+/// Treating points as complex numbers, the similarity of eq (6) simply is
+/// l(v) = (v - p*) * sum_i(w_i conj(p_hat_i) q_hat_i) / mu_s + q*,
+/// which avoids building the 2x2 matrices of every control point.
+/// Results are the same up to rounding errors.
+pub fn deform_similarity_complex<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> (T, T) {
+    let v = Point::from(point);
+    let mut c = Centered::new();
+    if let Err(index) = centered(controls_p, controls_q, v, &mut c, inverse_sqr_dist) {
         return controls_q[index];
     }
 
-    // Compute the centroid p*.
-    let wp_star_sum: Point = w_all
-        .iter()
-        .zip(controls_p)
-        .map(|(&w, &p)| w * Point::from(p))
-        .sum();
-    let p_star = (1.0 / w_sum) * wp_star_sum;
+    // Accumulate mu_s and the complex product conj(p_hat) * q_hat.
+    let mut mu_s = T::zero();
+    let mut re = T::zero();
+    let mut im = T::zero();
+    for ((&w, p), q) in c.w_all.iter().zip(&c.p_hat).zip(&c.q_hat) {
+        mu_s = mu_s + w * p.sqr_norm();
+        re = re + w * (p.x * q.x + p.y * q.y);
+        im = im + w * (p.x * q.y - p.y * q.x);
+    }
+    let (re, im) = (re / mu_s, im / mu_s);
 
-    // Compute the centroid q*.
-    let wq_star_sum: Point = w_all
+    // Complex multiplication of (v - p*) by (re + i im).
+    let d = v - c.p_star;
+    (
+        d.x * re - d.y * im + c.q_star.x,
+        d.x * im + d.y * re + c.q_star.y,
+    )
+}
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is a 2D rigid deformation.
+pub fn deform_rigid<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> (T, T) {
+    let v = Point::from(point);
+    match local(
+        controls_p,
+        controls_q,
+        v,
+        DeformMethod::Rigid,
+        &mut Centered::new(),
+    ) {
+        Ok(local) => local.apply(v).into(),
+        Err(index) => controls_q[index],
+    }
+}
+
+/// Control points interpolated between their original positions `controls_p` (t = 0)
+/// and their displaced positions `controls_q` (t = 1).
+///
+/// Using them as displaced control points gives a deformation easing
+/// from the identity into the full deformation, typically for animations.
+pub fn blend_controls<T: Scalar>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    t: T,
+) -> Vec<(T, T)> {
+    controls_p
         .iter()
         .zip(controls_q)
-        .map(|(&w, &q)| w * Point::from(q))
-        .sum();
-    let q_star = (1.0 / w_sum) * wq_star_sum;
+        .map(|(&p, &q)| {
+            let (p, q) = (Point::from(p), Point::from(q));
+            (p + (q - p) * t).into()
+        })
+        .collect()
+}
 
-    // Compute p_hat.
-    let p_hat: Vec<Point> = controls_p
-        .iter()
-        .map(|&p| Point::from(p) - p_star)
-        .collect();
+/// Same as [`deform`], but with the control points displaced only by a fraction `t`
+/// of the way from `controls_p` to `controls_q`, see [`blend_controls`].
+pub fn deform_blend<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    t: T,
+) -> (T, T) {
+    let controls_t = blend_controls(controls_p, controls_q, t);
+    deform(controls_p, &controls_t, point, method)
+}
 
-    // Compute q_hat.
-    let q_hat: Vec<Point> = controls_q
-        .iter()
-        .map(|&q| Point::from(q) - q_star)
-        .collect();
+/// Same as [`deform_rigid`], but computed with an explicit rotation,
+/// whose angle (in radians, counterclockwise for a y axis pointing up) is also returned.
+///
+/// The rotation is the one of the local rigid transformation at the point,
+/// so it can be used to rotate sprites or annotations consistently with the deformation.
+/// At a control point, it is the limit of the rotation when approaching that control point.
+pub fn deform_rigid_with_angle<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> ((T, T), T) {
+    let v = Point::from(point);
+    let mut c = Centered::new();
+    let (cos_sin, v_hat, q_star) =
+        match centered(controls_p, controls_q, v, &mut c, inverse_sqr_dist) {
+            Ok(()) => (
+                rotation_vector(
+                    c.w_all.iter().copied(),
+                    c.p_hat.iter().zip(&c.q_hat).map(|(&p, &q)| (p, q)),
+                ),
+                v - c.p_star,
+                c.q_star,
+            ),
+            Err(index) => {
+                // In the limit, the weight of the control point dominates the centroids,
+                // and the other control points are centered on it.
+                let (pi, qi) = (
+                    Point::from(controls_p[index]),
+                    Point::from(controls_q[index]),
+                );
+                let others = || {
+                    controls_p
+                        .iter()
+                        .zip(controls_q)
+                        .enumerate()
+                        .filter(move |(j, _)| *j != index)
+                };
+                let weights =
+                    others().map(|(_, (&p, _))| T::one() / (Point::from(p) - v).sqr_norm());
+                let hats = others().map(|(_, (&p, &q))| (Point::from(p) - pi, Point::from(q) - qi));
+                (rotation_vector(weights, hats), Point::zero(), qi)
+            }
+        };
 
-    // Compute mu_s (eq 6).
-    let mu_s: f32 = w_all
-        .iter()
-        .zip(&p_hat)
-        .map(|(wi, pi)| wi * pi.sqr_norm())
-        .sum();
+    // Rotate v - p* by the angle of the rotation vector.
+    let norm = cos_sin.sqr_norm().sqrt();
+    let (cos, sin) = (cos_sin.x / norm, cos_sin.y / norm);
+    let rotated = Point {
+        x: cos * v_hat.x - sin * v_hat.y,
+        y: sin * v_hat.x + cos * v_hat.y,
+    };
+    ((rotated + q_star).into(), cos_sin.y.atan2(cos_sin.x))
+}
 
-    // Compute M (eq 6)
-    let m: Mat2 = w_all
-        .iter()
-        .zip(&p_hat)
-        .zip(&q_hat)
-        .map(|((&wi, pi), qi)| {
-            let p_mat = Mat2 {
-                m11: pi.x,
-                m12: pi.y,
-                m21: pi.y,
-                m22: -pi.x,
-            };
-            let q_mat = Mat2 {
-                m11: qi.x,
-                m21: qi.y,
-                m12: qi.y,
-                m22: -qi.x,
-            };
-            wi * p_mat * q_mat
+/// Vector (mu_r times the cosine and sine of the rotation angle) of the rigid deformation,
+/// which is the mu_r vector of eq (8): sum_i w_i (q_hat_i . p_hat_i, q_hat_i . p_hat_i⊥).
+fn rotation_vector<T: Scalar>(
+    weights: impl Iterator<Item = T>,
+    hats: impl Iterator<Item = (Point<T>, Point<T>)>,
+) -> Point<T> {
+    weights
+        .zip(hats)
+        .map(|(w, (p, q))| Point {
+            x: w * p.dot(q),
+            y: w * (p.x * q.y - p.y * q.x),
         })
-        .sum();
-    let m = (1.0 / mu_s) * m;
+        .sum()
+}
 
-    // Finally compute the projection of our original point (eq 3).
-    ((v - p_star).transpose_mul(m) + q_star).into()
+/// Same as [`deform`], but with compensated (Kahan) summation of the weights and centroids.
+///
+/// This is slower, but more accurate with many control points,
+/// especially in `f32` when control points are close to each other.
+pub fn deform_compensated<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    deform_with(
+        controls_p,
+        controls_q,
+        point,
+        method,
+        &mut Centered::compensated(),
+    )
 }
 
-/// Move a given point from its original position to its new position
-/// according to the deformation that transforms the original control points
-/// into their displaced locations.
+/// Same as [`deform`], but points closer than `threshold` to a control point
+/// are translated along with it instead.
 ///
-/// The estimated transformation is a 2D rigid deformation.
-pub fn deform_rigid(
-    controls_p: &[(f32, f32)], // p in the paper
-    controls_q: &[(f32, f32)], // q in the paper
-    point: (f32, f32),         // v in the paper
-) -> (f32, f32) {
+/// Just next to a control point its weight is huge, and the rounding errors
+/// of the weighted sums make the deformation unstable.
+/// Since the deformation there is close to the translation of that control point,
+/// this replaces it with a negligible error for a small threshold,
+/// typically a fraction of pixel.
+pub fn deform_snapped<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    threshold: T,
+) -> (T, T) {
+    MlsOptions::new(method)
+        .epsilon(threshold)
+        .deform(controls_p, controls_q, point)
+}
+
+/// Same as [`deform`], but checks the control points and the deformed point,
+/// and returns an error instead of NaN or meaningless results
+/// when the configuration is degenerate.
+pub fn try_deform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> Result<(T, T), DeformError> {
+    match method {
+        DeformMethod::Affine => try_deform_affine(controls_p, controls_q, point),
+        DeformMethod::Similarity => try_deform_similarity(controls_p, controls_q, point),
+        DeformMethod::Rigid => try_deform_rigid(controls_p, controls_q, point),
+    }
+}
+
+/// Same as [`deform_affine`], but returns an error when the configuration is degenerate,
+/// for example with less than 3 control points or if they are all colinear.
+pub fn try_deform_affine<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> Result<(T, T), DeformError> {
+    error::check_inputs(controls_p, controls_q, point)?;
+    let v = Point::from(point);
+    error::check_local(
+        local(
+            controls_p,
+            controls_q,
+            v,
+            DeformMethod::Affine,
+            &mut Centered::new(),
+        ),
+        v,
+        controls_q,
+    )
+}
+
+/// Same as [`deform_similarity`], but returns an error when the configuration is degenerate,
+/// for example if all control points are at the same location.
+pub fn try_deform_similarity<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> Result<(T, T), DeformError> {
+    error::check_inputs(controls_p, controls_q, point)?;
+    let v = Point::from(point);
+    error::check_local(
+        local(
+            controls_p,
+            controls_q,
+            v,
+            DeformMethod::Similarity,
+            &mut Centered::new(),
+        ),
+        v,
+        controls_q,
+    )
+}
+
+/// Same as [`deform_rigid`], but returns an error when the configuration is degenerate,
+/// for example if all original or displaced control points are at the same location.
+pub fn try_deform_rigid<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> Result<(T, T), DeformError> {
+    error::check_inputs(controls_p, controls_q, point)?;
+    let v = Point::from(point);
+    error::check_local(
+        local(
+            controls_p,
+            controls_q,
+            v,
+            DeformMethod::Rigid,
+            &mut Centered::new(),
+        ),
+        v,
+        controls_q,
+    )
+}
+
+/// Estimate the local transformation of the chosen method
+/// for the deformation at the given point.
+///
+/// Applying it to the point gives the same result than [`deform`].
+/// When the point coincides with a control point, the transformation is
+/// the translation from that control point to its displaced location.
+pub fn deform_transform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> Affine2<T> {
+    match method {
+        DeformMethod::Affine => deform_affine_transform(controls_p, controls_q, point),
+        DeformMethod::Similarity => deform_similarity_transform(controls_p, controls_q, point),
+        DeformMethod::Rigid => deform_rigid_transform(controls_p, controls_q, point),
+    }
+}
+
+/// Estimate the local affine transformation l_v of the deformation at the given point.
+///
+/// Applying it to the point gives the same result than [`deform_affine`].
+/// When the point coincides with a control point, the transformation is
+/// the translation from that control point to its displaced location.
+pub fn deform_affine_transform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> Affine2<T> {
+    let v = Point::from(point);
+    let local = local(
+        controls_p,
+        controls_q,
+        v,
+        DeformMethod::Affine,
+        &mut Centered::new(),
+    );
+    Affine2::from_local(local, controls_p, controls_q)
+}
+
+/// Estimate the local similarity l_v of the deformation at the given point.
+///
+/// Applying it to the point gives the same result than [`deform_similarity`].
+/// When the point coincides with a control point, the transformation is
+/// the translation from that control point to its displaced location.
+pub fn deform_similarity_transform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> Affine2<T> {
     let v = Point::from(point);
-    let sqr_dist = |p: Point| (p - v).sqr_norm();
+    let local = local(
+        controls_p,
+        controls_q,
+        v,
+        DeformMethod::Similarity,
+        &mut Centered::new(),
+    );
+    Affine2::from_local(local, controls_p, controls_q)
+}
+
+/// Estimate the local rigid transformation l_v of the deformation at the given point.
+///
+/// Applying it to the point gives the same result than [`deform_rigid`].
+/// When the point coincides with a control point, the transformation is
+/// the translation from that control point to its displaced location.
+pub fn deform_rigid_transform<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> Affine2<T> {
+    let v = Point::from(point);
+    let local = local(
+        controls_p,
+        controls_q,
+        v,
+        DeformMethod::Rigid,
+        &mut Centered::new(),
+    );
+    Affine2::from_local(local, controls_p, controls_q)
+}
+
+// Local transformations #######################################################
 
+/// Local transformation l_v(x) = (x - p*) M + q* estimated at a point v (eq 1).
+#[derive(Clone, Copy)]
+struct Local<T> {
+    p_star: Point<T>,
+    q_star: Point<T>,
+    m: Mat2<T>,
+    /// True if the system solved to compute M is singular,
+    /// in which case M is meaningless.
+    singular: bool,
+}
+
+impl<T: Scalar> Local<T> {
+    /// Apply the local transformation to a point.
+    fn apply(&self, x: Point<T>) -> Point<T> {
+        (x - self.p_star).transpose_mul(self.m) + self.q_star
+    }
+}
+
+/// Same as [`deform`], reusing the buffers of `c`.
+fn deform_with<T: Scalar>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    point: (T, T),
+    method: DeformMethod,
+    c: &mut Centered<T>,
+) -> (T, T) {
+    let v = Point::from(point);
+    match local(controls_p, controls_q, v, method, c) {
+        Ok(local) => local.apply(v).into(),
+        // v is at (or snapped to) a control point, so it follows its translation.
+        Err(index) => (Point::from(controls_q[index]) + v - Point::from(controls_p[index])).into(),
+    }
+}
+
+/// Estimate the local transformation of the chosen method at v,
+/// with the inverse squared distance weights of the paper.
+fn local<T: Scalar>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    v: Point<T>,
+    method: DeformMethod,
+    c: &mut Centered<T>,
+) -> Result<Local<T>, usize> {
+    local_weighted(controls_p, controls_q, v, method, c, inverse_sqr_dist)
+}
+
+/// Estimate the local transformation of the chosen method at v,
+/// with the weights computed by `weight(index, sqr_dist)` for each control point.
+fn local_weighted<T: Scalar, W: Fn(usize, T) -> T>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    v: Point<T>,
+    method: DeformMethod,
+    c: &mut Centered<T>,
+    weight: W,
+) -> Result<Local<T>, usize> {
+    centered(controls_p, controls_q, v, c, weight)?;
+    Ok(match method {
+        DeformMethod::Affine => local_affine(c),
+        DeformMethod::Similarity => local_similarity(c),
+        DeformMethod::Rigid => local_rigid(c),
+    })
+}
+
+/// Weight of a control point in the paper (with alpha = 1): w = 1 / |p - v|^2.
+fn inverse_sqr_dist<T: Scalar>(_index: usize, sqr_dist: T) -> T {
+    T::one() / sqr_dist
+}
+
+/// Weights, centroids and centered control points for a given point v.
+struct Centered<T> {
+    w_all: Vec<T>,
+    w_sum: T,
+    p_star: Point<T>,
+    q_star: Point<T>,
+    p_hat: Vec<Point<T>>,
+    q_hat: Vec<Point<T>>,
+    /// Use compensated summation for the weights and centroids.
+    compensated: bool,
+    /// Squared distance under which v is snapped to its nearest control point.
+    snap_sqr_dist: T,
+}
+
+impl<T: Scalar> Centered<T> {
+    /// Empty buffers.
+    fn new() -> Self {
+        Self {
+            compensated: false,
+            snap_sqr_dist: T::zero(),
+            w_all: Vec::new(),
+            w_sum: T::zero(),
+            p_star: Point::zero(),
+            q_star: Point::zero(),
+            p_hat: Vec::new(),
+            q_hat: Vec::new(),
+        }
+    }
+
+    /// Empty buffers, with compensated summation of the weights and centroids.
+    fn compensated() -> Self {
+        Self {
+            compensated: true,
+            ..Self::new()
+        }
+    }
+
+    /// Empty buffers, snapping v to its nearest control point when closer than `threshold`.
+    fn snapping(threshold: T) -> Self {
+        Self {
+            snap_sqr_dist: threshold * threshold,
+            ..Self::new()
+        }
+    }
+
+    /// Check if the given normalization factor mu (eq 6) is zero,
+    /// or negligible compared to the rounding errors of p_hat,
+    /// meaning that the control points basically are all the same.
+    fn is_degenerate(&self, mu: T) -> bool {
+        mu <= T::epsilon() * self.w_sum * self.p_star.sqr_norm() || !mu.is_finite()
+    }
+
+    /// Check if the weights of all the control points but one are negligible,
+    /// like the gaussian weights far from the control points.
+    fn is_concentrated(&self) -> bool {
+        let max = self.w_all.iter().fold(T::zero(), |max, &w| max.max(w));
+        self.w_sum - max <= T::epsilon() * self.w_sum
+    }
+}
+
+/// Compute the weights, the centroids p* and q*, as well as p_hat and q_hat.
+/// The buffers of `c` are reused, so that it can serve for multiple points.
+///
+/// Returns the index of the control point coinciding with v if there is one.
+fn centered<T: Scalar, W: Fn(usize, T) -> T>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    v: Point<T>,
+    c: &mut Centered<T>,
+    weight: W,
+) -> Result<(), usize> {
     // The weight of a given control point depends on its distance to the current point.
     // CAREFUL: this w can go to infinity.
-    let weight = |pt| 1.0 / sqr_dist(pt);
-    let w_all: Vec<_> = controls_p.iter().map(|&p| weight(p.into())).collect();
-    let w_sum: f32 = w_all.iter().sum();
+    c.w_all.clear();
+    let mut snapped: Option<(usize, T)> = None;
+    for (i, &p) in controls_p.iter().enumerate() {
+        let sqr_dist = (Point::from(p) - v).sqr_norm();
+        let closer = snapped.map_or(true, |(_, d)| sqr_dist < d);
+        if c.snap_sqr_dist > T::zero() && sqr_dist <= c.snap_sqr_dist && closer {
+            snapped = Some((i, sqr_dist));
+        }
+        c.w_all.push(weight(i, sqr_dist));
+    }
+    if let Some((index, _)) = snapped {
+        // v is too close to a control point for the weights to be stable.
+        return Err(index);
+    }
+    let w_sum: T = sum(c.compensated, T::zero(), c.w_all.iter().copied());
     if w_sum.is_infinite() {
         // Most probably, at least one of the weights is infinite,
         // because our point basically coincide with a control point.
-        let index = w_all
+        let index = c
+            .w_all
             .iter()
             .position(|w| w.is_infinite())
             .expect("There is an infinite sum of the weights but none is infinite");
-        return controls_q[index];
+        return Err(index);
     }
+    c.w_sum = w_sum;
 
     // Compute the centroid p*.
-    let wp_star_sum: Point = w_all
-        .iter()
-        .zip(controls_p)
-        .map(|(&w, &p)| w * Point::from(p))
-        .sum();
-    let p_star = (1.0 / w_sum) * wp_star_sum;
+    let wp_star_sum: Point<T> = sum(
+        c.compensated,
+        Point::zero(),
+        c.w_all
+            .iter()
+            .zip(controls_p)
+            .map(|(&w, &p)| Point::from(p) * w),
+    );
+    let p_star = wp_star_sum * (T::one() / w_sum);
+    c.p_star = p_star;
 
     // Compute the centroid q*.
-    let wq_star_sum: Point = w_all
-        .iter()
-        .zip(controls_q)
-        .map(|(&w, &q)| w * Point::from(q))
-        .sum();
-    let q_star = (1.0 / w_sum) * wq_star_sum;
+    let wq_star_sum: Point<T> = sum(
+        c.compensated,
+        Point::zero(),
+        c.w_all
+            .iter()
+            .zip(controls_q)
+            .map(|(&w, &q)| Point::from(q) * w),
+    );
+    let q_star = wq_star_sum * (T::one() / w_sum);
+    c.q_star = q_star;
 
     // Compute p_hat.
-    let p_hat: Vec<Point> = controls_p
-        .iter()
-        .map(|&p| Point::from(p) - p_star)
-        .collect();
+    c.p_hat.clear();
+    c.p_hat
+        .extend(controls_p.iter().map(|&p| Point::from(p) - p_star));
 
     // Compute q_hat.
-    let q_hat: Vec<Point> = controls_q
-        .iter()
-        .map(|&q| Point::from(q) - q_star)
-        .collect();
+    c.q_hat.clear();
+    c.q_hat
+        .extend(controls_q.iter().map(|&q| Point::from(q) - q_star));
+
+    Ok(())
+}
+
+/// Sum of the terms, with Kahan compensated summation if `compensated` is true.
+///
+/// Compensated summation keeps track of the rounding error of each addition,
+/// so the result stays accurate with many terms of very different magnitudes,
+/// typically with hundreds of control points close to each other.
+fn sum<X, I>(compensated: bool, zero: X, terms: I) -> X
+where
+    X: Copy + Add<Output = X> + Sub<Output = X> + Sum,
+    I: Iterator<Item = X>,
+{
+    if !compensated {
+        return accumulate(zero, terms);
+    }
+    let mut total = zero;
+    let mut compensation = zero;
+    for x in terms {
+        let y = x - compensation;
+        let t = total + y;
+        compensation = (t - total) - y;
+        total = t;
+    }
+    total
+}
+
+/// Sum of the terms, accumulated one after the other.
+#[cfg(not(feature = "simd"))]
+fn accumulate<X, I>(_zero: X, terms: I) -> X
+where
+    X: Copy + Add<Output = X> + Sum,
+    I: Iterator<Item = X>,
+{
+    terms.sum()
+}
+
+/// Sum of the terms, accumulated in 4 independent lanes
+/// which the compiler can vectorize, processing 4 control points per iteration.
+///
+/// The lanes are combined in a fixed order, so results are deterministic,
+/// but may differ from the sequential sum by rounding errors.
+#[cfg(feature = "simd")]
+fn accumulate<X, I>(zero: X, terms: I) -> X
+where
+    X: Copy + Add<Output = X> + Sum,
+    I: Iterator<Item = X>,
+{
+    let mut lanes = [zero; 4];
+    let mut terms = terms.fuse();
+    while let Some(x0) = terms.next() {
+        lanes[0] = lanes[0] + x0;
+        if let Some(x1) = terms.next() {
+            lanes[1] = lanes[1] + x1;
+        }
+        if let Some(x2) = terms.next() {
+            lanes[2] = lanes[2] + x2;
+        }
+        if let Some(x3) = terms.next() {
+            lanes[3] = lanes[3] + x3;
+        }
+    }
+    (lanes[0] + lanes[1]) + (lanes[2] + lanes[3])
+}
+
+/// Estimate the local affine transformation at v, from its centered control points.
+fn local_affine<T: Scalar>(c: &Centered<T>) -> Local<T> {
+    // Compute the affine matrix M.
+    // mp is a 2x2 matrix.
+    let mp: Mat2<T> = accumulate(
+        Mat2::zero(),
+        c.w_all
+            .iter()
+            .zip(&c.p_hat)
+            .map(|(&w, &p)| p.times_transpose(p) * w),
+    );
+    // Compute the second part of M.
+    let mq: Mat2<T> = accumulate(
+        Mat2::zero(),
+        c.w_all
+            .iter()
+            .zip(&c.p_hat)
+            .zip(&c.q_hat)
+            .map(|((&w, &ph), &qh)| (ph * w).times_transpose(qh)),
+    );
+
+    Local {
+        p_star: c.p_star,
+        q_star: c.q_star,
+        m: mp.inv() * mq,
+        singular: mp.is_singular(),
+    }
+}
+
+/// Estimate the local similarity at v, from its centered control points.
+fn local_similarity<T: Scalar>(c: &Centered<T>) -> Local<T> {
+    // Compute mu_s (eq 6).
+    let mu_s: T = accumulate(
+        T::zero(),
+        c.w_all
+            .iter()
+            .zip(&c.p_hat)
+            .map(|(&wi, pi)| wi * pi.sqr_norm()),
+    );
+
+    // Compute M (eq 6)
+    let m = similarity_matrix(c) * (T::one() / mu_s);
+    Local {
+        p_star: c.p_star,
+        q_star: c.q_star,
+        m,
+        singular: c.is_degenerate(mu_s),
+    }
+}
 
+/// Estimate the local rigid transformation at v, from its centered control points.
+fn local_rigid<T: Scalar>(c: &Centered<T>) -> Local<T> {
     // Compute mu_r.
-    let mu_r_vec: Point = w_all
-        .iter()
-        .zip(&p_hat)
-        .zip(&q_hat)
-        .map(|((&wi, pi), qi)| {
-            let pi_perp = Point { x: -pi.y, y: pi.x };
-            Point {
-                x: wi * qi.dot(*pi),
-                y: wi * qi.dot(pi_perp),
-            }
-        })
-        .sum();
+    let mu_r_vec: Point<T> = accumulate(
+        Point::zero(),
+        c.w_all
+            .iter()
+            .zip(&c.p_hat)
+            .zip(&c.q_hat)
+            .map(|((&wi, pi), qi)| {
+                let pi_perp = Point { x: -pi.y, y: pi.x };
+                Point {
+                    x: wi * qi.dot(*pi),
+                    y: wi * qi.dot(pi_perp),
+                }
+            }),
+    );
     let mu_r = mu_r_vec.sqr_norm().sqrt();
 
     // Compute M (eq 6)
-    let m: Mat2 = w_all
+    let m = similarity_matrix(c) * (T::one() / mu_r);
+    Local {
+        p_star: c.p_star,
+        q_star: c.q_star,
+        m,
+        // mu_r is zero, or negligible compared to the rounding errors of p_hat and q_hat.
+        singular: mu_r
+            <= T::epsilon() * c.w_sum * (c.p_star.sqr_norm() * c.q_star.sqr_norm()).sqrt()
+            || !mu_r.is_finite(),
+    }
+}
+
+/// Compute the sum in M (eq 6), shared by the similarity and rigid deformations.
+fn similarity_matrix<T: Scalar>(c: &Centered<T>) -> Mat2<T> {
+    let terms = c
+        .w_all
         .iter()
-        .zip(&p_hat)
-        .zip(&q_hat)
+        .zip(&c.p_hat)
+        .zip(&c.q_hat)
         .map(|((&wi, pi), qi)| {
             let p_mat = Mat2 {
                 m11: pi.x,
@@ -256,43 +881,174 @@ pub fn deform_rigid(
                 m12: qi.y,
                 m22: -qi.x,
             };
-            wi * p_mat * q_mat
-        })
-        .sum();
-    let m = (1.0 / mu_r) * m;
+            p_mat * q_mat * wi
+        });
+    accumulate(Mat2::zero(), terms)
+}
+
+// Scalar trait ################################################################
+// That's to avoid a dependency on a package such as num-traits
+
+/// Floating point scalar type usable for the MLS computations.
+///
+/// It is implemented for `f32` and `f64`, so that callers needing more precision,
+/// typically with large image coordinates, can use `f64` points.
+pub trait Scalar:
+    Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Sum
+    + Send
+    + Sync
+{
+    /// 0
+    fn zero() -> Self;
+
+    /// 1
+    fn one() -> Self;
+
+    /// Square root.
+    fn sqrt(self) -> Self;
+
+    /// True if this value is positive or negative infinity.
+    fn is_infinite(self) -> bool;
+
+    /// True if this value is neither infinite nor NaN.
+    fn is_finite(self) -> bool;
+
+    /// Simultaneously computes the sine and cosine of self (in radians).
+    fn sin_cos(self) -> (Self, Self);
+
+    /// Four quadrant arctangent of self (y) and other (x) in radians.
+    fn atan2(self, other: Self) -> Self;
+
+    /// Natural logarithm.
+    fn ln(self) -> Self;
+
+    /// Exponential function.
+    fn exp(self) -> Self;
+
+    /// Raise self to a floating point power.
+    fn powf(self, n: Self) -> Self;
+
+    /// Cube root.
+    fn cbrt(self) -> Self;
+
+    /// Absolute value.
+    fn abs(self) -> Self;
+
+    /// Largest integer less than or equal to self.
+    fn floor(self) -> Self;
+
+    /// Maximum of two numbers, ignoring NaN.
+    fn max(self, other: Self) -> Self;
+
+    /// Machine epsilon value.
+    fn epsilon() -> Self;
+
+    /// Conversion from a f64, possibly losing precision.
+    fn from_f64(x: f64) -> Self;
+
+    /// Conversion into a f64.
+    fn to_f64(self) -> f64;
+}
 
-    // Finally compute the projection of our original point (eq 3).
-    ((v - p_star).transpose_mul(m) + q_star).into()
+macro_rules! impl_scalar {
+    ($t:ty) => {
+        impl Scalar for $t {
+            fn zero() -> Self {
+                0.0
+            }
+            fn one() -> Self {
+                1.0
+            }
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+            fn is_infinite(self) -> bool {
+                <$t>::is_infinite(self)
+            }
+            fn is_finite(self) -> bool {
+                <$t>::is_finite(self)
+            }
+            fn sin_cos(self) -> (Self, Self) {
+                <$t>::sin_cos(self)
+            }
+            fn atan2(self, other: Self) -> Self {
+                <$t>::atan2(self, other)
+            }
+            fn ln(self) -> Self {
+                <$t>::ln(self)
+            }
+            fn exp(self) -> Self {
+                <$t>::exp(self)
+            }
+            fn powf(self, n: Self) -> Self {
+                <$t>::powf(self, n)
+            }
+            fn cbrt(self) -> Self {
+                <$t>::cbrt(self)
+            }
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+            fn floor(self) -> Self {
+                <$t>::floor(self)
+            }
+            fn max(self, other: Self) -> Self {
+                <$t>::max(self, other)
+            }
+            fn epsilon() -> Self {
+                <$t>::EPSILON
+            }
+            fn from_f64(x: f64) -> Self {
+                x as $t
+            }
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        }
+    };
 }
 
+impl_scalar!(f32);
+impl_scalar!(f64);
+
 // 2D points helper ############################################################
 // That's to avoid a dependency on a heavy package such as nalgebra
 
 /// Point represented by a 2x1 column vector.
 #[derive(Clone, Copy)]
-struct Point {
-    x: f32,
-    y: f32,
+struct Point<T> {
+    x: T,
+    y: T,
 }
 
-impl Point {
+impl<T: Scalar> Point<T> {
     /// 0
     fn zero() -> Self {
-        Self { x: 0.0, y: 0.0 }
+        Self {
+            x: T::zero(),
+            y: T::zero(),
+        }
     }
 
     /// Dot product with another point.
-    fn dot(self, rhs: Self) -> f32 {
+    fn dot(self, rhs: Self) -> T {
         self.x * rhs.x + self.y * rhs.y
     }
 
     /// Square norm.
-    fn sqr_norm(self) -> f32 {
+    fn sqr_norm(self) -> T {
         self.x * self.x + self.y * self.y
     }
 
     /// Create a 2x2 matrix from a 2x1 point
-    fn times_transpose(self, rhs: Self) -> Mat2 {
+    fn times_transpose(self, rhs: Self) -> Mat2<T> {
         Mat2 {
             m11: self.x * rhs.x,
             m21: self.y * rhs.x,
@@ -303,7 +1059,7 @@ impl Point {
 
     /// Multiply with a Mat2 on the right.
     /// Returns a Point even though it should be a line vector (no big deal).
-    fn transpose_mul(self, rhs: Mat2) -> Self {
+    fn transpose_mul(self, rhs: Mat2<T>) -> Self {
         Self {
             x: rhs.m11 * self.x + rhs.m21 * self.y,
             y: rhs.m12 * self.x + rhs.m22 * self.y,
@@ -312,21 +1068,21 @@ impl Point {
 }
 
 // Convert from (x,y) to Point { x, y }
-impl From<(f32, f32)> for Point {
-    fn from((x, y): (f32, f32)) -> Self {
+impl<T> From<(T, T)> for Point<T> {
+    fn from((x, y): (T, T)) -> Self {
         Point { x, y }
     }
 }
 
 // Convert from Point { x, y } to (x,y)
-impl From<Point> for (f32, f32) {
-    fn from(point: Point) -> (f32, f32) {
+impl<T> From<Point<T>> for (T, T) {
+    fn from(point: Point<T>) -> (T, T) {
         (point.x, point.y)
     }
 }
 
 // Add two points
-impl Add for Point {
+impl<T: Scalar> Add for Point<T> {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
         Self {
@@ -337,7 +1093,7 @@ impl Add for Point {
 }
 
 // Substract a point
-impl Sub for Point {
+impl<T: Scalar> Sub for Point<T> {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self::Output {
         Self {
@@ -348,18 +1104,18 @@ impl Sub for Point {
 }
 
 // Scalar multiplication
-impl Mul<Point> for f32 {
-    type Output = Point;
-    fn mul(self, rhs: Point) -> Self::Output {
+impl<T: Scalar> Mul<T> for Point<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self::Output {
         Point {
-            x: self * rhs.x,
-            y: self * rhs.y,
+            x: self.x * rhs,
+            y: self.y * rhs,
         }
     }
 }
 
 // Sum an iterator of points
-impl Sum for Point {
+impl<T: Scalar> Sum for Point<T> {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.fold(Self::zero(), |s, p| s + p)
     }
@@ -373,43 +1129,49 @@ impl Sum for Point {
 /// | m11  m12 |
 /// | m21  m22 |
 #[derive(Clone, Copy)]
-struct Mat2 {
-    m11: f32,
-    m21: f32,
-    m12: f32,
-    m22: f32,
+struct Mat2<T> {
+    m11: T,
+    m21: T,
+    m12: T,
+    m22: T,
 }
 
-impl Mat2 {
+impl<T: Scalar> Mat2<T> {
     /// 0
     fn zero() -> Self {
         Self {
-            m11: 0.0,
-            m21: 0.0,
-            m12: 0.0,
-            m22: 0.0,
+            m11: T::zero(),
+            m21: T::zero(),
+            m12: T::zero(),
+            m22: T::zero(),
         }
     }
 
     /// Determinant
-    fn det(self) -> f32 {
+    fn det(self) -> T {
         self.m11 * self.m22 - self.m21 * self.m12
     }
 
+    /// Check if the determinant is zero, or negligible compared to the rounding errors.
+    fn is_singular(self) -> bool {
+        let magnitude = (self.m11 * self.m22).abs() + (self.m21 * self.m12).abs();
+        let det = self.det().abs();
+        det <= T::epsilon() * magnitude || !det.is_finite()
+    }
+
     /// Inverse of a matrix (does not check if det is 0)
     fn inv(self) -> Self {
-        1.0 / self.det()
-            * Self {
-                m11: self.m22,
-                m21: -self.m21,
-                m12: -self.m12,
-                m22: self.m11,
-            }
+        Self {
+            m11: self.m22,
+            m21: -self.m21,
+            m12: -self.m12,
+            m22: self.m11,
+        } * (T::one() / self.det())
     }
 }
 
 // Add two matrices
-impl Add for Mat2 {
+impl<T: Scalar> Add for Mat2<T> {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
         Self {
@@ -421,21 +1183,34 @@ impl Add for Mat2 {
     }
 }
 
+// Subtract two matrices
+impl<T: Scalar> Sub for Mat2<T> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            m11: self.m11 - rhs.m11,
+            m21: self.m21 - rhs.m21,
+            m12: self.m12 - rhs.m12,
+            m22: self.m22 - rhs.m22,
+        }
+    }
+}
+
 // Scalar multiplication
-impl Mul<Mat2> for f32 {
-    type Output = Mat2;
-    fn mul(self, rhs: Mat2) -> Self::Output {
+impl<T: Scalar> Mul<T> for Mat2<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self::Output {
         Mat2 {
-            m11: self * rhs.m11,
-            m21: self * rhs.m21,
-            m12: self * rhs.m12,
-            m22: self * rhs.m22,
+            m11: self.m11 * rhs,
+            m21: self.m21 * rhs,
+            m12: self.m12 * rhs,
+            m22: self.m22 * rhs,
         }
     }
 }
 
 // Matrix multiplication
-impl Mul for Mat2 {
+impl<T: Scalar> Mul for Mat2<T> {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self::Output {
         Mat2 {
@@ -448,7 +1223,7 @@ impl Mul for Mat2 {
 }
 
 // Sum an iterator of matrices
-impl Sum for Mat2 {
+impl<T: Scalar> Sum for Mat2<T> {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.fold(Self::zero(), |s, m| s + m)
     }
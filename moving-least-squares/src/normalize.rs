@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Normalization of the coordinates, for a better numerical precision.
+//!
+//! With large coordinates, typically pixels of big images in `f32`,
+//! the weighted sums of the centroids and matrices lose precision.
+//! Since all three deformations commute with translations and uniform scalings
+//! of the whole configuration, we can instead compute them on coordinates
+//! centered on the control points and scaled to unit size,
+//! and then bring the result back to the original coordinates.
+
+use crate::{deform, DeformMethod, Point, Scalar};
+
+/// Control points normalized around their centroid, to deform many points.
+pub struct Normalized<T> {
+    center: Point<T>,
+    scale: T,
+    controls_p: Vec<(T, T)>,
+    controls_q: Vec<(T, T)>,
+}
+
+impl<T: Scalar> Normalized<T> {
+    /// Normalize the original and displaced control points with the same
+    /// translation and scaling, such that the original ones are centered
+    /// on the origin and at a root mean square distance of 1 from it.
+    pub fn new(controls_p: &[(T, T)], controls_q: &[(T, T)]) -> Self {
+        // Without control points, keep the identity normalization.
+        if controls_p.is_empty() {
+            return Self {
+                center: Point::zero(),
+                scale: T::one(),
+                controls_p: Vec::new(),
+                controls_q: controls_q.to_vec(),
+            };
+        }
+        let n = T::from_f64(controls_p.len() as f64);
+        let center = controls_p.iter().map(|&p| Point::from(p)).sum::<Point<T>>() * (T::one() / n);
+        let sqr_spread: T = controls_p
+            .iter()
+            .map(|&p| (Point::from(p) - center).sqr_norm())
+            .sum();
+        let spread = (sqr_spread / n).sqrt();
+        // Do not scale if all points are at the same location.
+        let scale = if spread > T::zero() && spread.is_finite() {
+            spread
+        } else {
+            T::one()
+        };
+        let normalize = |points: &[(T, T)]| {
+            points
+                .iter()
+                .map(|&p| ((Point::from(p) - center) * (T::one() / scale)).into())
+                .collect()
+        };
+        Self {
+            center,
+            scale,
+            controls_p: normalize(controls_p),
+            controls_q: normalize(controls_q),
+        }
+    }
+
+    /// Move a given point with the deformation of the normalized control points,
+    /// with the result in the original coordinates.
+    pub fn deform(&self, point: (T, T), method: DeformMethod) -> (T, T) {
+        let v = (Point::from(point) - self.center) * (T::one() / self.scale);
+        let deformed = deform(&self.controls_p, &self.controls_q, v.into(), method);
+        (Point::from(deformed) * self.scale + self.center).into()
+    }
+}
+
+/// Same as [`deform`], but computed in normalized coordinates.
+///
+/// To deform many points with the same control points,
+/// create a [`Normalized`] once instead.
+pub fn deform_normalized<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    Normalized::new(controls_p, controls_q).deform(point, method)
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Options of the MLS deformation, gathered in a single builder
+//! instead of one function per combination of options.
+
+use crate::batch::map_with_buffers;
+use crate::error::check_inputs;
+use crate::{local_weighted, Centered, DeformError, DeformMethod, Kernel, Point, Scalar};
+
+/// Options of the MLS deformation, built from [`MlsOptions::new`]
+/// and the chainable setters below,
+/// such as `MlsOptions::new(DeformMethod::Rigid).alpha(1.5).max_radius(20.0)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MlsOptions<T> {
+    /// Type of the local transformation.
+    pub method: DeformMethod,
+    /// Weights of the control points, the inverse squared distance of the paper by default.
+    /// The exponent alpha of eq (2) is set with [`MlsOptions::alpha`].
+    pub kernel: Kernel<T>,
+    /// Points closer than epsilon to a control point are translated along with it,
+    /// see [`deform_snapped`](crate::deform_snapped). 0 by default.
+    pub epsilon: T,
+    /// Radius beyond which control points have no influence,
+    /// see [`deform_bounded`](crate::deform_bounded). `None` by default.
+    pub max_radius: Option<T>,
+    /// Split batch deformations across cores, if the `rayon` feature is enabled.
+    /// True by default.
+    pub parallel: bool,
+}
+
+impl<T: Scalar> MlsOptions<T> {
+    /// Default options for the given method, which are those of the paper.
+    pub fn new(method: DeformMethod) -> Self {
+        Self {
+            method,
+            kernel: Kernel::default(),
+            epsilon: T::zero(),
+            max_radius: None,
+            parallel: true,
+        }
+    }
+
+    /// Set the type of the local transformation.
+    pub fn method(mut self, method: DeformMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Use the inverse distance kernel with the given exponent alpha (eq 2).
+    pub fn alpha(mut self, alpha: T) -> Self {
+        self.kernel = Kernel::InverseDistance { alpha };
+        self
+    }
+
+    /// Set the weights kernel.
+    pub fn kernel(mut self, kernel: Kernel<T>) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// Set the snapping distance to the control points.
+    pub fn epsilon(mut self, epsilon: T) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Limit the influence of control points to the given radius.
+    pub fn max_radius(mut self, radius: T) -> Self {
+        self.max_radius = Some(radius);
+        self
+    }
+
+    /// Enable or disable the parallelization of batch deformations.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Move a given point with the deformation of these options.
+    pub fn deform(
+        &self,
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        point: (T, T),         // v in the paper
+    ) -> (T, T) {
+        self.deform_with(controls_p, controls_q, point, &mut self.buffers())
+    }
+
+    /// Move all the given points with the deformation of these options,
+    /// reusing the internal buffers as [`deform_all`](crate::deform_all).
+    pub fn deform_all(
+        &self,
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        points: &[(T, T)],     // v in the paper
+    ) -> Vec<(T, T)> {
+        let f = |c: &mut Centered<T>, &point: &(T, T)| {
+            self.deform_with(controls_p, controls_q, point, c)
+        };
+        if self.parallel {
+            map_with_buffers(points, || self.buffers(), f)
+        } else {
+            let mut c = self.buffers();
+            points.iter().map(|point| f(&mut c, point)).collect()
+        }
+    }
+
+    /// Same as [`MlsOptions::deform`], but checks the control points and the deformed point,
+    /// and returns an error instead of NaN or meaningless results
+    /// when the configuration is degenerate, as [`try_deform`](crate::try_deform).
+    pub fn try_deform(
+        &self,
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        point: (T, T),         // v in the paper
+    ) -> Result<(T, T), DeformError> {
+        check_inputs(controls_p, controls_q, point)?;
+        self.evaluate(controls_p, controls_q, point, &mut self.buffers(), true)
+    }
+
+    /// Same as [`MlsOptions::deform_all`], but returns the first error of
+    /// [`MlsOptions::try_deform`] if the deformation fails at any of the points.
+    pub fn try_deform_all(
+        &self,
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        points: &[(T, T)],     // v in the paper
+    ) -> Result<Vec<(T, T)>, DeformError> {
+        let origin = (T::zero(), T::zero());
+        check_inputs(controls_p, controls_q, origin)?;
+        let f = |c: &mut Centered<T>, &point: &(T, T)| {
+            let (x, y) = point;
+            if !(x.is_finite() && y.is_finite()) {
+                return Err(DeformError::NonFinitePoint);
+            }
+            self.evaluate(controls_p, controls_q, point, c, true)
+        };
+        if self.parallel {
+            map_with_buffers(points, || self.buffers(), f)
+                .into_iter()
+                .collect()
+        } else {
+            let mut c = self.buffers();
+            points.iter().map(|point| f(&mut c, point)).collect()
+        }
+    }
+
+    /// Empty buffers for these options.
+    fn buffers(&self) -> Centered<T> {
+        Centered::snapping(self.epsilon)
+    }
+
+    /// Same as [`MlsOptions::deform`], reusing the buffers of `c`.
+    fn deform_with(
+        &self,
+        controls_p: &[(T, T)],
+        controls_q: &[(T, T)],
+        point: (T, T),
+        c: &mut Centered<T>,
+    ) -> (T, T) {
+        self.evaluate(controls_p, controls_q, point, c, false)
+            .unwrap_or(point)
+    }
+
+    /// Compute the deformation of the point,
+    /// returning an error for singular local transformations if `strict`.
+    fn evaluate(
+        &self,
+        controls_p: &[(T, T)],
+        controls_q: &[(T, T)],
+        point: (T, T),
+        c: &mut Centered<T>,
+        strict: bool,
+    ) -> Result<(T, T), DeformError> {
+        let v = Point::from(point);
+        let kernel = self.kernel;
+        let offset = kernel.offset(controls_p, v);
+        let local = match self.max_radius {
+            None => {
+                let weight = |_, sqr_dist| kernel.weight(sqr_dist - offset);
+                local_weighted(controls_p, controls_q, v, self.method, c, weight)
+            }
+            Some(radius) => {
+                let sqr_radius = radius * radius;
+                let in_range = |&p: &(T, T)| (Point::from(p) - v).sqr_norm() < sqr_radius;
+                if !controls_p.iter().any(in_range) {
+                    return Ok(point);
+                }
+                // Same fading as deform_bounded, applied to the kernel weight.
+                let weight = |_, sqr_dist: T| {
+                    if sqr_dist < sqr_radius {
+                        let fade = T::one() - sqr_dist / sqr_radius;
+                        fade * fade * kernel.weight(sqr_dist - offset)
+                    } else {
+                        T::zero()
+                    }
+                };
+                match local_weighted(controls_p, controls_q, v, self.method, c, weight) {
+                    Ok(local) if local.singular && !strict => {
+                        return Ok((v - local.p_star + local.q_star).into())
+                    }
+                    local => local,
+                }
+            }
+        };
+        match local {
+            // v only depends on a single control point, so it follows its translation.
+            Ok(local) if local.singular && c.is_concentrated() => {
+                Ok((v - local.p_star + local.q_star).into())
+            }
+            Ok(local) if strict && local.singular => Err(DeformError::SingularSystem),
+            Ok(local) => {
+                let (x, y) = local.apply(v).into();
+                if strict && !(x.is_finite() && y.is_finite()) {
+                    Err(DeformError::SingularSystem)
+                } else {
+                    Ok((x, y))
+                }
+            }
+            // v is at (or snapped to) a control point, so it follows its translation.
+            Err(index) => {
+                let q = Point::from(controls_q[index]);
+                Ok((q + v - Point::from(controls_p[index])).into())
+            }
+        }
+    }
+}
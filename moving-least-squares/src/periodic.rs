@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deformations of periodic domains, such as 360° panoramas,
+//! where the left and right borders of the image are glued together.
+
+use crate::{deform, DeformMethod, Scalar};
+
+/// Periods of the x and y coordinates, `None` for a non-periodic coordinate.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Periodicity<T> {
+    /// Period of the x coordinate, typically the width of a 360° panorama.
+    pub x: Option<T>,
+    /// Period of the y coordinate.
+    pub y: Option<T>,
+}
+
+impl<T: Scalar> Periodicity<T> {
+    /// Only the x coordinate is periodic, as for a 360° panorama.
+    pub fn horizontal(period: T) -> Self {
+        Self {
+            x: Some(period),
+            y: None,
+        }
+    }
+
+    /// Wrap the periodic coordinates of the point into `[0, period)`.
+    pub fn wrap(&self, (x, y): (T, T)) -> (T, T) {
+        (wrap(x, self.x), wrap(y, self.y))
+    }
+
+    /// Shift bringing the control point to its periodic copy nearest to the point.
+    fn nearest_shift(&self, control: (T, T), point: (T, T)) -> (T, T) {
+        let shift = |c: T, v: T, period: Option<T>| match period {
+            Some(period) => {
+                let half = T::one() / (T::one() + T::one());
+                ((v - c) / period + half).floor() * period
+            }
+            None => T::zero(),
+        };
+        (
+            shift(control.0, point.0, self.x),
+            shift(control.1, point.1, self.y),
+        )
+    }
+}
+
+/// Wrap the coordinate into `[0, period)` if it is periodic.
+fn wrap<T: Scalar>(coordinate: T, period: Option<T>) -> T {
+    match period {
+        Some(period) => coordinate - (coordinate / period).floor() * period,
+        None => coordinate,
+    }
+}
+
+/// Move a given point with a deformation of a periodic domain,
+/// returning its new position wrapped into `[0, period)` along the periodic coordinates.
+///
+/// Each control point acts through its periodic copy nearest to the point,
+/// so a handle close to the seam of a panorama influences both sides of it.
+/// The deformation is thus exactly periodic, but has a small discontinuity
+/// half a period away from each control point, where its weight is low.
+pub fn deform_periodic<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    periodicity: Periodicity<T>,
+) -> (T, T) {
+    let (shifted_p, shifted_q): (Vec<_>, Vec<_>) = controls_p
+        .iter()
+        .zip(controls_q)
+        .map(|(&(px, py), &(qx, qy))| {
+            let (dx, dy) = periodicity.nearest_shift((px, py), point);
+            ((px + dx, py + dy), (qx + dx, qy + dy))
+        })
+        .unzip();
+    periodicity.wrap(deform(&shifted_p, &shifted_q, point, method))
+}
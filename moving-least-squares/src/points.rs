@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deformations of points of other types than tuples,
+//! such as arrays or the vectors of other math libraries.
+
+use crate::{deform, DeformMethod, Scalar};
+
+/// 2D point type convertible from and into a `(x, y)` tuple.
+///
+/// It is implemented for tuples and `[T; 2]` arrays,
+/// and for `glam::Vec2` and `glam::DVec2` with the `glam` feature.
+pub trait Point2Like<T> {
+    /// Coordinates of the point.
+    fn to_tuple(&self) -> (T, T);
+
+    /// Point with the given coordinates.
+    fn from_tuple(xy: (T, T)) -> Self;
+}
+
+impl<T: Scalar> Point2Like<T> for (T, T) {
+    fn to_tuple(&self) -> (T, T) {
+        *self
+    }
+    fn from_tuple(xy: (T, T)) -> Self {
+        xy
+    }
+}
+
+impl<T: Scalar> Point2Like<T> for [T; 2] {
+    fn to_tuple(&self) -> (T, T) {
+        (self[0], self[1])
+    }
+    fn from_tuple((x, y): (T, T)) -> Self {
+        [x, y]
+    }
+}
+
+#[cfg(feature = "glam")]
+impl Point2Like<f32> for glam::Vec2 {
+    fn to_tuple(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+    fn from_tuple((x, y): (f32, f32)) -> Self {
+        glam::Vec2::new(x, y)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl Point2Like<f64> for glam::DVec2 {
+    fn to_tuple(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+    fn from_tuple((x, y): (f64, f64)) -> Self {
+        glam::DVec2::new(x, y)
+    }
+}
+
+/// Same as [`deform`], but with any point type implementing [`Point2Like`].
+///
+/// The control points are converted to tuples at every call,
+/// so prefer tuples when deforming many points.
+pub fn deform_points<T: Scalar, P: Point2Like<T>>(
+    controls_p: &[P], // p in the paper
+    controls_q: &[P], // q in the paper
+    point: P,         // v in the paper
+    method: DeformMethod,
+) -> P {
+    let to_tuples = |points: &[P]| -> Vec<(T, T)> { points.iter().map(P::to_tuple).collect() };
+    P::from_tuple(deform(
+        &to_tuples(controls_p),
+        &to_tuples(controls_q),
+        point.to_tuple(),
+        method,
+    ))
+}
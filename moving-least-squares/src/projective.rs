@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Projective MLS deformation, where the local transformation is a homography.
+//!
+//! Following the same framework than the affine deformation of the paper,
+//! the best homography H minimizing sum_i w_i |H(p_i) - q_i|^2 is estimated at each point.
+//! Since this is not a linear least squares problem, we instead minimize
+//! the usual algebraic error of the direct linear transformation (DLT),
+//! with the last coefficient of H fixed to 1:
+//!
+//! h11 x + h12 y + h13 - q_x (h31 x + h32 y) = q_x
+//! h21 x + h22 y + h23 - q_y (h31 x + h32 y) = q_y
+//!
+//! For a better conditioning, this is solved with coordinates centered
+//! on the weighted centroids p* and q*, and scaled to unit size.
+
+use crate::{centered, inverse_sqr_dist, Centered, Point, Scalar};
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations.
+///
+/// The estimated transformation is a 2D homography, which can represent perspective effects.
+/// It needs at least 4 control points, with no 3 of them colinear.
+pub fn deform_projective<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+) -> (T, T) {
+    let v = Point::from(point);
+    let mut c = Centered::new();
+    if let Err(index) = centered(controls_p, controls_q, v, &mut c, inverse_sqr_dist) {
+        return controls_q[index];
+    }
+
+    // Scale of the centered points, for the conditioning of the linear system.
+    let rms = |hat: &[Point<T>]| {
+        let sqr: T = c
+            .w_all
+            .iter()
+            .zip(hat)
+            .map(|(&w, h)| w * h.sqr_norm())
+            .sum();
+        (sqr / c.w_sum).sqrt()
+    };
+    let (scale_p, scale_q) = (rms(&c.p_hat), rms(&c.q_hat));
+
+    // Accumulate the normal equations of the weighted DLT.
+    let mut ata = [[T::zero(); 8]; 8];
+    let mut atb = [T::zero(); 8];
+    for ((&w, &ph), &qh) in c.w_all.iter().zip(&c.p_hat).zip(&c.q_hat) {
+        let (x, y) = (ph.x / scale_p, ph.y / scale_p);
+        let (u, v) = (qh.x / scale_q, qh.y / scale_q);
+        let zero = T::zero();
+        let one = T::one();
+        let rows = [
+            ([x, y, one, zero, zero, zero, -u * x, -u * y], u),
+            ([zero, zero, zero, x, y, one, -v * x, -v * y], v),
+        ];
+        for (a, b) in rows.iter() {
+            for i in 0..8 {
+                let wai = w * a[i];
+                for (j, &aj) in a.iter().enumerate() {
+                    ata[i][j] = ata[i][j] + wai * aj;
+                }
+                atb[i] = atb[i] + wai * *b;
+            }
+        }
+    }
+    let h = solve(ata, atb);
+
+    // Apply the homography to the normalized point.
+    let (x, y) = ((v.x - c.p_star.x) / scale_p, (v.y - c.p_star.y) / scale_p);
+    let denom = h[6] * x + h[7] * y + T::one();
+    let u = (h[0] * x + h[1] * y + h[2]) / denom;
+    let w = (h[3] * x + h[4] * y + h[5]) / denom;
+    (u * scale_q + c.q_star.x, w * scale_q + c.q_star.y)
+}
+
+/// Solve the 8x8 linear system A x = b with Gaussian elimination and partial pivoting.
+/// The result is NaN or infinite if A is singular.
+fn solve<T: Scalar>(mut a: [[T; 8]; 8], mut b: [T; 8]) -> [T; 8] {
+    for col in 0..8 {
+        // Swap the row with the largest pivot into place.
+        let pivot = (col..8)
+            .max_by(|&i, &j| {
+                a[i][col]
+                    .abs()
+                    .partial_cmp(&a[j][col].abs())
+                    .unwrap_or(core::cmp::Ordering::Equal)
+            })
+            .unwrap_or(col);
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        // Eliminate the coefficients below the pivot.
+        for row in col + 1..8 {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col];
+            for (x, &p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x = *x - factor * p;
+            }
+            b[row] = b[row] - factor * b[col];
+        }
+    }
+    // Back substitution.
+    let mut x = [T::zero(); 8];
+    for row in (0..8).rev() {
+        let sum: T = (row + 1..8).map(|j| a[row][j] * x[j]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x
+}
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Quality metrics of the deformation, to detect folds, strong distortions,
+//! and imprecise interpolation of the control points.
+
+use crate::{deform, deform_grid, jacobian, DeformMethod, Scalar};
+
+/// Local distortion of the deformation, computed from its Jacobian matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distortion<T> {
+    /// Determinant of the Jacobian, which is the local area change ratio.
+    /// It is negative where the deformation folds over itself.
+    pub det: T,
+    /// Largest and smallest stretch factors, which are the singular values of the Jacobian.
+    pub stretch: (T, T),
+    /// Maximum change of an angle, in radians, between 0 and π.
+    /// It is 0 for locally conformal deformations, such as similarities.
+    pub angular: T,
+}
+
+impl<T: Scalar> Distortion<T> {
+    /// Compute the distortion of a 2x2 Jacobian matrix
+    /// with `jacobian[i][j]` the derivative of the coordinate i with respect to the coordinate j.
+    pub fn from_jacobian(jacobian: [[T; 2]; 2]) -> Self {
+        let [[a, b], [c, d]] = jacobian;
+        let two = T::one() + T::one();
+        // Closed form singular values of a 2x2 matrix.
+        let (e, f) = ((a + d) / two, (a - d) / two);
+        let (g, h) = ((c + b) / two, (c - b) / two);
+        let q = (e * e + h * h).sqrt();
+        let r = (f * f + g * g).sqrt();
+        let (s_max, s_min) = (q + r, (q - r).abs());
+        // Tissot: the maximum angular distortion is 2 asin((s_max - s_min) / (s_max + s_min)).
+        let sin = (s_max - s_min) / (s_max + s_min);
+        let angular = two * sin.atan2((T::one() - sin * sin).sqrt());
+        Self {
+            det: a * d - b * c,
+            stretch: (s_max, s_min),
+            angular,
+        }
+    }
+
+    /// True if the deformation folds over itself (or collapses) here,
+    /// meaning that the warped image will have flips.
+    pub fn is_folded(&self) -> bool {
+        self.det <= T::zero()
+    }
+}
+
+/// Compute the distortion of the deformation at the given point.
+pub fn distortion<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> Distortion<T> {
+    Distortion::from_jacobian(jacobian(controls_p, controls_q, point, method))
+}
+
+/// Compute the distortion of each cell of the regular grid of [`deform_grid`],
+/// from the deformation of its corners.
+///
+/// The grid has `(width - 1) / step + 1` columns of cells and `(height - 1) / step + 1` rows,
+/// returned row by row.
+/// This is cheaper than evaluating [`distortion`] at every pixel,
+/// and catches folds as soon as a whole cell is flipped.
+pub fn distortion_grid<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    width: u32,
+    height: u32,
+    step: u32,
+    method: DeformMethod,
+) -> Vec<Distortion<T>> {
+    let grid = deform_grid(controls_p, controls_q, width, height, step, method);
+    let nb_columns = ((width.max(1) - 1) / step + 2) as usize;
+    let nb_rows = ((height.max(1) - 1) / step + 2) as usize;
+    let two_step = T::from_f64(2.0 * step as f64);
+    let mut cells = Vec::with_capacity((nb_columns - 1) * (nb_rows - 1));
+    for row in 0..nb_rows - 1 {
+        let top = row * nb_columns;
+        let bot = top + nb_columns;
+        for column in 0..nb_columns - 1 {
+            let (tl, tr) = (grid[top + column], grid[top + column + 1]);
+            let (bl, br) = (grid[bot + column], grid[bot + column + 1]);
+            // Derivatives averaged over the two opposite edges of the cell.
+            let dx = ((tr.0 - tl.0 + br.0 - bl.0), (tr.1 - tl.1 + br.1 - bl.1));
+            let dy = ((bl.0 - tl.0 + br.0 - tr.0), (bl.1 - tl.1 + br.1 - tr.1));
+            cells.push(Distortion::from_jacobian([
+                [dx.0 / two_step, dy.0 / two_step],
+                [dx.1 / two_step, dy.1 / two_step],
+            ]));
+        }
+    }
+    cells
+}
+
+/// Report of how precisely the deformation interpolates the control points,
+/// and of its smoothness on a regular grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResidualReport<T> {
+    /// For each control point, the distance between its displaced location q
+    /// and the limit of the deformation when approaching its original location p.
+    pub control_errors: Vec<T>,
+    /// Largest of the control errors.
+    pub max_control_error: T,
+    /// Largest norm of the second differences of the deformed grid, divided by the squared step.
+    /// It is 0 for a deformation that is globally affine, and grows with its curvature.
+    pub max_second_difference: T,
+    /// Mean norm of the second differences of the deformed grid, divided by the squared step.
+    pub mean_second_difference: T,
+}
+
+/// Compute a [`ResidualReport`] of the deformation,
+/// with the smoothness estimated on the regular grid of [`deform_grid`].
+///
+/// The deformation at a control point is special-cased to return its displaced location,
+/// so the interpolation error is instead measured by averaging the deformation
+/// at four points around the control point, which is its limit up to second order terms.
+/// Affine deformations with few control points, or nearly colinear ones,
+/// may have a limit that differs from the displaced control point.
+pub fn residual_report<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    method: DeformMethod,
+    width: u32,
+    height: u32,
+    step: u32,
+) -> ResidualReport<T> {
+    let f = |v| deform(controls_p, controls_q, v, method);
+    let control_errors: Vec<T> = controls_p
+        .iter()
+        .zip(controls_q)
+        .map(|(&(x, y), &(qx, qy))| {
+            let h = T::epsilon().cbrt() * T::one().max(x.abs()).max(y.abs());
+            let around = [f((x + h, y)), f((x - h, y)), f((x, y + h)), f((x, y - h))];
+            let four = T::from_f64(4.0);
+            let mean_x = around.iter().map(|a| a.0).sum::<T>() / four;
+            let mean_y = around.iter().map(|a| a.1).sum::<T>() / four;
+            ((mean_x - qx) * (mean_x - qx) + (mean_y - qy) * (mean_y - qy)).sqrt()
+        })
+        .collect();
+    let max_control_error = control_errors.iter().fold(T::zero(), |m, &e| m.max(e));
+
+    // Second differences along both axes of the deformed grid.
+    let grid = deform_grid(controls_p, controls_q, width, height, step, method);
+    let nb_columns = ((width.max(1) - 1) / step + 2) as usize;
+    let nb_rows = grid.len() / nb_columns;
+    let sqr_step = T::from_f64((step * step) as f64);
+    let second_difference = |a: (T, T), b: (T, T), c: (T, T)| {
+        let (dx, dy) = (a.0 - b.0 - b.0 + c.0, a.1 - b.1 - b.1 + c.1);
+        (dx * dx + dy * dy).sqrt() / sqr_step
+    };
+    let mut max_second_difference = T::zero();
+    let mut sum_second_difference = T::zero();
+    let mut count = 0;
+    for row in 0..nb_rows {
+        for column in 0..nb_columns {
+            let i = row * nb_columns + column;
+            let mut differences = Vec::with_capacity(2);
+            if column >= 1 && column + 1 < nb_columns {
+                differences.push(second_difference(grid[i - 1], grid[i], grid[i + 1]));
+            }
+            if row >= 1 && row + 1 < nb_rows {
+                let (above, below) = (grid[i - nb_columns], grid[i + nb_columns]);
+                differences.push(second_difference(above, grid[i], below));
+            }
+            for d in differences {
+                max_second_difference = max_second_difference.max(d);
+                sum_second_difference = sum_second_difference + d;
+                count += 1;
+            }
+        }
+    }
+    let mean_second_difference = if count > 0 {
+        sum_second_difference / T::from_f64(count as f64)
+    } else {
+        T::zero()
+    };
+    ResidualReport {
+        control_errors,
+        max_control_error,
+        max_second_difference,
+        mean_second_difference,
+    }
+}
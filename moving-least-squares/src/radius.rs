@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deformations where each control point only has influence within a given radius.
+//!
+//! With the inverse squared distance weights of the paper, every control point
+//! pulls on the whole domain, so that a handle moved on one side of an image
+//! also shifts the other side.
+//! Here the weight of a control point fades smoothly to exactly zero at its radius:
+//!
+//! w = (1 - d^2 / r^2)^2 / d^2 if d < r, and 0 otherwise.
+
+use crate::{local_weighted, Centered, DeformMethod, Point, Scalar};
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control points
+/// into their displaced locations,
+/// where each control point `i` only has influence within `radii[i]` of its original position.
+///
+/// Points outside of the radius of every control point are left unchanged.
+/// When there are not enough control points within range to estimate the
+/// transformation of the chosen method (for example a single one),
+/// the point is only translated by q* - p*.
+///
+/// Panics if `radii` does not have the same length as the control points.
+pub fn deform_bounded<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    radii: &[T],
+    point: (T, T), // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    assert_eq!(
+        radii.len(),
+        controls_p.len(),
+        "There must be one radius per control point"
+    );
+    let v = Point::from(point);
+    let in_range = |(&p, &r): (&(T, T), &T)| (Point::from(p) - v).sqr_norm() < r * r;
+    if !controls_p.iter().zip(radii).any(in_range) {
+        return point;
+    }
+    let weight = |i: usize, sqr_dist: T| {
+        let sqr_radius = radii[i] * radii[i];
+        if sqr_dist < sqr_radius {
+            let fade = T::one() - sqr_dist / sqr_radius;
+            fade * fade / sqr_dist
+        } else {
+            T::zero()
+        }
+    };
+    let mut c = Centered::new();
+    match local_weighted(controls_p, controls_q, v, method, &mut c, weight) {
+        Ok(local) if local.singular => (v - local.p_star + local.q_star).into(),
+        Ok(local) => local.apply(v).into(),
+        Err(index) => controls_q[index],
+    }
+}
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Polygonal regions of the plane that must stay rigid, or fixed, during the deformation,
+//! such as glasses on a face.
+//!
+//! The rigid transformation of each region is the best fit of the unconstrained
+//! deformation of its border (the identity for fixed regions).
+//! Points sampled along the border are then added as control points moved by that transformation,
+//! so that the deformation outside of the region blends continuously into it,
+//! while points inside the region are exactly moved by the rigid transformation.
+
+use crate::{deform, rotation_vector, Affine2, ControlPoints, DeformMethod, Point, Scalar};
+
+/// Polygonal region, which moves rigidly with the deformation or stays fixed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RigidRegion<T> {
+    /// Vertices of the polygon, in any order along its border.
+    pub polygon: Vec<(T, T)>,
+    /// True if the region must stay where it is instead of moving rigidly.
+    pub fixed: bool,
+}
+
+impl<T: Scalar> RigidRegion<T> {
+    /// Region moving rigidly with the deformation.
+    pub fn rigid(polygon: Vec<(T, T)>) -> Self {
+        Self {
+            polygon,
+            fixed: false,
+        }
+    }
+
+    /// Region staying where it is.
+    pub fn fixed(polygon: Vec<(T, T)>) -> Self {
+        Self {
+            polygon,
+            fixed: true,
+        }
+    }
+
+    /// True if the point is inside the polygon, with the even-odd rule.
+    pub fn contains(&self, (x, y): (T, T)) -> bool {
+        let n = self.polygon.len();
+        let mut inside = false;
+        for i in 0..n {
+            let (xa, ya) = self.polygon[i];
+            let (xb, yb) = self.polygon[(i + 1) % n];
+            if (ya > y) != (yb > y) && x < xa + (y - ya) / (yb - ya) * (xb - xa) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Points along the border of the polygon, with its vertices,
+    /// and at most `spacing` apart from each other.
+    fn border_samples(&self, spacing: T) -> Vec<(T, T)> {
+        let n = self.polygon.len();
+        let mut samples = Vec::new();
+        for i in 0..n {
+            let a = Point::from(self.polygon[i]);
+            let b = Point::from(self.polygon[(i + 1) % n]);
+            let length = (b - a).sqr_norm().sqrt();
+            let nb_steps = (length / spacing).to_f64().ceil().max(1.0);
+            for k in 0..nb_steps as usize {
+                let t = T::from_f64(k as f64 / nb_steps);
+                samples.push((a + (b - a) * t).into());
+            }
+        }
+        samples
+    }
+}
+
+/// Deformation with rigid or fixed regions, precomputed for a given set of control points.
+#[derive(Debug, Clone)]
+pub struct RigidRegions<T> {
+    /// Regions with their rigid transformations.
+    regions: Vec<(RigidRegion<T>, Affine2<T>)>,
+    /// Control points outside of the regions, completed by their border samples.
+    controls: ControlPoints<T>,
+    method: DeformMethod,
+}
+
+impl<T: Scalar> RigidRegions<T> {
+    /// Fit the rigid transformation of each region,
+    /// and sample their borders every `spacing` (in pixels for an image).
+    ///
+    /// Original control points inside a region are ignored outside of it,
+    /// where they would conflict with the constraints of its border.
+    pub fn new(
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        regions: &[RigidRegion<T>],
+        spacing: T,
+        method: DeformMethod,
+    ) -> Self {
+        let mut controls = ControlPoints::new(Vec::new(), Vec::new());
+        for (&p, &q) in controls_p.iter().zip(controls_q) {
+            if !regions.iter().any(|region| region.contains(p)) {
+                controls.push(p, q);
+            }
+        }
+        let regions: Vec<_> = regions
+            .iter()
+            .map(|region| {
+                let samples = region.border_samples(spacing);
+                let transform = if region.fixed {
+                    Affine2::identity()
+                } else {
+                    let deformed: Vec<_> = samples
+                        .iter()
+                        .map(|&s| deform(controls_p, controls_q, s, method))
+                        .collect();
+                    fit_rigid(&samples, &deformed)
+                };
+                for &s in &samples {
+                    controls.push(s, transform.apply(s));
+                }
+                (region.clone(), transform)
+            })
+            .collect();
+        Self {
+            regions,
+            controls,
+            method,
+        }
+    }
+
+    /// Rigid transformation of each region, in the order they were given.
+    pub fn transforms(&self) -> Vec<Affine2<T>> {
+        self.regions.iter().map(|(_, t)| *t).collect()
+    }
+
+    /// Move a given point with the deformation.
+    pub fn deform(&self, point: (T, T)) -> (T, T) {
+        match self.regions.iter().find(|(r, _)| r.contains(point)) {
+            Some((_, transform)) => transform.apply(point),
+            None => self.controls.deform(point, self.method),
+        }
+    }
+}
+
+/// Move a given point with a deformation keeping the given regions rigid or fixed,
+/// with their borders sampled every `spacing`.
+///
+/// To deform many points, create a [`RigidRegions`] once instead.
+pub fn deform_with_rigid_regions<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    regions: &[RigidRegion<T>],
+    point: (T, T), // v in the paper
+    method: DeformMethod,
+    spacing: T,
+) -> (T, T) {
+    RigidRegions::new(controls_p, controls_q, regions, spacing, method).deform(point)
+}
+
+/// Rigid transformation best mapping the points `from` onto the points `to`,
+/// in the least squares sense.
+fn fit_rigid<T: Scalar>(from: &[(T, T)], to: &[(T, T)]) -> Affine2<T> {
+    let n = T::from_f64(from.len() as f64);
+    let mean = |points: &[(T, T)]| {
+        points.iter().map(|&p| Point::from(p)).sum::<Point<T>>() * (T::one() / n)
+    };
+    let (from_star, to_star) = (mean(from), mean(to));
+    let hats = from
+        .iter()
+        .zip(to)
+        .map(|(&a, &b)| (Point::from(a) - from_star, Point::from(b) - to_star));
+    let r = rotation_vector(core::iter::repeat(T::one()), hats);
+    let norm = r.sqr_norm().sqrt();
+    if norm == T::zero() {
+        return Affine2::translation((to_star - from_star).into());
+    }
+    let (cos, sin) = (r.x / norm, r.y / norm);
+    let rotated_star = Point {
+        x: cos * from_star.x - sin * from_star.y,
+        y: sin * from_star.x + cos * from_star.y,
+    };
+    Affine2 {
+        matrix: [[cos, -sin], [sin, cos]],
+        translation: (to_star - rotated_star).into(),
+    }
+}
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handles with a prescribed rotation, in addition to their displacement.
+//!
+//! The rotation of the local rigid transformation (or similarity) at a point
+//! is the angle of the mu_r vector of eq (8), a weighted sum over all control points.
+//! A handle with a target rotation θ_i adds to this sum a term w_i s (cos θ_i, sin θ_i),
+//! where s is the weighted mean of |p_hat_j| |q_hat_j|, such that it counts
+//! as much as a typical control point.
+//! Close to the handle, its weight dominates and the rotation is θ_i,
+//! giving "twist" brushes, while the influence fades away with the distance.
+
+use crate::{centered, inverse_sqr_dist, rotation_vector, Centered, Point, Scalar};
+
+/// Same as [`deform_rigid`](crate::deform_rigid), but where each control point `i`
+/// with `rotations[i]` not `None` also constrains the local rotation
+/// around it to that angle (in radians).
+///
+/// Panics if `rotations` does not have the same length as the control points.
+pub fn deform_rigid_with_rotations<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    rotations: &[Option<T>],
+    point: (T, T), // v in the paper
+) -> (T, T) {
+    deform_with_rotations(controls_p, controls_q, rotations, point, false)
+}
+
+/// Same as [`deform_similarity`](crate::deform_similarity), but where each control point `i`
+/// with `rotations[i]` not `None` also constrains the local rotation
+/// around it to that angle (in radians).
+/// The local scaling is the one of the similarity without rotation constraints.
+///
+/// Panics if `rotations` does not have the same length as the control points.
+pub fn deform_similarity_with_rotations<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    rotations: &[Option<T>],
+    point: (T, T), // v in the paper
+) -> (T, T) {
+    deform_with_rotations(controls_p, controls_q, rotations, point, true)
+}
+
+/// Shared implementation of the rigid and similarity deformations with rotation constraints.
+fn deform_with_rotations<T: Scalar>(
+    controls_p: &[(T, T)],
+    controls_q: &[(T, T)],
+    rotations: &[Option<T>],
+    point: (T, T),
+    similarity: bool,
+) -> (T, T) {
+    assert_eq!(
+        rotations.len(),
+        controls_p.len(),
+        "There must be one optional rotation per control point"
+    );
+    let v = Point::from(point);
+    let mut c = Centered::new();
+    if let Err(index) = centered(controls_p, controls_q, v, &mut c, inverse_sqr_dist) {
+        return controls_q[index];
+    }
+    let hats = || c.p_hat.iter().zip(&c.q_hat).map(|(&p, &q)| (p, q));
+    let mu_r_vec = rotation_vector(c.w_all.iter().copied(), hats());
+
+    // Typical magnitude of the contribution of a control point.
+    let scale: T = c
+        .w_all
+        .iter()
+        .zip(hats())
+        .map(|(&w, (p, q))| w * (p.sqr_norm() * q.sqr_norm()).sqrt())
+        .sum::<T>()
+        / c.w_sum;
+    let constraints: Point<T> = c
+        .w_all
+        .iter()
+        .zip(rotations)
+        .filter_map(|(&w, rotation)| {
+            rotation.map(|angle| {
+                let (sin, cos) = angle.sin_cos();
+                Point { x: cos, y: sin } * (w * scale)
+            })
+        })
+        .sum();
+    let r = mu_r_vec + constraints;
+
+    // Rotate v - p* by the angle of the constrained rotation vector.
+    let norm = r.sqr_norm().sqrt();
+    let (cos, sin) = (r.x / norm, r.y / norm);
+    let stretch = if similarity {
+        let mu_s: T = c
+            .w_all
+            .iter()
+            .zip(&c.p_hat)
+            .map(|(&w, p)| w * p.sqr_norm())
+            .sum();
+        mu_r_vec.sqr_norm().sqrt() / mu_s
+    } else {
+        T::one()
+    };
+    let v_hat = v - c.p_star;
+    let rotated = Point {
+        x: cos * v_hat.x - sin * v_hat.y,
+        y: sin * v_hat.x + cos * v_hat.y,
+    };
+    (rotated * stretch + c.q_star).into()
+}
@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reusable buffers, to deform many points without allocations.
+
+use crate::{deform_with, Centered, DeformMethod, Scalar};
+
+/// Scratch buffers for the weights and centered control points,
+/// reused from one deformed point to the next.
+///
+/// Every call to [`deform`](crate::deform) allocates these buffers,
+/// which is a significant part of the time when deforming every pixel of an image.
+pub struct DeformScratch<T> {
+    centered: Centered<T>,
+}
+
+impl<T: Scalar> Default for DeformScratch<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> DeformScratch<T> {
+    /// Empty buffers, growing to the number of control points at the first deformation.
+    pub fn new() -> Self {
+        Self {
+            centered: Centered::new(),
+        }
+    }
+
+    /// Same as [`deform`](crate::deform), reusing these buffers.
+    pub fn deform(
+        &mut self,
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        point: (T, T),         // v in the paper
+        method: DeformMethod,
+    ) -> (T, T) {
+        deform_with(controls_p, controls_q, point, method, &mut self.centered)
+    }
+}
@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deformations controlled by line segments instead of points (section 5 of the paper).
+//!
+//! Each handle is a segment `(a, b)` in the original configuration,
+//! displaced to the segment `(c, d)`.
+//! The sums over control points of the point version become integrals
+//! along the segments, which are computed in closed form.
+//!
+//! Open polylines, such as curves annotating eyebrows or road centerlines,
+//! are handled by splitting them into corresponding segments.
+
+use crate::{DeformMethod, Mat2, Point, Scalar};
+use core::iter::Sum;
+use core::ops::{Add, Mul};
+
+/// Segment given by its two extremities `(a, b)`.
+pub type Segment<T> = ((T, T), (T, T));
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control segments
+/// into their displaced locations.
+///
+/// The estimated transformation is an affine 2d transformation.
+pub fn deform_affine<T: Scalar>(
+    segments_p: &[Segment<T>], // (a, b) in the paper
+    segments_q: &[Segment<T>], // (c, d) in the paper
+    point: (T, T),             // v in the paper
+) -> (T, T) {
+    let v = Point::from(point);
+    let Hat {
+        deltas,
+        p_hat,
+        q_hat,
+        p_star,
+        q_star,
+    } = match centered(segments_p, segments_q, v) {
+        Centered::OnSegment(q) => return q.into(),
+        Centered::Hat(hat) => hat,
+    };
+
+    // Compute the affine matrix M.
+    // mp is a 2x2 matrix.
+    let mp: Mat2<T> = integrate(&deltas, &p_hat, &p_hat, |p1, p2| p1.times_transpose(p2));
+    // Compute the second part of M.
+    let mq: Mat2<T> = integrate(&deltas, &p_hat, &q_hat, |p, q| p.times_transpose(q));
+
+    // Finally compute the projection of our original point.
+    ((v - p_star).transpose_mul(mp.inv()).transpose_mul(mq) + q_star).into()
+}
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control segments
+/// into their displaced locations.
+///
+/// The estimated transformation is a 2D similarity.
+pub fn deform_similarity<T: Scalar>(
+    segments_p: &[Segment<T>], // (a, b) in the paper
+    segments_q: &[Segment<T>], // (c, d) in the paper
+    point: (T, T),             // v in the paper
+) -> (T, T) {
+    let v = Point::from(point);
+    let Hat {
+        deltas,
+        p_hat,
+        q_hat,
+        p_star,
+        q_star,
+    } = match centered(segments_p, segments_q, v) {
+        Centered::OnSegment(q) => return q.into(),
+        Centered::Hat(hat) => hat,
+    };
+
+    // Compute mu_s.
+    let mu_s: T = integrate(&deltas, &p_hat, &p_hat, |p1, p2| p1.dot(p2));
+
+    // Compute M.
+    let m: Mat2<T> = integrate(&deltas, &p_hat, &q_hat, similarity_mat);
+    let m = m * (T::one() / mu_s);
+
+    // Finally compute the projection of our original point.
+    ((v - p_star).transpose_mul(m) + q_star).into()
+}
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control segments
+/// into their displaced locations.
+///
+/// The estimated transformation is a 2D rigid deformation.
+pub fn deform_rigid<T: Scalar>(
+    segments_p: &[Segment<T>], // (a, b) in the paper
+    segments_q: &[Segment<T>], // (c, d) in the paper
+    point: (T, T),             // v in the paper
+) -> (T, T) {
+    let v = Point::from(point);
+    let Hat {
+        deltas,
+        p_hat,
+        q_hat,
+        p_star,
+        q_star,
+    } = match centered(segments_p, segments_q, v) {
+        Centered::OnSegment(q) => return q.into(),
+        Centered::Hat(hat) => hat,
+    };
+
+    // Compute mu_r.
+    let mu_r_vec: Point<T> = integrate(&deltas, &p_hat, &q_hat, |p, q| {
+        let p_perp = Point { x: -p.y, y: p.x };
+        Point {
+            x: q.dot(p),
+            y: q.dot(p_perp),
+        }
+    });
+    let mu_r = mu_r_vec.sqr_norm().sqrt();
+
+    // Compute M.
+    let m: Mat2<T> = integrate(&deltas, &p_hat, &q_hat, similarity_mat);
+    let m = m * (T::one() / mu_r);
+
+    // Finally compute the projection of our original point.
+    ((v - p_star).transpose_mul(m) + q_star).into()
+}
+
+/// Move a given point from its original position to its new position
+/// according to the deformation that transforms the original control segments
+/// into their displaced locations.
+///
+/// The estimated transformation is the one of the chosen method.
+pub fn deform<T: Scalar>(
+    segments_p: &[Segment<T>], // (a, b) in the paper
+    segments_q: &[Segment<T>], // (c, d) in the paper
+    point: (T, T),             // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    match method {
+        DeformMethod::Affine => deform_affine(segments_p, segments_q, point),
+        DeformMethod::Similarity => deform_similarity(segments_p, segments_q, point),
+        DeformMethod::Rigid => deform_rigid(segments_p, segments_q, point),
+    }
+}
+
+/// Move a given point according to the deformation that transforms
+/// each original polyline into its displaced polyline.
+///
+/// The polylines are split into segments with [`polyline_segments`] at every call,
+/// so prefer calling it once and then [`deform`] when deforming many points.
+///
+/// Panics if there are not as many displaced polylines as original ones.
+pub fn deform_polylines<T: Scalar>(
+    polylines_p: &[Vec<(T, T)>],
+    polylines_q: &[Vec<(T, T)>],
+    point: (T, T), // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    let (segments_p, segments_q) = polylines_segments(polylines_p, polylines_q);
+    deform(&segments_p, &segments_q, point, method)
+}
+
+/// Split every pair of original and displaced polylines into corresponding segments,
+/// with [`polyline_segments`].
+///
+/// Panics if there are not as many displaced polylines as original ones.
+pub fn polylines_segments<T: Scalar>(
+    polylines_p: &[Vec<(T, T)>],
+    polylines_q: &[Vec<(T, T)>],
+) -> (Vec<Segment<T>>, Vec<Segment<T>>) {
+    assert_eq!(
+        polylines_p.len(),
+        polylines_q.len(),
+        "There must be one displaced polyline per original polyline"
+    );
+    let mut segments_p = Vec::new();
+    let mut segments_q = Vec::new();
+    for (polyline_p, polyline_q) in polylines_p.iter().zip(polylines_q) {
+        let (p, q) = polyline_segments(polyline_p, polyline_q);
+        segments_p.extend(p);
+        segments_q.extend(q);
+    }
+    (segments_p, segments_q)
+}
+
+/// Split an original polyline and its displaced polyline into corresponding segments.
+///
+/// The two polylines may have different numbers of vertices.
+/// They are matched by their normalized arc length, such that a point at some fraction
+/// of the original curve length goes to the same fraction of the displaced curve length.
+/// Both polylines are cut at the vertices of each other, so the segments follow them exactly.
+pub fn polyline_segments<T: Scalar>(
+    polyline_p: &[(T, T)],
+    polyline_q: &[(T, T)],
+) -> (Vec<Segment<T>>, Vec<Segment<T>>) {
+    if polyline_p.len() < 2 || polyline_q.len() < 2 {
+        return (Vec::new(), Vec::new());
+    }
+    let params_p = arc_length_params(polyline_p);
+    let params_q = arc_length_params(polyline_q);
+    let mut params: Vec<T> = params_p.iter().chain(&params_q).copied().collect();
+    params.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+    params.dedup();
+    let resample = |polyline: &[(T, T)], polyline_params: &[T]| -> Vec<(T, T)> {
+        params
+            .iter()
+            .map(|&t| sample(polyline, polyline_params, t))
+            .collect()
+    };
+    let into_segments = |points: Vec<(T, T)>| -> Vec<Segment<T>> {
+        points.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    (
+        into_segments(resample(polyline_p, &params_p)),
+        into_segments(resample(polyline_q, &params_q)),
+    )
+}
+
+/// Normalized arc length of every vertex of the polyline, from 0 to 1.
+///
+/// A polyline of zero length is parameterized by its vertices indices instead.
+fn arc_length_params<T: Scalar>(polyline: &[(T, T)]) -> Vec<T> {
+    let mut params = Vec::with_capacity(polyline.len());
+    let mut length = T::zero();
+    params.push(length);
+    for w in polyline.windows(2) {
+        length = length + (Point::from(w[1]) - Point::from(w[0])).sqr_norm().sqrt();
+        params.push(length);
+    }
+    if length > T::zero() {
+        params.iter().map(|&l| l / length).collect()
+    } else {
+        let last = T::from_f64((polyline.len() - 1) as f64);
+        (0..polyline.len())
+            .map(|i| T::from_f64(i as f64) / last)
+            .collect()
+    }
+}
+
+/// Point of the polyline at the normalized arc length t.
+fn sample<T: Scalar>(polyline: &[(T, T)], params: &[T], t: T) -> (T, T) {
+    // Index of the segment containing t.
+    let i = params[1..params.len() - 1]
+        .iter()
+        .take_while(|&&param| param <= t)
+        .count();
+    let (t0, t1) = (params[i], params[i + 1]);
+    let (a, b) = (Point::from(polyline[i]), Point::from(polyline[i + 1]));
+    if t1 <= t0 {
+        return a.into();
+    }
+    (a + (b - a) * ((t - t0) / (t1 - t0))).into()
+}
+
+/// Integrals of the weight along a segment.
+///
+/// With p(t) = (1-t) a + t b for t in [0,1], and w(t) = |b - a| / |p(t) - v|^2,
+/// they are the coefficients of the symmetric matrix W of section 5:
+///
+/// | d00  d01 |   | ∫ w(t) (1-t)^2   ∫ w(t) (1-t) t |
+/// | d01  d11 | = | ∫ w(t) (1-t) t   ∫ w(t) t^2     |
+#[derive(Clone, Copy)]
+struct Deltas<T> {
+    d00: T,
+    d01: T,
+    d11: T,
+}
+
+impl<T: Scalar> Deltas<T> {
+    /// ∫ w(t) dt
+    fn sum(self) -> T {
+        self.d00 + self.d01 + self.d01 + self.d11
+    }
+}
+
+/// Result of the weights integration along a segment.
+enum SegmentWeights<T> {
+    /// v lies on the segment, at p(t).
+    OnSegment(T),
+    /// v is away from the segment.
+    Deltas(Deltas<T>),
+}
+
+/// Compute in closed form the integrals of the weight along the segment [a, b].
+///
+/// Writing |p(t) - v|^2 = A t^2 + 2 B t + C, we have
+/// (with D = sqrt(A C - B^2) the absolute cross product of b - a and a - v):
+///
+///  - I0 = ∫ 1 / |p(t) - v|^2 = atan2(D, B + C) / D
+///  - I1 = ∫ t / |p(t) - v|^2 = (ln((A + 2B + C) / C) / 2 - B * I0) / A
+///  - I2 = ∫ t^2 / |p(t) - v|^2 = (1 - 2 B * I1 - C * I0) / A
+fn segment_weights<T: Scalar>(a: Point<T>, b: Point<T>, v: Point<T>) -> SegmentWeights<T> {
+    let ab = b - a;
+    let va = a - v;
+    let coef_a = ab.sqr_norm();
+    let coef_b = ab.dot(va);
+    let coef_c = va.sqr_norm();
+    if coef_a == T::zero() {
+        // A degenerated segment has no length, so no weight.
+        let zero = T::zero();
+        return SegmentWeights::Deltas(Deltas {
+            d00: zero,
+            d01: zero,
+            d11: zero,
+        });
+    }
+    let d = (ab.x * va.y - ab.y * va.x).abs();
+    let end_sqr_dist = coef_a + coef_b + coef_b + coef_c;
+    let i0 = if d == T::zero() {
+        // v is on the line (a, b), and outside of the segment if B + C > 0.
+        T::one() / (coef_b + coef_c)
+    } else {
+        d.atan2(coef_b + coef_c) / d
+    };
+    let i1 = ((end_sqr_dist / coef_c).ln() / (T::one() + T::one()) - coef_b * i0) / coef_a;
+    let i2 = (T::one() - (coef_b + coef_b) * i1 - coef_c * i0) / coef_a;
+    let all_finite = [i0, i1, i2]
+        .iter()
+        .all(|&i| i.is_finite() && i >= T::zero());
+    if !all_finite {
+        // The weights are infinite because v basically lies on the segment.
+        let t = -coef_b / coef_a;
+        let t = if t < T::zero() {
+            T::zero()
+        } else if t > T::one() {
+            T::one()
+        } else {
+            t
+        };
+        return SegmentWeights::OnSegment(t);
+    }
+    let length = coef_a.sqrt();
+    SegmentWeights::Deltas(Deltas {
+        d00: length * (i0 - i1 - i1 + i2),
+        d01: length * (i1 - i2),
+        d11: length * i2,
+    })
+}
+
+/// Segments centered around their weighted centroids.
+struct Hat<T> {
+    deltas: Vec<Deltas<T>>,
+    p_hat: Vec<(Point<T>, Point<T>)>,
+    q_hat: Vec<(Point<T>, Point<T>)>,
+    p_star: Point<T>,
+    q_star: Point<T>,
+}
+
+enum Centered<T> {
+    /// v lies on a control segment and must be mapped to this point.
+    OnSegment(Point<T>),
+    Hat(Hat<T>),
+}
+
+/// Compute the weights and centroids p* and q*, as well as the centered segments.
+fn centered<T: Scalar>(
+    segments_p: &[Segment<T>],
+    segments_q: &[Segment<T>],
+    v: Point<T>,
+) -> Centered<T> {
+    // The weights of a given control segment depends on its distance to the current point.
+    let mut deltas = Vec::with_capacity(segments_p.len());
+    for (&(a, b), &(c, d)) in segments_p.iter().zip(segments_q) {
+        match segment_weights(a.into(), b.into(), v) {
+            SegmentWeights::Deltas(delta) => deltas.push(delta),
+            SegmentWeights::OnSegment(t) => {
+                let (c, d) = (Point::from(c), Point::from(d));
+                return Centered::OnSegment(c + (d - c) * t);
+            }
+        }
+    }
+    let w_sum: T = deltas.iter().map(|delta| delta.sum()).sum();
+
+    // Compute the centroids p* and q*.
+    let centroid = |segments: &[Segment<T>]| -> Point<T> {
+        let w_sum_inv = T::one() / w_sum;
+        deltas
+            .iter()
+            .zip(segments)
+            .map(|(delta, &(a, b))| {
+                Point::from(a) * (delta.d00 + delta.d01) + Point::from(b) * (delta.d01 + delta.d11)
+            })
+            .sum::<Point<T>>()
+            * w_sum_inv
+    };
+    let p_star = centroid(segments_p);
+    let q_star = centroid(segments_q);
+
+    // Compute p_hat and q_hat.
+    let hat = |segments: &[Segment<T>], star: Point<T>| {
+        segments
+            .iter()
+            .map(|&(a, b)| (Point::from(a) - star, Point::from(b) - star))
+            .collect()
+    };
+    Centered::Hat(Hat {
+        p_hat: hat(segments_p, p_star),
+        q_hat: hat(segments_q, q_star),
+        deltas,
+        p_star,
+        q_star,
+    })
+}
+
+/// Integrate along all segments a quantity f(p(t), q(t)) that is bilinear in p and q.
+///
+/// Since p(t) = (1-t) a + t b and q(t) = (1-t) c + t d,
+/// ∫ w(t) f(p(t), q(t)) = d00 f(a, c) + d01 (f(a, d) + f(b, c)) + d11 f(b, d).
+fn integrate<T, X, F>(
+    deltas: &[Deltas<T>],
+    p_hat: &[(Point<T>, Point<T>)],
+    q_hat: &[(Point<T>, Point<T>)],
+    f: F,
+) -> X
+where
+    T: Scalar,
+    X: Add<Output = X> + Mul<T, Output = X> + Sum,
+    F: Fn(Point<T>, Point<T>) -> X,
+{
+    deltas
+        .iter()
+        .zip(p_hat)
+        .zip(q_hat)
+        .map(|((delta, &(a, b)), &(c, d))| {
+            f(a, c) * delta.d00 + (f(a, d) + f(b, c)) * delta.d01 + f(b, d) * delta.d11
+        })
+        .sum()
+}
+
+/// Matrix of the similarity (eq 6) for a pair p, q.
+fn similarity_mat<T: Scalar>(p: Point<T>, q: Point<T>) -> Mat2<T> {
+    let p_mat = Mat2 {
+        m11: p.x,
+        m12: p.y,
+        m21: p.y,
+        m22: -p.x,
+    };
+    let q_mat = Mat2 {
+        m11: q.x,
+        m21: q.y,
+        m12: q.y,
+        m22: -q.x,
+    };
+    p_mat * q_mat
+}
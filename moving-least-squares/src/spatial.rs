@@ -0,0 +1,248 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Spatial index of the control points, to prune far control points.
+//!
+//! With thousands of control points, every deformed point depends on all of them,
+//! while the far ones barely contribute since weights decrease with the squared distance.
+//! The deformations of this module only use the nearest control points,
+//! found with a kd-tree built once from the original control points.
+//!
+//! # Approximation error
+//!
+//! Ignoring control points amounts to setting their weights to zero.
+//! Let d_1 be the distance to the nearest control point,
+//! and d the distance to the closest ignored control point
+//! (the k+1-th nearest one, or the radius of the search).
+//! Each ignored weight is at most 1 / d^2 while the weights sum is at least 1 / d_1^2,
+//! so the relative weight of the m ignored control points is at most m * (d_1 / d)^2.
+//! The centroids p* and q* and the matrix M are weighted means,
+//! so their error is bounded by this relative weight, times the extent of the ignored
+//! control points (and their displacements) around the point.
+//! [`ControlsIndex::ignored_weight_bound`] computes this bound for a given point.
+
+use crate::{deform, DeformMethod, Scalar};
+
+/// Kd-tree of the original control points p.
+pub struct ControlsIndex<T> {
+    /// Nodes of the tree in implicit form: the node of a slice is its middle element,
+    /// and its left and right children are the middles of the left and right halves.
+    nodes: Vec<Node<T>>,
+}
+
+#[derive(Clone, Copy)]
+struct Node<T> {
+    point: (T, T),
+    /// Index of the point in the control points.
+    index: usize,
+}
+
+impl<T: Scalar> ControlsIndex<T> {
+    /// Build the kd-tree of the original control points `controls_p`.
+    pub fn new(controls_p: &[(T, T)]) -> Self {
+        let mut nodes: Vec<Node<T>> = controls_p
+            .iter()
+            .enumerate()
+            .map(|(index, &point)| Node { point, index })
+            .collect();
+        build(&mut nodes, 0);
+        Self { nodes }
+    }
+
+    /// Indices of the `k` control points nearest to the given point,
+    /// sorted from the nearest to the farthest.
+    pub fn nearest(&self, point: (T, T), k: usize) -> Vec<usize> {
+        self.nearest_nodes(point, k)
+            .into_iter()
+            .map(|(_, node)| node.index)
+            .collect()
+    }
+
+    /// Indices of the control points within the given radius of a point,
+    /// in no particular order.
+    pub fn within_radius(&self, point: (T, T), radius: T) -> Vec<usize> {
+        self.nodes_within_radius(point, radius)
+            .into_iter()
+            .map(|node| node.index)
+            .collect()
+    }
+
+    /// Same as [`deform`], but only with the `k` control points nearest to the point.
+    ///
+    /// `controls_q` are all the displaced control points, in the same order as
+    /// the original ones used to build the index.
+    pub fn deform_nearest(
+        &self,
+        controls_q: &[(T, T)],
+        point: (T, T),
+        k: usize,
+        method: DeformMethod,
+    ) -> (T, T) {
+        let subset: Vec<Node<T>> = self
+            .nearest_nodes(point, k)
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect();
+        deform_subset(&subset, controls_q, point, method)
+    }
+
+    /// Same as [`deform`], but only with the control points within the given radius of the point.
+    /// The point is left unchanged if there are none.
+    ///
+    /// `controls_q` are all the displaced control points, in the same order as
+    /// the original ones used to build the index.
+    pub fn deform_within_radius(
+        &self,
+        controls_q: &[(T, T)],
+        point: (T, T),
+        radius: T,
+        method: DeformMethod,
+    ) -> (T, T) {
+        let subset = self.nodes_within_radius(point, radius);
+        if subset.is_empty() {
+            return point;
+        }
+        deform_subset(&subset, controls_q, point, method)
+    }
+
+    /// Upper bound of the relative weight of the control points ignored
+    /// when only using the `k` nearest ones, as explained in the module documentation.
+    pub fn ignored_weight_bound(&self, point: (T, T), k: usize) -> T {
+        let nearest = self.nearest_nodes(point, k + 1);
+        if nearest.len() <= k {
+            // No control point is ignored.
+            T::zero()
+        } else if k == 0 {
+            // All control points are ignored.
+            T::one()
+        } else {
+            let nb_ignored = T::from_f64((self.nodes.len() - k) as f64);
+            nb_ignored * nearest[0].0 / nearest[k].0
+        }
+    }
+
+    /// The k nearest nodes, with their squared distance to the point.
+    fn nearest_nodes(&self, point: (T, T), k: usize) -> Vec<(T, Node<T>)> {
+        let mut best = Vec::with_capacity(k + 1);
+        if k > 0 {
+            nearest(&self.nodes, 0, point, k, &mut best);
+        }
+        best
+    }
+
+    /// The nodes within the given radius of the point.
+    fn nodes_within_radius(&self, point: (T, T), radius: T) -> Vec<Node<T>> {
+        let mut found = Vec::new();
+        within_radius(&self.nodes, 0, point, radius * radius, &mut found);
+        found
+    }
+}
+
+/// Deform the point with a subset of the control points.
+fn deform_subset<T: Scalar>(
+    subset: &[Node<T>],
+    controls_q: &[(T, T)],
+    point: (T, T),
+    method: DeformMethod,
+) -> (T, T) {
+    let sub_p: Vec<(T, T)> = subset.iter().map(|node| node.point).collect();
+    let sub_q: Vec<(T, T)> = subset.iter().map(|node| controls_q[node.index]).collect();
+    deform(&sub_p, &sub_q, point, method)
+}
+
+/// Recursively reorder the nodes into an implicit kd-tree,
+/// splitting along x at even depths and along y at odd depths.
+fn build<T: Scalar>(nodes: &mut [Node<T>], depth: usize) {
+    if nodes.len() <= 1 {
+        return;
+    }
+    let mid = nodes.len() / 2;
+    let key = |node: &Node<T>| coordinate(node.point, depth);
+    nodes.select_nth_unstable_by(mid, |a, b| {
+        key(a)
+            .partial_cmp(&key(b))
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+    let (left, right) = nodes.split_at_mut(mid);
+    build(left, depth + 1);
+    build(&mut right[1..], depth + 1);
+}
+
+/// Coordinate used to split the tree at a given depth.
+fn coordinate<T>((x, y): (T, T), depth: usize) -> T {
+    if depth % 2 == 0 {
+        x
+    } else {
+        y
+    }
+}
+
+/// Squared distance between two points.
+fn sqr_dist<T: Scalar>((x1, y1): (T, T), (x2, y2): (T, T)) -> T {
+    (x1 - x2) * (x1 - x2) + (y1 - y2) * (y1 - y2)
+}
+
+/// Recursive k nearest neighbors search.
+/// `best` is kept sorted by increasing distance.
+fn nearest<T: Scalar>(
+    nodes: &[Node<T>],
+    depth: usize,
+    point: (T, T),
+    k: usize,
+    best: &mut Vec<(T, Node<T>)>,
+) {
+    if nodes.is_empty() {
+        return;
+    }
+    let mid = nodes.len() / 2;
+    let node = nodes[mid];
+
+    // Insert the current node if it is among the k nearest.
+    let d = sqr_dist(node.point, point);
+    if best.len() < k || d < best[best.len() - 1].0 {
+        let position = best
+            .iter()
+            .position(|&(bd, _)| d < bd)
+            .unwrap_or(best.len());
+        best.insert(position, (d, node));
+        best.truncate(k);
+    }
+
+    // Visit the side containing the point first, then the other one if needed.
+    let diff = coordinate(point, depth) - coordinate(node.point, depth);
+    let (left, right) = (&nodes[..mid], &nodes[mid + 1..]);
+    let (near, far) = if diff < T::zero() {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    nearest(near, depth + 1, point, k, best);
+    if best.len() < k || diff * diff < best[best.len() - 1].0 {
+        nearest(far, depth + 1, point, k, best);
+    }
+}
+
+/// Recursive search of the points within a radius.
+fn within_radius<T: Scalar>(
+    nodes: &[Node<T>],
+    depth: usize,
+    point: (T, T),
+    sqr_radius: T,
+    found: &mut Vec<Node<T>>,
+) {
+    if nodes.is_empty() {
+        return;
+    }
+    let mid = nodes.len() / 2;
+    let node = nodes[mid];
+    if sqr_dist(node.point, point) <= sqr_radius {
+        found.push(node);
+    }
+    let diff = coordinate(point, depth) - coordinate(node.point, depth);
+    let (left, right) = (&nodes[..mid], &nodes[mid + 1..]);
+    if diff <= T::zero() || diff * diff <= sqr_radius {
+        within_radius(left, depth + 1, point, sqr_radius, found);
+    }
+    if diff >= T::zero() || diff * diff <= sqr_radius {
+        within_radius(right, depth + 1, point, sqr_radius, found);
+    }
+}
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deformations on the sphere, for equirectangular panoramas.
+//!
+//! The control points are (longitude, latitude) pairs in radians.
+//! The deformation at a point v is computed in the tangent plane of the sphere at v,
+//! where the control points are mapped with the azimuthal equidistant projection
+//! (the logarithm map), which preserves their geodesic distances and directions from v.
+//! The 2D MLS deformation of the origin of that plane is then mapped back on the sphere.
+//! Unlike a plain 2D deformation of the equirectangular image,
+//! this is undistorted near the poles and across the ±180° longitude seam.
+
+use crate::{deform, DeformMethod, Scalar};
+
+/// Move a given point of the sphere, given by its (longitude, latitude) in radians,
+/// from its original position to its new position according to the deformation
+/// that transforms the original control points into their displaced locations.
+///
+/// The returned longitude is in `[-π, π]`.
+/// Control points antipodal to the point have an undefined direction and are ignored.
+pub fn deform_spherical<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+) -> (T, T) {
+    let v = to_unit(point);
+    let (e1, e2) = tangent_basis(v);
+    let log = |lon_lat| {
+        let x = to_unit(lon_lat);
+        let cos = dot(v, x);
+        // Component of x orthogonal to v, with norm sin(angle).
+        let ortho = sub(x, scale(v, cos));
+        let sin = dot(ortho, ortho).sqrt();
+        if sin == T::zero() {
+            return (T::zero(), T::zero());
+        }
+        let factor = sin.atan2(cos) / sin;
+        (dot(ortho, e1) * factor, dot(ortho, e2) * factor)
+    };
+    let tangent_p: Vec<(T, T)> = controls_p.iter().map(|&p| log(p)).collect();
+    let tangent_q: Vec<(T, T)> = controls_q.iter().map(|&q| log(q)).collect();
+    let (tx, ty) = deform(&tangent_p, &tangent_q, (T::zero(), T::zero()), method);
+
+    // Exponential map of the tangent vector back on the sphere.
+    let angle = (tx * tx + ty * ty).sqrt();
+    if angle == T::zero() {
+        return from_unit(v);
+    }
+    let (sin, cos) = angle.sin_cos();
+    let direction = add(scale(e1, tx / angle), scale(e2, ty / angle));
+    from_unit(add(scale(v, cos), scale(direction, sin)))
+}
+
+/// Same as [`deform_spherical`], but with pixel coordinates of an equirectangular
+/// image of the given dimensions, where the x axis spans the longitudes
+/// from -180° to 180° and the y axis the latitudes from 90° to -90°.
+///
+/// The returned x coordinate is wrapped into `[0, width)`.
+pub fn deform_equirectangular<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    width: u32,
+    height: u32,
+) -> (T, T) {
+    let pi = T::from_f64(std::f64::consts::PI);
+    let two = T::one() + T::one();
+    let (w, h) = (T::from_f64(width as f64), T::from_f64(height as f64));
+    let to_lon_lat = |&(x, y): &(T, T)| (x / w * two * pi - pi, pi / two - y / h * pi);
+    let to_pixel = |(lon, lat): (T, T)| {
+        let x = (lon + pi) / (two * pi) * w;
+        (x - (x / w).floor() * w, (pi / two - lat) / pi * h)
+    };
+    let p: Vec<(T, T)> = controls_p.iter().map(to_lon_lat).collect();
+    let q: Vec<(T, T)> = controls_q.iter().map(to_lon_lat).collect();
+    to_pixel(deform_spherical(&p, &q, to_lon_lat(&point), method))
+}
+
+// 3D vectors helper ###########################################################
+
+type Vec3<T> = (T, T, T);
+
+/// Unit vector of the given (longitude, latitude).
+fn to_unit<T: Scalar>((lon, lat): (T, T)) -> Vec3<T> {
+    let (sin_lon, cos_lon) = lon.sin_cos();
+    let (sin_lat, cos_lat) = lat.sin_cos();
+    (cos_lat * cos_lon, cos_lat * sin_lon, sin_lat)
+}
+
+/// (Longitude, latitude) of the given unit vector.
+fn from_unit<T: Scalar>((x, y, z): Vec3<T>) -> (T, T) {
+    (y.atan2(x), z.atan2((x * x + y * y).sqrt()))
+}
+
+/// Orthonormal basis of the plane tangent to the sphere at the unit vector v.
+///
+/// Any basis works since the MLS deformations are invariant by rotation,
+/// so this one is chosen to be well defined everywhere, including at the poles.
+fn tangent_basis<T: Scalar>(v: Vec3<T>) -> (Vec3<T>, Vec3<T>) {
+    // Cross v with the axis the least aligned with it.
+    let (ax, ay, az) = (v.0.abs(), v.1.abs(), v.2.abs());
+    let (o, i) = (T::zero(), T::one());
+    let axis = if ax <= ay && ax <= az {
+        (i, o, o)
+    } else if ay <= az {
+        (o, i, o)
+    } else {
+        (o, o, i)
+    };
+    let e1 = cross(axis, v);
+    let e1 = scale(e1, T::one() / dot(e1, e1).sqrt());
+    (e1, cross(v, e1))
+}
+
+fn dot<T: Scalar>(a: Vec3<T>, b: Vec3<T>) -> T {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+fn cross<T: Scalar>(a: Vec3<T>, b: Vec3<T>) -> Vec3<T> {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+fn add<T: Scalar>(a: Vec3<T>, b: Vec3<T>) -> Vec3<T> {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+fn sub<T: Scalar>(a: Vec3<T>, b: Vec3<T>) -> Vec3<T> {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+fn scale<T: Scalar>(a: Vec3<T>, s: T) -> Vec3<T> {
+    (a.0 * s, a.1 * s, a.2 * s)
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deformations symmetric with respect to an axis, typically for faces.
+
+use crate::{deform, ControlPoints, DeformMethod, Point, Scalar};
+
+/// Axis of a mirror symmetry, given by a point on it and its direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymmetryAxis<T> {
+    /// Point on the axis.
+    pub point: (T, T),
+    /// Direction of the axis, not necessarily normalized.
+    pub direction: (T, T),
+}
+
+impl<T: Scalar> SymmetryAxis<T> {
+    /// Vertical axis at the given x coordinate.
+    pub fn vertical(x: T) -> Self {
+        Self {
+            point: (x, T::zero()),
+            direction: (T::zero(), T::one()),
+        }
+    }
+
+    /// Mirror image of a point with respect to the axis.
+    pub fn mirror(&self, point: (T, T)) -> (T, T) {
+        let (o, d) = (Point::from(self.point), Point::from(self.direction));
+        let v = Point::from(point) - o;
+        // Projection of v on the axis, then reflection across it.
+        let projected = d * (v.dot(d) / d.sqr_norm());
+        (o + projected + projected - v).into()
+    }
+
+    /// Add the mirror image of every pair of control points, such that the
+    /// configuration is symmetric: moving one handle moves its mirror image accordingly.
+    ///
+    /// Control points on the axis are not duplicated.
+    pub fn symmetric_controls(
+        &self,
+        controls_p: &[(T, T)],
+        controls_q: &[(T, T)],
+    ) -> ControlPoints<T> {
+        let mut controls = ControlPoints::new(controls_p.to_vec(), controls_q.to_vec());
+        for (&p, &q) in controls_p.iter().zip(controls_q) {
+            let p_mirror = self.mirror(p);
+            let on_axis = (Point::from(p_mirror) - Point::from(p)).sqr_norm()
+                <= T::epsilon() * Point::from(p).sqr_norm();
+            if !on_axis {
+                controls.push(p_mirror, self.mirror(q));
+            }
+        }
+        controls
+    }
+}
+
+/// Move a given point with a deformation symmetric with respect to the axis.
+///
+/// The control points are completed with their mirror images (see [`SymmetryAxis::symmetric_controls`]),
+/// and the result is symmetrized, so that the deformation of the mirror image
+/// of a point is exactly the mirror image of its deformation,
+/// even with control points on the axis displaced away from it.
+pub fn deform_symmetric<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    point: (T, T),         // v in the paper
+    method: DeformMethod,
+    axis: SymmetryAxis<T>,
+) -> (T, T) {
+    let controls = axis.symmetric_controls(controls_p, controls_q);
+    let direct = Point::from(controls.deform(point, method));
+    let mirrored = deform(controls.src(), controls.dst(), axis.mirror(point), method);
+    let half = T::one() / (T::one() + T::one());
+    ((direct + Point::from(axis.mirror(mirrored))) * half).into()
+}
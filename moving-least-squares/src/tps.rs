@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Thin-plate spline (TPS) deformation, an alternative to MLS,
+//! to compare their smoothness on the same control points.
+//!
+//! The thin-plate spline is the interpolation of the control points
+//! minimizing the bending energy of the deformation:
+//!
+//! f(v) = a_0 + a_x v_x + a_y v_y + sum_i w_i U(|v - p_i|), with U(r) = r^2 ln(r^2)
+//!
+//! The coefficients are solved once for all the control points,
+//! in a linear system of size n + 3 for n control points.
+//! Contrary to MLS, every control point has a global influence.
+
+use crate::error::check_inputs;
+use crate::fit::solve;
+use crate::{DeformError, Scalar};
+
+/// Thin-plate spline moving the original control points p to their displaced positions q.
+///
+/// It is computed in `f64` with coordinates centered and scaled to unit size,
+/// for the conditioning of its linear system, whatever the scalar type of the points.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThinPlateSpline {
+    /// Normalized original control points.
+    centers: Vec<(f64, f64)>,
+    /// Weights of the radial basis function of each control point, for x and y.
+    weights: Vec<(f64, f64)>,
+    /// Coefficients of the affine part, for 1, v_x and v_y.
+    affine: [(f64, f64); 3],
+    /// Centroid of the original control points.
+    center: (f64, f64),
+    /// Scale of the original control points.
+    scale: f64,
+}
+
+impl ThinPlateSpline {
+    /// Thin-plate spline exactly interpolating the control points.
+    ///
+    /// It needs at least 3 control points, not all colinear.
+    pub fn new<T: Scalar>(
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+    ) -> Result<Self, DeformError> {
+        Self::regularized(controls_p, controls_q, T::zero())
+    }
+
+    /// Thin-plate spline approximating the control points,
+    /// smoother for a bigger regularization `lambda`, and tending to an affine transformation.
+    ///
+    /// The regularization is relative to control points scaled to unit size.
+    pub fn regularized<T: Scalar>(
+        controls_p: &[(T, T)], // p in the paper
+        controls_q: &[(T, T)], // q in the paper
+        lambda: T,
+    ) -> Result<Self, DeformError> {
+        let origin = (T::zero(), T::zero());
+        check_inputs(controls_p, controls_q, origin)?;
+        if controls_p.len() < 3 {
+            return Err(DeformError::SingularSystem);
+        }
+        let to_f64 = |&(x, y): &(T, T)| (x.to_f64(), y.to_f64());
+        let p: Vec<(f64, f64)> = controls_p.iter().map(to_f64).collect();
+        let q: Vec<(f64, f64)> = controls_q.iter().map(to_f64).collect();
+
+        // Normalize the original control points to unit size.
+        let n = p.len();
+        let center = (
+            p.iter().map(|c| c.0).sum::<f64>() / n as f64,
+            p.iter().map(|c| c.1).sum::<f64>() / n as f64,
+        );
+        let sqr: f64 = p
+            .iter()
+            .map(|c| (c.0 - center.0).powi(2) + (c.1 - center.1).powi(2))
+            .sum();
+        let scale = (sqr / n as f64).sqrt();
+        if scale == 0.0 {
+            return Err(DeformError::SingularSystem);
+        }
+        let centers: Vec<(f64, f64)> = p
+            .iter()
+            .map(|c| ((c.0 - center.0) / scale, (c.1 - center.1) / scale))
+            .collect();
+
+        // The affine part is undetermined if all the control points are colinear,
+        // with a singular covariance, whose trace is 1 once normalized.
+        let (sxx, syy, sxy) = centers.iter().fold((0.0, 0.0, 0.0), |(sxx, syy, sxy), c| {
+            (sxx + c.0 * c.0, syy + c.1 * c.1, sxy + c.0 * c.1)
+        });
+        let det = (sxx * syy - sxy * sxy) / (n * n) as f64;
+        if det < 1e-10 {
+            return Err(DeformError::SingularSystem);
+        }
+
+        // Linear system [K + lambda I, P; P^T, 0] [w; a] = [q; 0].
+        let size = n + 3;
+        let mut a = vec![vec![0.0; size]; size];
+        for (i, &ci) in centers.iter().enumerate() {
+            for (j, &cj) in centers.iter().enumerate() {
+                a[i][j] = radial(ci, cj);
+            }
+            a[i][i] += lambda.to_f64();
+            let row = [1.0, ci.0, ci.1];
+            for (k, &value) in row.iter().enumerate() {
+                a[i][n + k] = value;
+                a[n + k][i] = value;
+            }
+        }
+        let solve_for = |coordinate: fn(&(f64, f64)) -> f64| {
+            let mut b: Vec<f64> = q.iter().map(coordinate).collect();
+            b.extend([0.0; 3]);
+            solve(a.clone(), b)
+        };
+        let xs = solve_for(|c| c.0);
+        let ys = solve_for(|c| c.1);
+        if !xs.iter().chain(&ys).all(|c| c.is_finite()) {
+            return Err(DeformError::SingularSystem);
+        }
+        let weights = xs[..n].iter().copied().zip(ys[..n].iter().copied());
+        Ok(Self {
+            centers,
+            weights: weights.collect(),
+            affine: [
+                (xs[n], ys[n]),
+                (xs[n + 1], ys[n + 1]),
+                (xs[n + 2], ys[n + 2]),
+            ],
+            center,
+            scale,
+        })
+    }
+
+    /// Move a given point with the thin-plate spline.
+    pub fn deform<T: Scalar>(&self, (x, y): (T, T)) -> (T, T) {
+        let v = (
+            (x.to_f64() - self.center.0) / self.scale,
+            (y.to_f64() - self.center.1) / self.scale,
+        );
+        let [a0, ax, ay] = self.affine;
+        let mut fx = a0.0 + ax.0 * v.0 + ay.0 * v.1;
+        let mut fy = a0.1 + ax.1 * v.0 + ay.1 * v.1;
+        for (&c, &(wx, wy)) in self.centers.iter().zip(&self.weights) {
+            let u = radial(v, c);
+            fx += wx * u;
+            fy += wy * u;
+        }
+        (T::from_f64(fx), T::from_f64(fy))
+    }
+}
+
+/// Radial basis function U(r) = r^2 ln(r^2) of the distance between two points.
+fn radial(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let sqr = (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    if sqr == 0.0 {
+        0.0
+    } else {
+        sqr * sqr.ln()
+    }
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Local affine transformations estimated by the MLS deformations.
+
+use crate::{Local, Point, Scalar};
+
+/// Affine 2D transformation, mapping a point x to `matrix * x + translation`,
+/// where points are column vectors.
+///
+/// This is the local transformation l_v estimated by the MLS deformation at a point v.
+/// It can be reused to transform tangent vectors, normals, or a small patch around v.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Affine2<T> {
+    /// Linear part of the transformation, with `matrix[row][col]` coefficients.
+    pub matrix: [[T; 2]; 2],
+    /// Translation part of the transformation.
+    pub translation: (T, T),
+}
+
+impl<T: Scalar> Affine2<T> {
+    /// Identity transformation.
+    pub fn identity() -> Self {
+        Self::translation((T::zero(), T::zero()))
+    }
+
+    /// Pure translation.
+    pub fn translation(translation: (T, T)) -> Self {
+        let (zero, one) = (T::zero(), T::one());
+        Self {
+            matrix: [[one, zero], [zero, one]],
+            translation,
+        }
+    }
+
+    /// Apply the transformation to a point.
+    pub fn apply(&self, (x, y): (T, T)) -> (T, T) {
+        let [[m11, m12], [m21, m22]] = self.matrix;
+        let (tx, ty) = self.translation;
+        (m11 * x + m12 * y + tx, m21 * x + m22 * y + ty)
+    }
+
+    /// Apply only the linear part of the transformation to a vector,
+    /// typically a tangent vector.
+    pub fn apply_vector(&self, (x, y): (T, T)) -> (T, T) {
+        let [[m11, m12], [m21, m22]] = self.matrix;
+        (m11 * x + m12 * y, m21 * x + m22 * y)
+    }
+
+    /// Convert the local transformation (or the index of the control point
+    /// coinciding with v) into an affine transformation.
+    pub(crate) fn from_local(
+        local: Result<Local<T>, usize>,
+        controls_p: &[(T, T)],
+        controls_q: &[(T, T)],
+    ) -> Self {
+        match local {
+            Ok(Local {
+                p_star, q_star, m, ..
+            }) => {
+                // l(x) = (x - p*) M + q* with x a line vector,
+                // so the matrix for column vectors is the transpose of M.
+                let translation = q_star - p_star.transpose_mul(m);
+                Self {
+                    matrix: [[m.m11, m.m21], [m.m12, m.m22]],
+                    translation: translation.into(),
+                }
+            }
+            Err(index) => {
+                let p = Point::from(controls_p[index]);
+                let q = Point::from(controls_q[index]);
+                Self::translation((q - p).into())
+            }
+        }
+    }
+}
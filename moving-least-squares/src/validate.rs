@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Validation of the control points, reporting all issues at once.
+
+use crate::{DeformMethod, Point, Scalar};
+use std::fmt;
+
+/// Problem with the control points, preventing a meaningful deformation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlIssue {
+    /// There are not as many displaced control points as original ones.
+    MismatchedLengths {
+        /// Number of original control points.
+        nb_p: usize,
+        /// Number of displaced control points.
+        nb_q: usize,
+    },
+    /// There is no control point.
+    Empty,
+    /// A coordinate of the original control point at this index is NaN or infinite.
+    NonFiniteP(usize),
+    /// A coordinate of the displaced control point at this index is NaN or infinite.
+    NonFiniteQ(usize),
+    /// The original control points at these two indices are at the same location.
+    DuplicatedP(usize, usize),
+    /// All original control points are at the same location,
+    /// so no rotation nor scaling can be estimated.
+    AllCoincident,
+    /// All original control points are (almost) on the same line,
+    /// so the affine deformation is degenerate.
+    Colinear,
+}
+
+impl fmt::Display for ControlIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlIssue::MismatchedLengths { nb_p, nb_q } => write!(
+                f,
+                "there are {} original control points but {} displaced ones",
+                nb_p, nb_q
+            ),
+            ControlIssue::Empty => write!(f, "there is no control point"),
+            ControlIssue::NonFiniteP(i) => {
+                write!(f, "original control point {} is NaN or infinite", i)
+            }
+            ControlIssue::NonFiniteQ(i) => {
+                write!(f, "displaced control point {} is NaN or infinite", i)
+            }
+            ControlIssue::DuplicatedP(i, j) => {
+                write!(f, "original control points {} and {} are the same", i, j)
+            }
+            ControlIssue::AllCoincident => {
+                write!(f, "all original control points are at the same location")
+            }
+            ControlIssue::Colinear => write!(f, "all original control points are colinear"),
+        }
+    }
+}
+
+/// Check the control points for problems that would produce NaN
+/// or meaningless deformations with any method, and report all of them.
+///
+/// This checks the lengths, non-finite coordinates, duplicated original control points,
+/// and original control points all at the same location.
+pub fn validate_controls<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+) -> Result<(), Vec<ControlIssue>> {
+    let mut issues = Vec::new();
+    if controls_p.len() != controls_q.len() {
+        issues.push(ControlIssue::MismatchedLengths {
+            nb_p: controls_p.len(),
+            nb_q: controls_q.len(),
+        });
+    }
+    if controls_p.is_empty() {
+        issues.push(ControlIssue::Empty);
+    }
+    let finite = |&(x, y): &(T, T)| x.is_finite() && y.is_finite();
+    let nb_issues = issues.len();
+    issues.extend(
+        (controls_p.iter().enumerate())
+            .filter(|(_, p)| !finite(p))
+            .map(|(i, _)| ControlIssue::NonFiniteP(i)),
+    );
+    let p_all_finite = issues.len() == nb_issues;
+    issues.extend(
+        (controls_q.iter().enumerate())
+            .filter(|(_, q)| !finite(q))
+            .map(|(i, _)| ControlIssue::NonFiniteQ(i)),
+    );
+
+    // The remaining checks are only meaningful with finite original control points.
+    if p_all_finite && !controls_p.is_empty() {
+        issues.extend(duplicates(controls_p));
+        let (sqr_max, _) = spread(controls_p);
+        if sqr_max == T::zero() {
+            issues.push(ControlIssue::AllCoincident);
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Same as [`validate_controls`], but also checking issues specific to the given method,
+/// which is the colinearity of the original control points for the affine deformation.
+pub fn validate_controls_for<T: Scalar>(
+    controls_p: &[(T, T)], // p in the paper
+    controls_q: &[(T, T)], // q in the paper
+    method: DeformMethod,
+) -> Result<(), Vec<ControlIssue>> {
+    let mut issues = validate_controls(controls_p, controls_q)
+        .err()
+        .unwrap_or_default();
+    let checkable = !issues.iter().any(|issue| {
+        matches!(
+            issue,
+            ControlIssue::Empty | ControlIssue::NonFiniteP(_) | ControlIssue::AllCoincident
+        )
+    });
+    if method == DeformMethod::Affine && checkable {
+        let (sqr_max, sqr_min) = spread(controls_p);
+        // Squared spreads ratio under sqrt(eps), where the affine system is ill-conditioned.
+        if sqr_min <= T::epsilon().sqrt() * sqr_max {
+            issues.push(ControlIssue::Colinear);
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Pairs of indices of original control points at the same location.
+fn duplicates<T: Scalar>(controls_p: &[(T, T)]) -> Vec<ControlIssue> {
+    let mut sorted: Vec<(usize, (T, T))> = controls_p.iter().copied().enumerate().collect();
+    sorted.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+    sorted
+        .windows(2)
+        .filter(|pair| pair[0].1 == pair[1].1)
+        .map(|pair| {
+            let (i, j) = (pair[0].0, pair[1].0);
+            ControlIssue::DuplicatedP(i.min(j), i.max(j))
+        })
+        .collect()
+}
+
+/// Largest and smallest eigen values of the covariance matrix of the points,
+/// which are their squared spreads along the principal axes.
+fn spread<T: Scalar>(points: &[(T, T)]) -> (T, T) {
+    let n = T::from_f64(points.len() as f64);
+    let mean = points.iter().map(|&p| Point::from(p)).sum::<Point<T>>() * (T::one() / n);
+    let (mut sxx, mut sxy, mut syy) = (T::zero(), T::zero(), T::zero());
+    for &p in points {
+        let d = Point::from(p) - mean;
+        sxx = sxx + d.x * d.x;
+        sxy = sxy + d.x * d.y;
+        syy = syy + d.y * d.y;
+    }
+    // Eigen values of the symmetric 2x2 matrix.
+    let two = T::one() + T::one();
+    let half_trace = (sxx + syy) / two;
+    let half_diff = (sxx - syy) / two;
+    let root = (half_diff * half_diff + sxy * sxy).sqrt();
+    (half_trace + root, half_trace - root)
+}
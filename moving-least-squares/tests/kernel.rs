@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Checks of the weight kernels far from the control points.
+
+use moving_least_squares::{self as mls, DeformMethod, Kernel, MlsOptions};
+
+const CONTROLS_P: [(f64, f64); 4] = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (10.0, 10.0)];
+const FAR: (f64, f64) = (100.0, 100.0);
+const GAUSSIAN: Kernel<f64> = Kernel::Gaussian { sigma: 2.0 };
+
+/// Control points translated by (3, 4).
+fn translated() -> Vec<(f64, f64)> {
+    CONTROLS_P
+        .iter()
+        .map(|&(x, y)| (x + 3.0, y + 4.0))
+        .collect()
+}
+
+#[test]
+fn gaussian_weights_do_not_vanish_far_from_the_controls() {
+    let weights = mls::influence_weights(&CONTROLS_P, FAR, GAUSSIAN);
+    assert!(weights.iter().all(|w| w.is_finite()));
+    assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    // Almost all the influence is on the closest control point.
+    assert!(weights[3] > 0.99);
+}
+
+#[test]
+fn gaussian_deformations_stay_defined_far_from_the_controls() {
+    let controls_q = translated();
+    for method in [
+        DeformMethod::Affine,
+        DeformMethod::Similarity,
+        DeformMethod::Rigid,
+    ] {
+        let (x, y) = mls::deform_with_kernel(&CONTROLS_P, &controls_q, FAR, method, GAUSSIAN);
+        assert!((x - 103.0).abs() < 1e-6 && (y - 104.0).abs() < 1e-6);
+        let options = MlsOptions::new(method).kernel(GAUSSIAN);
+        let (x, y) = options.try_deform(&CONTROLS_P, &controls_q, FAR).unwrap();
+        assert!((x - 103.0).abs() < 1e-6 && (y - 104.0).abs() < 1e-6);
+    }
+}
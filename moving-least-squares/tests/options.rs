@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Checks of the fallible deformations of MlsOptions.
+
+use moving_least_squares::{DeformError, DeformMethod, MlsOptions};
+
+/// Colinear control points, degenerate for an affine deformation.
+const COLINEAR_P: [(f64, f64); 3] = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)];
+const COLINEAR_Q: [(f64, f64); 3] = [(1.0, 2.0), (2.0, 3.0), (3.0, 4.0)];
+
+#[test]
+fn degenerate_controls_are_reported_with_or_without_radius() {
+    let point = (4.0, 4.0);
+    let options = MlsOptions::new(DeformMethod::Affine);
+    let bounded = options.max_radius(10.0);
+    for options in [options, bounded] {
+        assert_eq!(
+            options.try_deform(&COLINEAR_P, &COLINEAR_Q, point),
+            Err(DeformError::SingularSystem)
+        );
+        assert_eq!(
+            options.try_deform_all(&COLINEAR_P, &COLINEAR_Q, &[point]),
+            Err(DeformError::SingularSystem)
+        );
+    }
+}
+
+#[test]
+fn degenerate_controls_translate_without_check() {
+    let bounded = MlsOptions::new(DeformMethod::Affine).max_radius(10.0);
+    let (x, y) = bounded.deform(&COLINEAR_P, &COLINEAR_Q, (4.0, 4.0));
+    assert!((x - 5.0).abs() < 1e-9 && (y - 6.0).abs() < 1e-9);
+}
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Property-based checks of the guarantees of the MLS deformations,
+//! on many random configurations of control points,
+//! shrunk to a minimal configuration on failure.
+
+use moving_least_squares::{self as mls, DeformMethod};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+type Point = (f64, f64);
+
+const NB_CASES: u32 = 500;
+const METHODS: [DeformMethod; 3] = [
+    DeformMethod::Affine,
+    DeformMethod::Similarity,
+    DeformMethod::Rigid,
+];
+
+/// Random point in the [0, 1000) square.
+fn point() -> impl Strategy<Value = Point> {
+    (0.0..1000.0, 0.0..1000.0)
+}
+
+/// Random valid configuration of 3 to 12 control points, with random displacements.
+fn controls() -> impl Strategy<Value = (Vec<Point>, Vec<Point>)> {
+    let displacement = (-50.0..50.0, -50.0..50.0);
+    prop::collection::vec((point(), displacement), 3..13)
+        .prop_map(|pairs: Vec<(Point, Point)>| {
+            let p: Vec<Point> = pairs.iter().map(|&(v, _)| v).collect();
+            let q = pairs.iter().map(|&((x, y), (dx, dy))| (x + dx, y + dy));
+            (p, q.collect())
+        })
+        .prop_filter(
+            "degenerate configuration",
+            |(p, q): &(Vec<Point>, Vec<Point>)| {
+                mls::validate_controls_for(p, q, DeformMethod::Affine).is_ok()
+            },
+        )
+}
+
+fn distance((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+}
+
+fn is_finite((x, y): (f64, f64)) -> bool {
+    x.is_finite() && y.is_finite()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(NB_CASES))]
+
+    #[test]
+    fn control_points_map_to_their_targets((p, q) in controls()) {
+        for method in METHODS {
+            for (&pi, &qi) in p.iter().zip(&q) {
+                prop_assert_eq!(mls::deform(&p, &q, pi, method), qi);
+            }
+        }
+    }
+
+    #[test]
+    fn deformation_is_continuous_at_control_points((p, q) in controls()) {
+        for method in [DeformMethod::Similarity, DeformMethod::Rigid] {
+            for (&(x, y), &qi) in p.iter().zip(&q) {
+                let near = (x + 1e-6, y - 1e-6);
+                let deformed = mls::deform(&p, &q, near, method);
+                prop_assert!(
+                    distance(deformed, qi) < 1e-3,
+                    "{:?} at {:?} gives {:?} instead of {:?}",
+                    method,
+                    near,
+                    deformed,
+                    qi
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn identity_controls_give_identity((p, _) in controls(), v in point()) {
+        for method in METHODS {
+            let deformed = mls::deform(&p, &p, v, method);
+            prop_assert!(
+                distance(deformed, v) < 1e-6,
+                "{:?} moves {:?} to {:?}",
+                method,
+                v,
+                deformed
+            );
+        }
+    }
+
+    #[test]
+    fn results_are_finite(
+        (p, q) in controls(),
+        // Points inside the domain, and far outside of it.
+        v in (-5000.0..5000.0, -5000.0..5000.0),
+    ) {
+        for method in METHODS {
+            let deformed = mls::deform(&p, &q, v, method);
+            prop_assert!(is_finite(deformed), "{:?} at {:?}", method, v);
+            prop_assert!(mls::try_deform(&p, &q, v, method).is_ok());
+        }
+    }
+
+    #[test]
+    fn coincident_handles_give_finite_results(
+        (mut p, mut q) in controls(),
+        index in any::<Index>(),
+        shift in -1.0..1.0,
+        v in point(),
+    ) {
+        // Duplicate a handle, with the same target or a different one.
+        let i = index.index(p.len());
+        p.push(p[i]);
+        q.push((q[i].0 + shift, q[i].1));
+        for method in METHODS {
+            prop_assert!(is_finite(mls::deform(&p, &q, v, method)), "{:?}", method);
+            prop_assert!(is_finite(mls::deform(&p, &q, p[i], method)), "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn single_handle_is_reported_as_degenerate(p in point(), q in point(), v in point()) {
+        for method in METHODS {
+            let result = mls::try_deform(&[p], &[q], v, method);
+            prop_assert_eq!(
+                result,
+                Err(mls::DeformError::SingularSystem),
+                "{:?}",
+                method
+            );
+            // Still exact at the handle itself.
+            prop_assert_eq!(mls::try_deform(&[p], &[q], p, method), Ok(q));
+        }
+    }
+}